@@ -1,6 +1,6 @@
 use crate::ray::Ray;
 use crate::intervals::Interval;
-use crate::hittables::{hit_record, hittable, hittable_list};
+use crate::hittables::{hit_record, hittable, hittable_list, gather_emitters};
 use crate::aabb::AABB;
 use std::sync::Arc;
 
@@ -78,4 +78,28 @@ impl hittable for BVHNode {
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    fn area(&self) -> f64 {
+        //a node over a single object holds it twice
+        if Arc::ptr_eq(&self.left, &self.right) {
+            return self.left.area();
+        }
+        self.left.area() + self.right.area()
+    }
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+        gather_emitters(&self.left, emitters);
+        //a node over a single object holds it twice
+        if !Arc::ptr_eq(&self.left, &self.right) {
+            gather_emitters(&self.right, emitters);
+        }
+    }
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        if !self.bbox.hit(r, ray_t) {
+            return 1.0;
+        }
+        let left = self.left.transmittance(r, ray_t);
+        if left <= 0.0 || Arc::ptr_eq(&self.left, &self.right) {
+            return left;
+        }
+        left * self.right.transmittance(r, ray_t)
+    }
 }
\ No newline at end of file
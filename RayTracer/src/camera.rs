@@ -3,15 +3,17 @@ use image::{ImageBuffer, RgbImage}; //接收render传回来的图片，在main
 use indicatif::ProgressBar;
 use std::fs::File;
 use crate::vec3::Vec3;
-use crate::ray::Ray;
+use crate::ray::{Ray, RayDifferential};
 use crate::hittables::{hit_record, hittable_list, hittable};
 use crate::intervals::Interval;
 use crate::util;
-use crate::materials::{material, lambertian};
+use crate::environment::environment;
+use crate::lights::light;
+use crate::media::Fog;
+use crate::spectrum::{self, SampledWavelengths};
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{Ordering, AtomicUsize};
 use std::sync::Condvar;
-use crate::SolidColor;
 
 pub struct Camera {
     //basic camera settings
@@ -48,6 +50,18 @@ pub struct Camera {
 
     //background color
     pub background: Vec3,
+    //when set, rays that miss everything see the environment instead of the background color,
+    //and diffuse surfaces sample it directly
+    pub environment: Option<Arc<dyn environment + Send + Sync>>,
+
+    //point, spot and directional lights, reached by shadow rays from diffuse surfaces
+    pub lights: Vec<Arc<dyn light + Send + Sync>>,
+    //emissive objects (e.g. the ceiling light of the cornell box) that diffuse surfaces aim some of their rays at
+    pub area_lights: Option<Arc<dyn hittable + Send + Sync>>,
+    //haze filling the whole scene, camera and bounce rays may scatter in it before reaching a surface
+    pub fog: Option<Arc<Fog>>,
+    //trace wavelengths instead of RGB, needed for dispersion
+    pub spectral: bool,
 
     //Montcarlo
     sqrt_spp: u32,
@@ -93,6 +107,12 @@ impl Camera {
 
             //background color
             background,
+            environment: None,
+
+            lights: Vec::new(),
+            area_lights: None,
+            fog: None,
+            spectral: false,
 
             //Montcarlo
             sqrt_spp: 0,
@@ -153,7 +173,16 @@ impl Camera {
         let ray_dir = pixel_loc - ray_origin;
 
         let ray_time = util::random_f64_0_1();
-        Ray::new(ray_origin, ray_dir, ray_time)
+
+        //neighbouring pixels, narrowed as there are more samples per pixel
+        let diff_scale = util::fmax(0.125, self.recip_sqrt_spp);
+        let differential = RayDifferential {
+            rx_origin: ray_origin,
+            rx_direction: ray_dir + self.delta_u * diff_scale,
+            ry_origin: ray_origin,
+            ry_direction: ray_dir + self.delta_v * diff_scale,
+        };
+        Ray::new_with_differential(ray_origin, ray_dir, ray_time, differential)
     }
     
     
@@ -247,11 +276,21 @@ impl Camera {
                 let mut pixel_color = Vec3::zero();
                 for s_j in 0..self.sqrt_spp {
                     for s_i in 0..self.sqrt_spp {
-                        let ray = self.get_ray(i as f64, j as f64, s_i as u32, s_j as u32);
-                        pixel_color += self.ray_color(&ray, world, self.max_depth);
+                        let mut ray = self.get_ray(i as f64, j as f64, s_i as u32, s_j as u32);
+                        if self.spectral {
+                            //spectral samples are summed up as XYZ
+                            let wavelengths = SampledWavelengths::sample_visible(util::random_f64_0_1());
+                            ray.wavelengths = Some(wavelengths);
+                            pixel_color += wavelengths.to_xyz(self.ray_color(&ray, world, self.max_depth));
+                        } else {
+                            pixel_color += self.ray_color(&ray, world, self.max_depth);
+                        }
                     }
                 }
-                let written_color = pixel_color * (1.0 / (self.sqrt_spp * self.sqrt_spp) as f64);
+                let mut written_color = pixel_color * (1.0 / (self.sqrt_spp * self.sqrt_spp) as f64);
+                if self.spectral {
+                    written_color = spectrum::xyz_to_film_rgb(written_color);
+                }
                 write_buffer[(i - x_min) as usize][(j - y_min) as usize] = written_color;
             }
         }
@@ -272,28 +311,89 @@ impl Camera {
         if depth <= 0 {
             return Vec3::zero();
         }
-        let mut rec: hit_record = hit_record {
-            p: Vec3::zero(),
-            normal: Vec3::zero(),
-            t: 0.0,
-            front_face: false,
-            // mat: Arc::new(lambertian { tex: Arc::new(SolidColor::new(Vec3::zero())) }),
-            mat: Arc::new(lambertian::new_from_texture(Arc::new(SolidColor::new(Vec3::zero())))),
-            u: 0.0,
-            v: 0.0,
-        };
+        let mut rec: hit_record = hit_record::new();
 
-        if !world.hit(r, &mut Interval::new(0.001, f64::INFINITY), &mut rec) {
-            return self.background;
+        let hit_surface = world.hit(r, &mut Interval::new(0.001, f64::INFINITY), &mut rec);
+        //the fog may scatter the ray before it gets to the surface (or to the sky)
+        let fog_collision = match &self.fog {
+            Some(fog) => fog.sample_collision(r, if hit_surface { rec.t } else { f64::INFINITY }).map(|t| (fog, t)),
+            None => None,
+        };
+        if let Some((fog, t)) = fog_collision {
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
+            rec.front_face = true; // also arbitrary
+            rec.u = 0.0;
+            rec.v = 0.0;
+            rec.dpdu = Vec3::zero();
+            rec.dpdv = Vec3::zero();
+            rec.mat = fog.phase_function.clone();
+        } else if !hit_surface {
+            return spectrum::for_ray(match &self.environment {
+                Some(env) => env.value(&r.b_direction.unit_vector()),
+                None => self.background,
+            }, r);
+        } else {
+            rec.compute_differentials(r);
         }
         let mut scattered = Ray::new(Vec3::zero(), Vec3::zero(), 0.0);
         let mut attenuation = Vec3::new(1.0, 1.0, 1.0);
-        let color_from_emission = rec.mat.emitted(rec.u, rec.v, &rec.p);
+        let color_from_emission = spectrum::for_ray(rec.mat.emitted_toward(r, &rec), r);
         let mut color_from_scatter = Vec3::zero();
         
         if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
             return color_from_emission;
         }
+        //the wavelengths go on with the path, a dispersive material may have cut them down to the hero
+        let wavelengths = scattered.wavelengths.or(r.wavelengths);
+        let only_hero = match (&r.wavelengths, &wavelengths) {
+            (Some(before), Some(after)) => !before.secondary_terminated && after.secondary_terminated,
+            _ => false,
+        };
+        let mut color_from_lights = Vec3::zero();
+        //specular materials have no density and can not use light sampling
+        if rec.mat.scattering_pdf(r, &rec, &scattered) > 0.0 {
+            for light in self.lights.iter() {
+                color_from_lights += self.direct_light(light, r, &rec, world);
+            }
+
+            //one-sample MIS: the rays are split evenly between the material, the environment and the area lights,
+            //and weighted by the density of the mixture
+            let env = self.environment.as_ref();
+            let area = self.area_lights.as_ref();
+            let strategies = 1 + env.is_some() as u32 + area.is_some() as u32;
+            if strategies > 1 {
+                let pick = util::random_f64_0_1() * strategies as f64;
+                if pick >= 1.0 {
+                    let direction = match (env, area) {
+                        (Some(env), Some(_)) if pick < 2.0 => env.sample(),
+                        (Some(env), None) => env.sample(),
+                        (_, Some(area)) => area.random(&rec.p),
+                        (None, None) => scattered.b_direction,
+                    };
+                    scattered = Ray::new(rec.p, direction, r.time);
+                }
+                let mut pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+                if let Some(env) = env {
+                    pdf += env.pdf(&scattered.b_direction.unit_vector());
+                }
+                if let Some(area) = area {
+                    pdf += area.pdf_value(&rec.p, &scattered.b_direction);
+                }
+                pdf /= strategies as f64;
+                if pdf <= 0.0 {
+                    return color_from_emission + color_from_lights;
+                }
+                attenuation = rec.mat.eval(r, &rec, &scattered) / pdf;
+            }
+        }
+        attenuation = spectrum::for_ray(attenuation, r);
+        if only_hero {
+            attenuation = Vec3::new(3.0 * attenuation.x, 0.0, 0.0);
+            color_from_lights = Vec3::new(3.0 * color_from_lights.x, 0.0, 0.0);
+        }
+        scattered.wavelengths = wavelengths;
         let new_ray_color = self.ray_color(&scattered, world, depth - 1);
         color_from_scatter =  Vec3::new(
             attenuation.x * new_ray_color.x,
@@ -301,7 +401,35 @@ impl Camera {
             attenuation.z * new_ray_color.z,
         );
         
-        color_from_emission + color_from_scatter
+        color_from_emission + color_from_lights + color_from_scatter
+    }
+
+    //next event estimation for one point, spot or directional light: a shadow ray towards the light
+    fn direct_light(&self, light: &Arc<dyn light + Send + Sync>, r: &Ray, rec: &hit_record, world: &dyn hittable) -> Vec3 {
+        let sample = match light.sample_li(&rec.p) {
+            Some(sample) => sample,
+            None => return Vec3::zero(),
+        };
+        let shadow_ray = Ray::new(rec.p, sample.direction, r.time);
+        let f = rec.mat.eval(r, rec, &shadow_ray);
+        if f.near_zero() {
+            return Vec3::zero();
+        }
+        //surfaces block the shadow ray, smoke and fog only dim it
+        let mut transmittance = world.transmittance(&shadow_ray, &Interval::new(0.001, sample.distance - 0.001));
+        if let Some(fog) = &self.fog {
+            transmittance *= fog.transmittance(&shadow_ray, sample.distance);
+        }
+        if transmittance <= 0.0 {
+            return Vec3::zero();
+        }
+        let f = spectrum::for_ray(f, r);
+        let radiance = spectrum::for_ray(sample.radiance, r);
+        Vec3::new(
+            f.x * radiance.x,
+            f.y * radiance.y,
+            f.z * radiance.z,
+        ) * transmittance
     }
 
     fn sample_square_stratified(&self, i: u32, j: u32) -> Vec3 {
@@ -24,3 +24,16 @@ pub fn write_color(pixel_color: Vec3, img: &mut RgbImage, i: usize, j: usize) {
     // Write the translated [0,255] value of each color component.
 
 }
+
+//relative luminance of a linear sRGB color
+pub fn luminance(c: Vec3) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+//CIE XYZ to linear sRGB (D65 white), negative values are out of gamut and clipped
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Vec3 {
+    let r = 3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z;
+    let g = -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z;
+    let b = 0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z;
+    Vec3::new(r.max(0.0), g.max(0.0), b.max(0.0))
+}
\ No newline at end of file
@@ -0,0 +1,186 @@
+use crate::vec3::Vec3;
+use crate::textures::{ImageData, TextureRegistry};
+use crate::util;
+use std::sync::Arc;
+
+//Light arriving from infinitely far away, looked up by the direction of a ray that hits nothing
+pub trait environment : Send + Sync {
+    //radiance seen along the unit direction dir
+    fn value(&self, dir: &Vec3) -> Vec3;
+    //a direction chosen roughly in proportion to value(), used for light sampling
+    fn sample(&self) -> Vec3 {
+        util::random_on_unit_sphere()
+    }
+    //solid angle density of sample()
+    fn pdf(&self, dir: &Vec3) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+}
+
+//piecewise constant 1D distribution over n cells of [0, 1]
+struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i] / n as f64;
+        }
+        let integral = cdf[n];
+        for i in 1..=n {
+            cdf[i] = if integral > 0.0 { cdf[i] / integral } else { i as f64 / n as f64 };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+    //returns the sample in [0, 1) and its cell
+    fn sample(&self, xi: f64) -> (f64, usize) {
+        //last cell whose cdf is <= xi
+        let cell = self.cdf.partition_point(|c| *c <= xi).clamp(1, self.func.len()) - 1;
+        let width = self.cdf[cell + 1] - self.cdf[cell];
+        let offset = if width > 0.0 { (xi - self.cdf[cell]) / width } else { 0.5 };
+        ((cell as f64 + offset) / self.func.len() as f64, cell)
+    }
+    fn pdf(&self, cell: usize) -> f64 {
+        if self.integral > 0.0 { self.func[cell] / self.integral } else { 1.0 }
+    }
+}
+
+//Equirectangular (latitude-longitude) environment map, usually an .hdr panorama
+//uses the same uv layout as Sphere, so a picture that wraps a sphere correctly also surrounds the scene correctly
+pub struct EnvironmentMap {
+    pub data: Arc<ImageData>,
+    //brightness multiplier
+    pub intensity: f64,
+    //degrees around the y axis, like rotate_y
+    rotation: f64,
+    sin_rot: f64,
+    cos_rot: f64,
+
+    //importance sampling: one distribution over the columns of every row, one over the rows
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl EnvironmentMap {
+    pub fn new(filename: &str) -> Self {
+        Self::new_from_data(TextureRegistry::load(filename))
+    }
+    pub fn new_from_data(data: Arc<ImageData>) -> Self {
+        let (conditional, marginal) = Self::build_distribution(&data);
+        Self {
+            data,
+            intensity: 1.0,
+            rotation: 0.0,
+            sin_rot: 0.0,
+            cos_rot: 1.0,
+
+            conditional,
+            marginal,
+        }
+    }
+    pub fn set_rotation(&mut self, degrees: f64) {
+        self.rotation = degrees;
+        self.sin_rot = degrees.to_radians().sin();
+        self.cos_rot = degrees.to_radians().cos();
+    }
+    pub fn rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    //luminance weighted by sin(theta), rows near the poles cover less solid angle
+    fn build_distribution(data: &ImageData) -> (Vec<Distribution1D>, Distribution1D) {
+        if data.levels.is_empty() {
+            return (vec![Distribution1D::new(vec![1.0])], Distribution1D::new(vec![1.0]));
+        }
+        let level = &data.levels[0];
+        let mut conditional = Vec::with_capacity(level.height as usize);
+        for row in 0..level.height {
+            let theta = std::f64::consts::PI * (1.0 - (row as f64 + 0.5) / level.height as f64);
+            let sin_theta = theta.sin();
+            let func = (0..level.width).map(|col| {
+                let c = Self::decode(data, level.texel(col, row));
+                (0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z) * sin_theta
+            }).collect();
+            conditional.push(Distribution1D::new(func));
+        }
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral).collect());
+        (conditional, marginal)
+    }
+    //8 bit images are gamma encoded like Image assumes
+    fn decode(data: &ImageData, c: Vec3) -> Vec3 {
+        if data.linear {
+            c
+        } else {
+            Vec3::new(c.x * c.x, c.y * c.y, c.z * c.z)
+        }
+    }
+
+    //world direction -> (u, v), rows counted from the top of the picture as in Image
+    fn direction_to_uv(&self, dir: &Vec3) -> (f64, f64) {
+        let d = dir.unit_vector();
+        //undo the rotation
+        let x = self.cos_rot * d.x - self.sin_rot * d.z;
+        let z = self.sin_rot * d.x + self.cos_rot * d.z;
+        let theta = (-d.y).clamp(-1.0, 1.0).acos();
+        let phi = (-z).atan2(x) + std::f64::consts::PI;
+        (phi / (2.0 * std::f64::consts::PI), theta / std::f64::consts::PI)
+    }
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let theta = std::f64::consts::PI * v;
+        let phi = 2.0 * std::f64::consts::PI * u - std::f64::consts::PI;
+        //inverse of Sphere's get_sphere_uv, where phi - pi = atan2(-z, x)
+        let x = phi.cos() * theta.sin();
+        let z = -phi.sin() * theta.sin();
+        let y = -theta.cos();
+        //apply the rotation
+        Vec3::new(self.cos_rot * x + self.sin_rot * z, y, -self.sin_rot * x + self.cos_rot * z)
+    }
+    fn cell(&self, u: f64, v: f64) -> (usize, usize) {
+        let level = &self.data.levels[0];
+        let col = ((u * level.width as f64) as usize).min(level.width as usize - 1);
+        let row = (((1.0 - v) * level.height as f64) as usize).min(level.height as usize - 1);
+        (col, row)
+    }
+}
+
+impl environment for EnvironmentMap {
+    fn value(&self, dir: &Vec3) -> Vec3 {
+        if self.data.levels.is_empty() {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        let (u, v) = self.direction_to_uv(dir);
+        let (col, row) = self.cell(u, v);
+        Self::decode(&self.data, self.data.levels[0].texel(col as u32, row as u32)) * self.intensity
+    }
+    fn sample(&self) -> Vec3 {
+        if self.data.levels.is_empty() || self.marginal.integral <= 0.0 {
+            return util::random_on_unit_sphere();
+        }
+        let (row_pos, row) = self.marginal.sample(util::random_f64_0_1());
+        let (u, _) = self.conditional[row].sample(util::random_f64_0_1());
+        self.uv_to_direction(u, 1.0 - row_pos)
+    }
+    fn pdf(&self, dir: &Vec3) -> f64 {
+        if self.data.levels.is_empty() || self.marginal.integral <= 0.0 {
+            return 1.0 / (4.0 * std::f64::consts::PI);
+        }
+        let (u, v) = self.direction_to_uv(dir);
+        let sin_theta = (std::f64::consts::PI * v).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (col, row) = self.cell(u, v);
+        //density over the unit square, then over solid angle: d(omega) = 2 pi^2 sin(theta) du dv
+        let pdf_uv = self.marginal.pdf(row) * self.conditional[row].pdf(col);
+        pdf_uv / (2.0 * std::f64::consts::PI * std::f64::consts::PI * sin_theta)
+    }
+}
\ No newline at end of file
@@ -1,13 +1,16 @@
-pub use crate::ray::Ray;
+pub use crate::ray::{Ray, RayDifferential};
 pub use crate::vec3::Vec3;
 use crate::Interval;
-use crate::materials::{material, lambertian, isotropic};
+use crate::materials::{material, lambertian, isotropic, volumetric};
+use crate::phase::phase_function;
 use std::sync::Arc;
 use crate::aabb::AABB;
 use crate::SolidColor;
 use crate::util;
-use crate::textures::texture;
+use crate::textures::{texture, UvDifferentials};
+use crate::light_bvh::LightBounds;
 
+#[derive(Clone)]
 pub struct hit_record{
     pub p: Vec3,
     pub normal: Vec3,
@@ -20,9 +23,89 @@ pub struct hit_record{
     //texture
     pub u: f64,
     pub v: f64,
+
+    //partial derivatives of p with respect to u and v, zero if the surface has no uv parameterization
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+
+    //how much u and v change from one pixel to the next, filled by compute_differentials()
+    pub uv_diff: UvDifferentials,
 }
 
 impl hit_record {
+    pub fn new() -> Self {
+        Self {
+            p: Vec3::zero(),
+            normal: Vec3::zero(),
+            t: 0.0,
+            front_face: false,
+            mat: Arc::new(lambertian::new_from_texture(Arc::new(SolidColor::new(Vec3::zero())))),
+            u: 0.0,
+            v: 0.0,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
+            uv_diff: UvDifferentials::default(),
+        }
+    }
+    //estimate the uv footprint of a pixel from the ray differentials (PBRT's approach):
+    //intersect the offset rays with the tangent plane, then express the offsets in terms of dpdu and dpdv
+    pub fn compute_differentials(&mut self, r: &Ray) {
+        self.uv_diff = UvDifferentials::default();
+        let diff = match r.differential {
+            Some(diff) => diff,
+            None => return,
+        };
+        let n = self.normal;
+        let d = n * self.p;
+        let denom_x = n * diff.rx_direction;
+        let denom_y = n * diff.ry_direction;
+        if denom_x.abs() < 1e-12 || denom_y.abs() < 1e-12 {
+            return;
+        }
+        let tx = (d - n * diff.rx_origin) / denom_x;
+        let ty = (d - n * diff.ry_origin) / denom_y;
+        let dpdx = diff.rx_origin + diff.rx_direction * tx - self.p;
+        let dpdy = diff.ry_origin + diff.ry_direction * ty - self.p;
+
+        //least squares solve of dpdx = dpdu * dudx + dpdv * dvdx
+        let a00 = self.dpdu * self.dpdu;
+        let a01 = self.dpdu * self.dpdv;
+        let a11 = self.dpdv * self.dpdv;
+        let det = a00 * a11 - a01 * a01;
+        if det.abs() < 1e-20 {
+            return;
+        }
+        let inv_det = 1.0 / det;
+        let solve = |dp: Vec3| -> (f64, f64) {
+            let b0 = self.dpdu * dp;
+            let b1 = self.dpdv * dp;
+            ((a11 * b0 - a01 * b1) * inv_det, (a00 * b1 - a01 * b0) * inv_det)
+        };
+        let (dudx, dvdx) = solve(dpdx);
+        let (dudy, dvdy) = solve(dpdy);
+        self.uv_diff = UvDifferentials {
+            dudx: if dudx.is_finite() { dudx } else { 0.0 },
+            dvdx: if dvdx.is_finite() { dvdx } else { 0.0 },
+            dudy: if dudy.is_finite() { dudy } else { 0.0 },
+            dvdy: if dvdy.is_finite() { dvdy } else { 0.0 },
+        };
+    }
+    //orthonormal tangent and bitangent around the normal, the tangent follows dpdu and the bitangent points along dpdv
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        let n = self.normal;
+        let mut tangent = self.dpdu - n * (n * self.dpdu);
+        if tangent.length() < 1e-12 {
+            //no uv parameterization, any direction in the tangent plane will do
+            let a = if n.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+            tangent = a.cross(n);
+        }
+        let tangent = tangent.unit_vector();
+        let mut bitangent = n.cross(tangent);
+        if bitangent * self.dpdv < 0.0 {
+            bitangent = bitangent * (-1.0);
+        }
+        (tangent, bitangent)
+    }
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vec3) {
         //we assume here that outward_normal is a unit vector
         self.front_face = (r.b_direction * *outward_normal) < 0.0;
@@ -37,6 +120,40 @@ impl hit_record {
 pub trait hittable : Send + Sync {
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool;
     fn bbox(&self) -> &AABB;
+    //sampling the object as a light: solid angle density of random() seen from origin
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        0.0
+    }
+    //a direction from origin towards a random point of the object
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
+    //surface area of a primitive or of all primitives in a group, 0 for objects that do not know theirs
+    fn area(&self) -> f64 {
+        0.0
+    }
+    //where and how strongly the object emits, only for single emissive primitives (spheres, quads);
+    //objects made of others return None and hand out their parts through collect_emitters()
+    fn light_bounds(&self) -> Option<LightBounds> {
+        None
+    }
+    //push every emissive primitive inside this object, see LightBVH::new_from_world
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {}
+    //fraction of light that gets through along ray_t, for shadow rays
+    //surfaces block it entirely, participating media let part of it through
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        let mut rec = hit_record::new();
+        if self.hit(r, ray_t, &mut rec) { 0.0 } else { 1.0 }
+    }
+}
+
+//an emissive child is an emitter itself, any other child may still contain some
+pub fn gather_emitters(object: &Arc<dyn hittable + Send + Sync>, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+    if object.light_bounds().is_some() {
+        emitters.push(object.clone());
+    } else {
+        object.collect_emitters(emitters);
+    }
 }
 
 pub struct hittable_list{
@@ -79,16 +196,7 @@ impl hittable_list {
 impl hittable for hittable_list {
 
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
-        let mut rec_temp = hit_record {
-            p: Vec3::zero(),
-            normal: Vec3::zero(),
-            t: 0.0,
-            front_face: false,
-            // mat: Arc::new(lambertian { tex: Arc::new(SolidColor::new(Vec3::zero())) }),
-            mat: Arc::new(lambertian::new_from_texture(Arc::new(SolidColor::new(Vec3::zero())))),
-            u: 0.0,
-            v: 0.0,
-        };
+        let mut rec_temp = hit_record::new();
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.tmax;
 
@@ -97,14 +205,7 @@ impl hittable for hittable_list {
                 hit_anything = true;
                 closest_so_far = rec_temp.t;
 
-                //manually copy rec_temp to rec
-                rec.p = rec_temp.p;
-                rec.normal = rec_temp.normal;
-                rec.t = rec_temp.t;
-                rec.front_face = rec_temp.front_face;
-                rec.mat = Arc::clone(&rec_temp.mat);
-                rec.u = rec_temp.u;
-                rec.v = rec_temp.v;
+                *rec = rec_temp.clone();
             }
         }
 
@@ -114,6 +215,39 @@ impl hittable for hittable_list {
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    //every object is picked with the same probability
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = util::random_range_int(0, self.objects.len() as i32) as usize;
+        self.objects[index].random(origin)
+    }
+    fn area(&self) -> f64 {
+        self.objects.iter().map(|object| object.area()).sum()
+    }
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+        for object in self.objects.iter() {
+            gather_emitters(object, emitters);
+        }
+    }
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        let mut transmittance = 1.0;
+        for object in self.objects.iter() {
+            transmittance *= object.transmittance(r, ray_t);
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+        }
+        transmittance
+    }
 }
 
 pub struct translate {
@@ -136,7 +270,14 @@ impl translate {
 
 impl hittable for translate {
     fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
-        let offset_r = Ray::new(r.a_origin - self.offset, r.b_direction, r.time);
+        let mut offset_r = Ray::new(r.a_origin - self.offset, r.b_direction, r.time);
+        if let Some(diff) = r.differential {
+            offset_r.differential = Some(RayDifferential {
+                rx_origin: diff.rx_origin - self.offset,
+                ry_origin: diff.ry_origin - self.offset,
+                ..diff
+            });
+        }
         if !self.object.hit(&offset_r, ray_t, rec) {
             return false;
         }
@@ -150,6 +291,32 @@ impl hittable for translate {
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(&(*origin - self.offset), direction)
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        self.object.random(&(*origin - self.offset))
+    }
+    fn area(&self) -> f64 {
+        self.object.area()
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let mut bounds = self.object.light_bounds()?;
+        bounds.min += self.offset;
+        bounds.max += self.offset;
+        Some(bounds)
+    }
+    //the emitters inside are moved the same way
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+        let mut inner = Vec::new();
+        gather_emitters(&self.object, &mut inner);
+        for emitter in inner {
+            emitters.push(Arc::new(translate::new(emitter, self.offset)));
+        }
+    }
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        self.object.transmittance(&Ray::new(r.a_origin - self.offset, r.b_direction, r.time), ray_t)
+    }
 }
 
 pub struct rotate_y {
@@ -218,7 +385,16 @@ impl hittable for rotate_y {
         direction.x = self.cos_theta * r.b_direction.x - self.sin_theta * r.b_direction.z;
         direction.z = self.sin_theta * r.b_direction.x + self.cos_theta * r.b_direction.z;
 
-        let rotated_r = Ray::new(origin, direction, r.time);
+        let mut rotated_r = Ray::new(origin, direction, r.time);
+        if let Some(diff) = r.differential {
+            let to_object = |v: Vec3| Vec3::new(self.cos_theta * v.x - self.sin_theta * v.z, v.y, self.sin_theta * v.x + self.cos_theta * v.z);
+            rotated_r.differential = Some(RayDifferential {
+                rx_origin: to_object(diff.rx_origin),
+                rx_direction: to_object(diff.rx_direction),
+                ry_origin: to_object(diff.ry_origin),
+                ry_direction: to_object(diff.ry_direction),
+            });
+        }
 
         // Determine whether an intersection exists in object space (and if so, where)
         if !self.object.hit(&rotated_r, ray_t, rec) {
@@ -239,12 +415,48 @@ impl hittable for rotate_y {
         rec.p = p;
         rec.normal = normal;
 
+        // Tangents follow the normal back to world space
+        let to_world = |v: Vec3| Vec3::new(self.cos_theta * v.x + self.sin_theta * v.z, v.y, -self.sin_theta * v.x + self.cos_theta * v.z);
+        rec.dpdu = to_world(rec.dpdu);
+        rec.dpdv = to_world(rec.dpdv);
+
         true
     }
 
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let to_object = |v: Vec3| Vec3::new(self.cos_theta * v.x - self.sin_theta * v.z, v.y, self.sin_theta * v.x + self.cos_theta * v.z);
+        self.object.pdf_value(&to_object(*origin), &to_object(*direction))
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let to_object = |v: Vec3| Vec3::new(self.cos_theta * v.x - self.sin_theta * v.z, v.y, self.sin_theta * v.x + self.cos_theta * v.z);
+        let v = self.object.random(&to_object(*origin));
+        Vec3::new(self.cos_theta * v.x + self.sin_theta * v.z, v.y, -self.sin_theta * v.x + self.cos_theta * v.z)
+    }
+    fn area(&self) -> f64 {
+        self.object.area()
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let bounds = self.object.light_bounds()?;
+        let axis = bounds.axis;
+        let axis = Vec3::new(self.cos_theta * axis.x + self.sin_theta * axis.z, axis.y, -self.sin_theta * axis.x + self.cos_theta * axis.z);
+        Some(LightBounds::new(&self.bbox, bounds.power, axis, bounds.cos_theta_o, bounds.cos_theta_e, bounds.two_sided))
+    }
+    //the emitters inside are rotated the same way
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+        let mut inner = Vec::new();
+        gather_emitters(&self.object, &mut inner);
+        let angle = self.sin_theta.atan2(self.cos_theta).to_degrees();
+        for emitter in inner {
+            emitters.push(Arc::new(rotate_y::new(emitter, angle)));
+        }
+    }
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        let to_object = |v: Vec3| Vec3::new(self.cos_theta * v.x - self.sin_theta * v.z, v.y, self.sin_theta * v.x + self.cos_theta * v.z);
+        self.object.transmittance(&Ray::new(to_object(r.a_origin), to_object(r.b_direction), r.time), ray_t)
+    }
 }
 
 pub struct constant_medium {
@@ -268,35 +480,26 @@ impl constant_medium {
             phase_function: Arc::new(isotropic::new(color)),
         }
     }
-}
+    //fog that scatters forward or backward instead of evenly, e.g. with HenyeyGreenstein
+    pub fn new_with_phase(boundary: Arc<dyn hittable + Send + Sync>, density: f64, color: Vec3, phase: Arc<dyn phase_function>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Arc::new(volumetric::new(color, phase)),
+        }
+    }
 
-impl hittable for constant_medium {
-    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
-        let mut rec1 = hit_record {
-            p: Vec3::zero(),
-            normal: Vec3::zero(),
-            t: 0.0,
-            front_face: false,
-            mat: Arc::new(lambertian::new_from_texture(Arc::new(SolidColor::new(Vec3::zero())))),
-            u: 0.0,
-            v: 0.0,
-        };
-        let mut rec2 = hit_record {
-            p: Vec3::zero(),
-            normal: Vec3::zero(),
-            t: 0.0,
-            front_face: false,
-            mat: Arc::new(lambertian::new_from_texture(Arc::new(SolidColor::new(Vec3::zero())))),
-            u: 0.0,
-            v: 0.0,
-        };
+    //the part of ray_t inside the boundary, taking the first two boundary hits (convex boundaries only)
+    fn inside(&self, r: &Ray, ray_t: &Interval) -> Option<(f64, f64)> {
+        let mut rec1 = hit_record::new();
+        let mut rec2 = hit_record::new();
 
         if !self.boundary.hit(r, &Interval::new(f64::NEG_INFINITY, f64::INFINITY), &mut rec1) {
-            return false;
+            return None;
         }
 
         if !self.boundary.hit(r, &Interval::new(rec1.t + 0.0001, f64::INFINITY), &mut rec2) {
-            return false;
+            return None;
         }
 
         if rec1.t < ray_t.tmin {
@@ -308,22 +511,32 @@ impl hittable for constant_medium {
         }
 
         if rec1.t >= rec2.t {
-            return false;
+            return None;
         }
 
         if rec1.t < 0.0 {
             rec1.t = 0.0;
         }
+        Some((rec1.t, rec2.t))
+    }
+}
+
+impl hittable for constant_medium {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        let (t_enter, t_exit) = match self.inside(r, ray_t) {
+            Some(segment) => segment,
+            None => return false,
+        };
 
         let ray_length = r.b_direction.length();
-        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
         let hit_distance = self.neg_inv_density * util::random_f64_0_1().ln();
 
         if hit_distance > distance_inside_boundary {
             return false;
         }
 
-        rec.t = rec1.t + hit_distance / ray_length;
+        rec.t = t_enter + hit_distance / ray_length;
         rec.p = r.at(rec.t);
 
         rec.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
@@ -336,4 +549,11 @@ impl hittable for constant_medium {
     fn bbox(&self) -> &AABB {
         self.boundary.bbox()
     }
+    //Beer-Lambert through the uniform density
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        match self.inside(r, ray_t) {
+            Some((t_enter, t_exit)) => ((t_exit - t_enter) * r.b_direction.length() / self.neg_inv_density).exp(),
+            None => 1.0,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,302 @@
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::intervals::Interval;
+use crate::hittables::{hit_record, hittable};
+use crate::aabb::AABB;
+use crate::util;
+use std::sync::Arc;
+
+//Where an emitter is, how much light it gives off and in which directions
+//the directions are a cone of normals (axis, theta_o) widened by theta_e, the spread of the emission around each normal
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+    //total emitted power, only compared between lights so constant factors like pi do not matter
+    pub power: f64,
+    pub axis: Vec3,
+    pub cos_theta_o: f64,
+    pub cos_theta_e: f64,
+    //emits from both sides of the surface, like quads
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    pub fn new(bbox: &AABB, power: f64, axis: Vec3, cos_theta_o: f64, cos_theta_e: f64, two_sided: bool) -> Self {
+        Self {
+            min: Vec3::new(bbox.x.tmin, bbox.y.tmin, bbox.z.tmin),
+            max: Vec3::new(bbox.x.tmax, bbox.y.tmax, bbox.z.tmax),
+            power,
+            axis,
+            cos_theta_o,
+            cos_theta_e,
+            two_sided,
+        }
+    }
+    //emits in every direction, e.g. a sphere
+    pub fn new_omni(bbox: &AABB, power: f64) -> Self {
+        Self::new(bbox, power, Vec3::new(0.0, 1.0, 0.0), -1.0, 0.0, false)
+    }
+    fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    fn bbox(&self) -> AABB {
+        AABB::new_from_points(self.min, self.max)
+    }
+
+    fn union(a: &LightBounds, b: &LightBounds) -> LightBounds {
+        if a.power <= 0.0 {
+            return *b;
+        }
+        if b.power <= 0.0 {
+            return *a;
+        }
+        let (axis, cos_theta_o) = Self::union_cones(a.axis, a.cos_theta_o, b.axis, b.cos_theta_o);
+        LightBounds {
+            min: Vec3::new(util::fmin(a.min.x, b.min.x), util::fmin(a.min.y, b.min.y), util::fmin(a.min.z, b.min.z)),
+            max: Vec3::new(util::fmax(a.max.x, b.max.x), util::fmax(a.max.y, b.max.y), util::fmax(a.max.z, b.max.z)),
+            power: a.power + b.power,
+            axis,
+            cos_theta_o,
+            cos_theta_e: util::fmin(a.cos_theta_e, b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided,
+        }
+    }
+    //smallest cone containing two cones (PBRT's DirectionCone::Union)
+    fn union_cones(wa: Vec3, cos_a: f64, wb: Vec3, cos_b: f64) -> (Vec3, f64) {
+        let pi = std::f64::consts::PI;
+        let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+        let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+        let theta_d = (wa * wb).clamp(-1.0, 1.0).acos();
+        if util::fmin(theta_d + theta_b, pi) <= theta_a {
+            return (wa, cos_a);
+        }
+        if util::fmin(theta_d + theta_a, pi) <= theta_b {
+            return (wb, cos_b);
+        }
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        if theta_o >= pi {
+            return (wa, -1.0);
+        }
+        //turn wa towards wb until the new cone just touches the far side of both
+        let k = wa.cross(wb);
+        if k.squared_length() < 1e-20 {
+            return (wa, -1.0);
+        }
+        let k = k.unit_vector();
+        let theta_r = theta_o - theta_a;
+        let axis = wa * theta_r.cos() + k.cross(wa) * theta_r.sin();
+        (axis.unit_vector(), theta_o.cos())
+    }
+
+    //estimated contribution of everything inside the bounds to point p (PBRT-v4 LightBounds::Importance)
+    fn importance(&self, p: &Vec3) -> f64 {
+        if self.power <= 0.0 {
+            return 0.0;
+        }
+        let pc = self.centroid();
+        let diagonal = (self.max - self.min).length();
+        //do not let the estimate blow up when p is inside or very close to the bounds
+        let d2 = util::fmax((*p - pc).squared_length(), diagonal / 2.0);
+        let wi = (*p - pc).unit_vector();
+
+        let mut cos_theta_w = self.axis * wi;
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        let theta_w = cos_theta_w.clamp(-1.0, 1.0).acos();
+        let theta_o = self.cos_theta_o.clamp(-1.0, 1.0).acos();
+        //angle the bounding sphere of the box covers as seen from p
+        let radius_squared = diagonal * diagonal / 4.0;
+        let theta_b = if (*p - pc).squared_length() <= radius_squared {
+            std::f64::consts::PI
+        } else {
+            (radius_squared / (*p - pc).squared_length()).sqrt().asin()
+        };
+        //the smallest angle between p and any normal of any point in the bounds
+        let theta = util::fmax(theta_w - theta_o - theta_b, 0.0);
+        if theta >= self.cos_theta_e.clamp(-1.0, 1.0).acos() {
+            return 0.0;
+        }
+        self.power * theta.cos() / d2
+    }
+}
+
+struct LightNode {
+    bounds: LightBounds,
+    //leaf: index into lights; interior: index of the second child, the first child is the next node
+    index: usize,
+    is_leaf: bool,
+}
+
+//Bounding volume hierarchy over the emitters of a scene, used to pick the light that most likely matters at a point
+//(Conty Estevez & Kulla, "Importance Sampling of Many Lights with Adaptive Tree Splitting")
+//As a hittable it can be set as Camera::area_lights: random() picks a light by importance and samples it,
+//pdf_value() is the matching density including the probability of picking the light
+pub struct LightBVH {
+    pub lights: Vec<Arc<dyn hittable + Send + Sync>>,
+    nodes: Vec<LightNode>,
+    //the path from the root to every light, one bit per level, 1 for the second child
+    trails: Vec<u64>,
+    bbox: AABB,
+}
+
+impl LightBVH {
+    //lights that do not emit anything are left out
+    pub fn new(lights: Vec<Arc<dyn hittable + Send + Sync>>) -> Self {
+        let lights: Vec<Arc<dyn hittable + Send + Sync>> = lights.into_iter().filter(|light| light.light_bounds().is_some()).collect();
+        let mut bounds: Vec<(usize, LightBounds)> = lights.iter().enumerate().map(|(i, light)| (i, light.light_bounds().unwrap())).collect();
+        let mut bvh = Self {
+            trails: vec![0; lights.len()],
+            lights,
+            nodes: Vec::new(),
+            bbox: AABB::new(Interval::new(0.0, 0.0), Interval::new(0.0, 0.0), Interval::new(0.0, 0.0)),
+        };
+        if !bounds.is_empty() {
+            let size = bounds.len();
+            bvh.build(&mut bounds, 0, size, 0, 0);
+            bvh.bbox = bvh.nodes[0].bounds.bbox();
+        }
+        bvh
+    }
+    //every emissive object in the world, looking through lists, BVHs, translations and rotations
+    pub fn new_from_world(world: &dyn hittable) -> Self {
+        let mut emitters = Vec::new();
+        world.collect_emitters(&mut emitters);
+        Self::new(emitters)
+    }
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    //split at the median centroid along the longest axis, like BVHNode
+    fn build(&mut self, bounds: &mut Vec<(usize, LightBounds)>, start: usize, end: usize, trail: u64, depth: u32) -> LightBounds {
+        if end - start == 1 {
+            let (light, light_bounds) = bounds[start];
+            self.trails[light] = trail;
+            self.nodes.push(LightNode {
+                bounds: light_bounds,
+                index: light,
+                is_leaf: true,
+            });
+            return light_bounds;
+        }
+        let mut min = bounds[start].1.centroid();
+        let mut max = min;
+        for (_, b) in bounds[start..end].iter() {
+            let c = b.centroid();
+            min = Vec3::new(util::fmin(min.x, c.x), util::fmin(min.y, c.y), util::fmin(min.z, c.z));
+            max = Vec3::new(util::fmax(max.x, c.x), util::fmax(max.y, c.y), util::fmax(max.z, c.z));
+        }
+        let axis = AABB::new_from_points(min, max).longest_axis();
+        bounds[start..end].sort_by(|a, b| a.1.centroid().lp(axis).partial_cmp(&b.1.centroid().lp(axis)).unwrap());
+        let mid = start + (end - start) / 2;
+
+        let node = self.nodes.len();
+        self.nodes.push(LightNode {
+            bounds: bounds[start].1,
+            index: 0,
+            is_leaf: false,
+        });
+        //deeper trees than this would need more than 64 bits; a median split of any realistic scene stays far below
+        assert!(depth < 64, "Light BVH is too deep");
+        let left = self.build(bounds, start, mid, trail, depth + 1);
+        self.nodes[node].index = self.nodes.len();
+        let right = self.build(bounds, mid, end, trail | (1 << depth), depth + 1);
+        let node_bounds = LightBounds::union(&left, &right);
+        self.nodes[node].bounds = node_bounds;
+        node_bounds
+    }
+
+    //probability of taking the first child of an interior node at p
+    //if neither child seems to reach p they are picked evenly, so every light can still be sampled
+    fn first_child_probability(&self, node: usize, p: &Vec3) -> f64 {
+        let first = self.nodes[node + 1].bounds.importance(p);
+        let second = self.nodes[self.nodes[node].index].bounds.importance(p);
+        if first + second <= 0.0 {
+            0.5
+        } else {
+            first / (first + second)
+        }
+    }
+
+    //choose a light by its estimated contribution to p, returns the light index and the probability of choosing it
+    pub fn sample(&self, p: &Vec3) -> Option<(usize, f64)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut node = 0;
+        let mut pmf = 1.0;
+        while !self.nodes[node].is_leaf {
+            let p_first = self.first_child_probability(node, p);
+            if util::random_f64_0_1() < p_first {
+                pmf *= p_first;
+                node += 1;
+            } else {
+                pmf *= 1.0 - p_first;
+                node = self.nodes[node].index;
+            }
+        }
+        Some((self.nodes[node].index, pmf))
+    }
+    //probability that sample(p) chooses light `light`, for MIS weights
+    pub fn pmf(&self, p: &Vec3, light: usize) -> f64 {
+        if light >= self.lights.len() {
+            return 0.0;
+        }
+        let trail = self.trails[light];
+        let mut node = 0;
+        let mut pmf = 1.0;
+        let mut depth = 0;
+        while !self.nodes[node].is_leaf {
+            let p_first = self.first_child_probability(node, p);
+            if trail & (1 << depth) == 0 {
+                pmf *= p_first;
+                node += 1;
+            } else {
+                pmf *= 1.0 - p_first;
+                node = self.nodes[node].index;
+            }
+            depth += 1;
+        }
+        pmf
+    }
+
+    //sum of pick probability times light density over the lights the ray can reach,
+    //subtrees whose box the ray misses can not contribute
+    fn pdf_node(&self, node: usize, r: &Ray, pmf: f64) -> f64 {
+        if pmf <= 0.0 || !self.nodes[node].bounds.bbox().hit(r, &Interval::new(0.001, f64::INFINITY)) {
+            return 0.0;
+        }
+        if self.nodes[node].is_leaf {
+            return pmf * self.lights[self.nodes[node].index].pdf_value(&r.a_origin, &r.b_direction);
+        }
+        let p_first = self.first_child_probability(node, &r.a_origin);
+        self.pdf_node(node + 1, r, pmf * p_first) + self.pdf_node(self.nodes[node].index, r, pmf * (1.0 - p_first))
+    }
+}
+
+impl hittable for LightBVH {
+    //the emitters are part of the world already, the tree is only there to sample them
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        false
+    }
+    fn bbox(&self) -> &AABB {
+        &self.bbox
+    }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        self.pdf_node(0, &Ray::new(*origin, *direction, 0.0), 1.0)
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        match self.sample(origin) {
+            Some((light, _)) => self.lights[light].random(origin),
+            None => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,270 @@
+use crate::vec3::Vec3;
+use crate::util;
+use crate::color;
+use std::sync::Arc;
+
+//Light arriving at a point from one light
+pub struct LightSample {
+    //unit vector from the point towards the light
+    pub direction: Vec3,
+    //how far the shadow ray has to stay unblocked, infinity for directional lights
+    pub distance: f64,
+    //incident radiance, already divided by the distance squared
+    pub radiance: Vec3,
+}
+
+//Lights that exist only as a position or a direction, they can not be hit by rays
+//and are found by shadow rays from every diffuse hit instead (next event estimation)
+pub trait light : Send + Sync {
+    //None if the light does not reach p
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample>;
+}
+
+//Shines equally in all directions, intensity is the radiant intensity (power per solid angle)
+pub struct point_light {
+    pub position: Vec3,
+    pub intensity: Vec3,
+}
+
+impl point_light {
+    pub fn new(position: Vec3, intensity: Vec3) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl light for point_light {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / distance_squared,
+        })
+    }
+}
+
+//A point light limited to a cone, full intensity within falloff_start degrees of the axis
+//and smoothly fading out to zero at total_width degrees
+pub struct spot_light {
+    pub position: Vec3,
+    pub intensity: Vec3,
+    direction: Vec3,
+    cos_total_width: f64,
+    cos_falloff_start: f64,
+    //measured distribution of a real fixture around the axis, on top of the cone
+    pub profile: Option<Arc<IesProfile>>,
+}
+
+impl spot_light {
+    pub fn new(position: Vec3, target: Vec3, intensity: Vec3, total_width: f64, falloff_start: f64) -> Self {
+        Self {
+            position,
+            intensity,
+            direction: (target - position).unit_vector(),
+            cos_total_width: total_width.to_radians().cos(),
+            cos_falloff_start: falloff_start.min(total_width).to_radians().cos(),
+            profile: None,
+        }
+    }
+    //a fixture shaped by an IES profile pointing at target (vertical angle 0), as bright as the profile's
+    //candela values; color only sets the tint
+    pub fn new_ies(position: Vec3, target: Vec3, color: Vec3, profile: Arc<IesProfile>) -> Self {
+        let luminance = color::luminance(color);
+        let tint = if luminance > 0.0 { color / luminance } else { Vec3::zero() };
+        let mut light = Self::new(position, target, tint * (profile.max_candela() / 683.0), 180.0, 180.0);
+        light.profile = Some(profile);
+        light
+    }
+    fn falloff(&self, cos_theta: f64) -> f64 {
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_total_width {
+            return 0.0;
+        }
+        //smoothstep like PBRT
+        let t = (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl light for spot_light {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *p;
+        let distance_squared = to_light.squared_length();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let direction = to_light / distance;
+        let mut falloff = self.falloff(direction * self.direction * (-1.0));
+        if let Some(profile) = &self.profile {
+            let w = direction * (-1.0);
+            let (s, t) = util::orthonormal_basis(self.direction);
+            let theta = (w * self.direction).clamp(-1.0, 1.0).acos().to_degrees();
+            let phi = (w * t).atan2(w * s).to_degrees();
+            falloff *= profile.relative(theta, phi);
+        }
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * (falloff / distance_squared),
+        })
+    }
+}
+
+//A very distant light like the sun, every point receives the same irradiance from one direction
+pub struct directional_light {
+    //the direction the light travels in
+    direction: Vec3,
+    pub irradiance: Vec3,
+}
+
+impl directional_light {
+    pub fn new(direction: Vec3, irradiance: Vec3) -> Self {
+        Self {
+            direction: direction.unit_vector(),
+            irradiance,
+        }
+    }
+}
+
+impl light for directional_light {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: self.direction * (-1.0),
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+        })
+    }
+}
+
+//Photometric data of a light fixture in the IESNA LM-63 format (.ies files from lamp manufacturers):
+//candela values on a grid of vertical angles from the fixture axis and horizontal angles around it
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    //candela[h][v], multiplier already applied
+    candela: Vec<Vec<f64>>,
+    max_candela: f64,
+    //lumens of the lamp as written in the file, -1 for absolute photometry
+    pub lumens: f64,
+}
+
+impl IesProfile {
+    pub fn new(filename: &str) -> Self {
+        let text = std::fs::read_to_string(filename).expect("failed to read the IES file");
+        Self::new_from_str(&text)
+    }
+    pub fn new_from_str(text: &str) -> Self {
+        //the keyword lines before TILT= are free form, everything after it is numbers
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => break line.trim_start()[5..].trim().to_string(),
+                Some(_) => continue,
+                None => panic!("IES file without a TILT= line"),
+            }
+        };
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest.iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>().expect("IES file with a bad number"));
+        let mut next = || numbers.next().expect("IES file ends early");
+        if tilt == "INCLUDE" {
+            //lamp to luminaire geometry, then the tilt angles and their multipliers, unused
+            next();
+            let count = next() as usize;
+            for _ in 0..2 * count {
+                next();
+            }
+        }
+        let _lamps = next();
+        let lumens = next();
+        let multiplier = next();
+        let vertical_count = next() as usize;
+        let horizontal_count = next() as usize;
+        //photometric type, units and the fixture's size, ballast factor, future use, input watts
+        for _ in 0..8 {
+            next();
+        }
+        let vertical: Vec<f64> = (0..vertical_count).map(|_| next()).collect();
+        let horizontal: Vec<f64> = (0..horizontal_count).map(|_| next()).collect();
+        let candela: Vec<Vec<f64>> = (0..horizontal_count)
+            .map(|_| (0..vertical_count).map(|_| next() * multiplier).collect())
+            .collect();
+        if vertical.is_empty() || horizontal.is_empty() {
+            panic!("IES file without angles");
+        }
+        let max_candela = candela.iter().flatten().fold(0.0, |a: f64, &b| a.max(b));
+        Self {
+            vertical,
+            horizontal,
+            candela,
+            max_candela,
+            lumens,
+        }
+    }
+    pub fn max_candela(&self) -> f64 {
+        self.max_candela
+    }
+    //luminous intensity at vertical angle theta from the axis and horizontal angle phi, in degrees
+    pub fn candela(&self, theta: f64, phi: f64) -> f64 {
+        let first = self.vertical[0];
+        let last = self.vertical[self.vertical.len() - 1];
+        if theta < first || theta > last {
+            return 0.0;
+        }
+        //the last horizontal angle tells the symmetry of the data
+        let mut phi = phi.rem_euclid(360.0);
+        let last_horizontal = self.horizontal[self.horizontal.len() - 1];
+        if last_horizontal <= 0.0 {
+            phi = 0.0;
+        } else if last_horizontal <= 90.0 {
+            if phi > 180.0 {
+                phi = 360.0 - phi;
+            }
+            if phi > 90.0 {
+                phi = 180.0 - phi;
+            }
+        } else if last_horizontal <= 180.0 && phi > 180.0 {
+            phi = 360.0 - phi;
+        }
+        let (v, tv) = Self::interval(&self.vertical, theta);
+        let (h, th) = Self::interval(&self.horizontal, phi);
+        let at = |h: usize| {
+            let row = &self.candela[h];
+            row[v] * (1.0 - tv) + row[(v + 1).min(row.len() - 1)] * tv
+        };
+        at(h) * (1.0 - th) + at((h + 1).min(self.horizontal.len() - 1)) * th
+    }
+    //candela relative to the brightest direction, 0 to 1
+    pub fn relative(&self, theta: f64, phi: f64) -> f64 {
+        if self.max_candela <= 0.0 {
+            return 0.0;
+        }
+        self.candela(theta, phi) / self.max_candela
+    }
+    //index of the last angle at or below x and how far x is towards the next one
+    fn interval(angles: &[f64], x: f64) -> (usize, f64) {
+        if angles.len() < 2 || x <= angles[0] {
+            return (0, 0.0);
+        }
+        let i = angles.partition_point(|&a| a <= x).min(angles.len() - 1) - 1;
+        let width = angles[i + 1] - angles[i];
+        let t = if width > 0.0 { (x - angles[i]) / width } else { 0.0 };
+        (i, t.clamp(0.0, 1.0))
+    }
+}
\ No newline at end of file
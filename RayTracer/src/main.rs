@@ -12,19 +12,45 @@ mod bvh;
 mod textures;
 mod perlins;
 mod quads;
+mod voxels;
+mod voxel_import;
+mod environment;
+mod sky;
+mod lights;
+mod light_bvh;
+mod microfacet;
+mod media;
+mod phase;
+mod spectrum;
+mod procedural;
+mod texture_nodes;
+mod solids;
 
 extern crate opencv;
 
 use vec3::Vec3;
 use sphere::Sphere;
-use crate::hittables::{hit_record, hittable_list, translate, rotate_y, constant_medium};
+use crate::hittables::{hit_record, hittable_list, hittable, translate, rotate_y, constant_medium};
 use intervals::Interval;
 use camera::Camera;
-use materials::{material, lambertian, metal, dielectric, diffuse_light, isotropic};
+use materials::{material, lambertian, metal, conductor, dielectric, thin_dielectric, principled, mix, coated, diffuse_light, isotropic, alpha_mask, bump_mapped, noise_bumped};
 use bvh::BVHNode;
 use std::sync::Arc;
 use textures::{Checker, SolidColor, Image, Noise};
+use texture_nodes::{Mix, Multiply, Add, Invert, Ramp, UvTransform, Triplanar, UvChecker};
+use perlins::{noise_source, improved_perlin, open_simplex};
+use procedural::{TextureSpace, ColorRamp, Fbm, RidgedMultifractal, Worley, WorleyFeature, Marble, Wood};
 use quads::{quad, newbox};
+use solids::{cylinder, cone, torus};
+use voxels::{VoxelGrid, Block};
+use environment::EnvironmentMap;
+use sky::Sky;
+use lights::{point_light, spot_light, directional_light, IesProfile};
+use light_bvh::LightBVH;
+use spectrum::Dispersion;
+use phase::{HenyeyGreenstein, DoubleHenyeyGreenstein};
+use media::{Fog, heterogeneous_medium, subsurface_volume, TurbulenceDensity, FunctionDensity, GridDensity};
+use voxel_import::{VoxModel, Structure, BlockTextures};
 
 fn bouncing_spheres() {
     let ASPECT_RATIO = 16.0 / 9.0 as f64;
@@ -98,6 +124,338 @@ fn bouncing_spheres() {
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
+fn environment_spheres(filename: &str) {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Arc::new(lambertian::new(Vec3::new(0.4, 0.2, 0.1))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, Arc::new(metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0)))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    //an .hdr panorama from the texture search paths lights the scene
+    let mut env = EnvironmentMap::new(filename);
+    env.set_rotation(90.0);
+    cam.environment = Some(Arc::new(env));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn sky_spheres(sun_elevation: f64) {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, Arc::new(lambertian::new(Vec3::new(0.4, 0.2, 0.1))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, Arc::new(metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0)))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    //sun behind the camera on the left, a low sun gives an orange evening light
+    cam.environment = Some(Arc::new(Sky::new(sun_elevation, 120.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn hazy_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+    //a row of spheres fading into the haze
+    for i in 0..8 {
+        let z = -4.0 * i as f64;
+        world.add(Arc::new(Sphere::new(Vec3::new(-2.0 * i as f64, 1.0, z), 1.0, Arc::new(lambertian::new(Vec3::new(0.4, 0.2, 0.1))))));
+    }
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(15.0, 120.0, 3.0)));
+    //ground fog thinning out within a few units, scattering forward so the sun side glows
+    let mut fog = Fog::new_height(0.05, 0.0, 0.4, Vec3::new(0.9, 0.9, 0.9));
+    fog.set_phase(Arc::new(HenyeyGreenstein::new(0.6)));
+    cam.fog = Some(Arc::new(fog));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn metal_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 14.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+    //from a polished mirror to a dull rough surface
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.5, 1.0, 0.0), 1.0, Arc::new(conductor::gold(0.0)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(-2.25, 1.0, 0.0), 1.0, Arc::new(conductor::copper(0.2)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(conductor::silver(0.4)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(2.25, 1.0, 0.0), 1.0, Arc::new(conductor::aluminium(0.6)))));
+    //brushed along the sphere's u direction
+    let brushed = conductor::new_anisotropic(Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837), 0.05, 0.5);
+    world.add(Arc::new(Sphere::new(Vec3::new(4.5, 1.0, 0.0), 1.0, Arc::new(brushed))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(40.0, 150.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn glass() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 200 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 4.0, 16.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+
+    //the same green glass, a thin slab and a thick block
+    let green_glass = Arc::new(dielectric::new_tinted(1.5, Vec3::new(0.4, 0.8, 0.5), 1.0));
+    world.add(Arc::new(newbox(Vec3::new(-5.0, 0.0, -0.5), Vec3::new(-4.8, 2.0, 0.5), green_glass.clone())));
+    world.add(Arc::new(newbox(Vec3::new(-4.0, 0.0, -1.0), Vec3::new(-2.0, 2.0, 1.0), green_glass.clone())));
+    //frosted glass
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(dielectric::new_rough(1.5, 0.3)))));
+    //frosted and tinted
+    let mut amber = dielectric::new_tinted(1.5, Vec3::new(0.9, 0.55, 0.2), 1.0);
+    amber.roughness = 0.15;
+    world.add(Arc::new(Sphere::new(Vec3::new(2.5, 1.0, 0.0), 1.0, Arc::new(amber))));
+    //a window pane, a single quad
+    world.add(Arc::new(quad::new(Vec3::new(4.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 2.0, 0.0), Arc::new(thin_dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.8, 0.5, -0.5), 0.5, Arc::new(lambertian::new(Vec3::new(0.8, 0.1, 0.1))))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(35.0, 150.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn dispersion() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 400 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 4.0, 16.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+
+    //a slanted block of flint glass, and diamond and crown glass spheres to compare
+    let flint = Arc::new(dielectric::new_dispersive(Dispersion::sf11()));
+    let block = newbox(Vec3::new(-1.0, 0.0, -1.0), Vec3::new(1.0, 2.0, 1.0), flint);
+    world.add(Arc::new(translate::new(Arc::new(rotate_y::new(Arc::new(block), 40.0)), Vec3::new(-3.0, 0.0, 0.0))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(dielectric::new_dispersive(Dispersion::diamond())))));
+    world.add(Arc::new(Sphere::new(Vec3::new(3.0, 1.0, 0.0), 1.0, Arc::new(dielectric::new_dispersive(Dispersion::bk7())))));
+    //a narrow bright strip behind them, its refracted image is split into colors
+    let light = Arc::new(diffuse_light::new_from_color(Vec3::new(15.0, 15.0, 15.0)));
+    world.add(Arc::new(quad::new(Vec3::new(-6.0, 0.5, -4.0), Vec3::new(12.0, 0.0, 0.0), Vec3::new(0.0, 0.2, 0.0), light)));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(10.0, 150.0, 3.0)));
+    cam.spectral = true;
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.area_lights = Some(Arc::new(LightBVH::new_from_world(world)));
+    cam.render(world);
+}
+fn principled_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 14.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+
+    //plastic, car paint, velvet, skin-like and frosted glass
+    let plastic = principled::new(Vec3::new(0.8, 0.1, 0.1), 0.0, 0.3);
+    let mut paint = principled::new(Vec3::new(0.1, 0.2, 0.7), 0.6, 0.4);
+    paint.clearcoat = Arc::new(SolidColor::new_gray(1.0));
+    let mut velvet = principled::new(Vec3::new(0.5, 0.1, 0.4), 0.0, 1.0);
+    velvet.sheen = Arc::new(SolidColor::new_gray(1.0));
+    let mut skin = principled::new(Vec3::new(0.9, 0.6, 0.5), 0.0, 0.5);
+    skin.subsurface = Arc::new(SolidColor::new_gray(1.0));
+    let mut frosted = principled::new(Vec3::new(0.9, 1.0, 0.9), 0.0, 0.2);
+    frosted.transmission = Arc::new(SolidColor::new_gray(1.0));
+    for (i, mat) in [plastic, paint, velvet, skin, frosted].into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(Vec3::new(-4.5 + 2.25 * i as f64, 1.0, 0.0), 1.0, Arc::new(mat))));
+    }
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(40.0, 150.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn layered_materials() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 12.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+
+    //painted metal: red paint chipped off a brushed steel sphere
+    let steel: Arc<dyn material + Send + Sync> = Arc::new(conductor::new_anisotropic(Vec3::new(2.5, 2.4, 2.2), Vec3::new(3.3, 3.2, 3.1), 0.4, 0.1));
+    let mut paint = principled::new(Vec3::new(0.7, 0.05, 0.05), 0.0, 0.4);
+    paint.clearcoat = Arc::new(SolidColor::new_gray(0.8));
+    let painted = mix::new_from_texture(steel, Arc::new(paint), Arc::new(Noise::new(6.0)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-2.5, 1.0, 0.0), 1.0, Arc::new(painted))));
+
+    //varnished wood: an amber clearcoat over the oak texture
+    let oak: Arc<dyn material + Send + Sync> = Arc::new(lambertian::new_from_texture(Arc::new(Image::new("oak_log.png"))));
+    let varnished = coated::new_tinted(oak, 1.5, 0.05, Vec3::new(0.95, 0.8, 0.55));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(varnished))));
+
+    //rust eating into copper
+    let rust: Arc<dyn material + Send + Sync> = Arc::new(lambertian::new(Vec3::new(0.45, 0.2, 0.08)));
+    let rusted = mix::new_from_texture(Arc::new(conductor::copper(0.2)), rust, Arc::new(Noise::new(3.0)));
+    world.add(Arc::new(Sphere::new(Vec3::new(2.5, 1.0, 0.0), 1.0, Arc::new(rusted))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+    cam.environment = Some(Arc::new(Sky::new(40.0, 150.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
 fn checkered_spheres() {
     let ASPECT_RATIO = 16.0 / 9.0 as f64;
     let IMAGE_WIDTH = 400 as u32;
@@ -117,9 +475,374 @@ fn checkered_spheres() {
 
     let world = &mut (hittable_list::new());
 
-    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -10.0, 0.0), 10.0, Arc::new(lambertian::new_from_texture(checker.clone())))));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 10.0, 0.0), 10.0, Arc::new(lambertian::new_from_texture(checker.clone())))));
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -10.0, 0.0), 10.0, Arc::new(lambertian::new_from_texture(checker.clone())))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 10.0, 0.0), 10.0, Arc::new(lambertian::new_from_texture(checker.clone())))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn texture_graph() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 30.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 12.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    //dirt blended into grass: fBm noise pushed through a narrow ramp makes a mask with soft edges
+    let dirt = Arc::new(Fbm::new(11, 8.0));
+    let dirt = Arc::new(Multiply::new(dirt, Arc::new(SolidColor::new(Vec3::new(0.45, 0.32, 0.2)))));
+    let grass = Arc::new(SolidColor::new(Vec3::new(0.2, 0.45, 0.1)));
+    let patches = Arc::new(Ramp::new(Arc::new(Fbm::new(12, 0.4)), ColorRamp::new(vec![(0.45, Vec3::zero()), (0.55, Vec3::new(1.0, 1.0, 1.0))])));
+    let ground = Arc::new(Mix::new(dirt, grass, patches));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(ground)))));
+
+    //a checker on the sphere's own uv, turned by 30 degrees
+    let stripes = Arc::new(UvChecker::new_from_color(1.0, 1.0, Vec3::new(0.9, 0.9, 0.9), Vec3::new(0.8, 0.1, 0.1)));
+    let turned = Arc::new(UvTransform::new(stripes, (16.0, 8.0), 30.0, (0.0, 0.0)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-2.5, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(turned)))));
+
+    //tiles projected from the three axes, the box has no uv of its own worth using
+    let tiles = Arc::new(UvChecker::new_from_color(1.0, 1.0, Vec3::new(0.2, 0.2, 0.25), Vec3::new(0.85, 0.85, 0.8)));
+    let tiled_box = newbox(Vec3::new(-0.8, 0.0, -0.8), Vec3::new(0.8, 1.6, 0.8), Arc::new(lambertian::new_from_texture(Arc::new(Triplanar::new(tiles, 2.5)))));
+    world.add(Arc::new(rotate_y::new(Arc::new(tiled_box), 30.0)));
+
+    //bright cells with dark borders, from inverting cellular distances
+    let cells = Arc::new(Worley::new(13, 4.0));
+    let glow = Arc::new(Multiply::new(Arc::new(Invert::new(cells)), Arc::new(SolidColor::new(Vec3::new(0.2, 0.6, 0.9)))));
+    let shine = Arc::new(Add::new(glow, Arc::new(SolidColor::new(Vec3::new(0.05, 0.05, 0.05)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(2.5, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(shine)))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn earth() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(12.0, 0.0, 12.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    let earth_texture = Arc::new(Image::new("double_baihua.png"));
+    let earth_surface = Arc::new(lambertian::new_from_texture(earth_texture.clone()));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, earth_surface)));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn baihua() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 1600 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 10.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.6;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    // let ground_material = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
+
+    for i in -11..11 {
+        for j in -11..11 {
+            let choose_mat = util::random_f64_0_1();
+            let center = Vec3::new(i as f64 + 0.9 * util::random_f64_0_1(), 0.2, j as f64 + 0.9 * util::random_f64_0_1());
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn material>;
+                if choose_mat < 0.8 {
+                    //diffuse
+                    let albedo = Vec3::new(util::random_f64_0_1() * util::random_f64_0_1(), 
+                                           util::random_f64_0_1() * util::random_f64_0_1(), 
+                                           util::random_f64_0_1() * util::random_f64_0_1());
+                    // sphere_material = Arc::new(lambertian::new(albedo));
+                    let earth_texture = Arc::new(Image::new("double_baihua.png"));
+                    let earth_surface = Arc::new(lambertian::new_from_texture(earth_texture.clone()));
+
+                    //bouncy balls
+                    let center2 = center + Vec3::new(0.0, util::random_range(0.0, 0.01), 0.0);
+                    world.add(Arc::new(Sphere::new_moving(center, center2, 0.2, earth_surface)));
+                } else if choose_mat < 0.95 {
+                    //metal
+                    let albedo = Vec3::new(util::random_range(0.5, 1.0), util::random_range(0.5, 1.0), util::random_range(0.5, 1.0));
+                    let fuzz = util::random_range(0.0, 0.5);
+                    sphere_material = Arc::new(metal::new(albedo, fuzz));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    //glass
+                    sphere_material = Arc::new(dielectric::new(1.5));
+                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    let material1 = Arc::new(dielectric::new(1.5));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, material1)));
+
+    let material2 = Arc::new(lambertian::new(Vec3::new(0.4, 0.2, 0.1)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, material2)));
+
+    let material3 = Arc::new(metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, material3)));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+                                        VFOV, LOOKFROM, LOOKAT, VUP,
+                                        DEFOCUS_ANGLE, FOCUS_DIST,
+                                        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn perlin_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    let pertext = Arc::new(Noise::new(4.0));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn procedural_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 30.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 14.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    //cracked mud: the borders between the cells are dark
+    let mut mud = Worley::new(1, 1.5);
+    mud.feature = WorleyFeature::F2MinusF1;
+    mud.ramp = ColorRamp::new(vec![(0.0, Vec3::new(0.15, 0.1, 0.05)), (0.08, Vec3::new(0.55, 0.42, 0.3)), (1.0, Vec3::new(0.65, 0.5, 0.35))]);
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(Arc::new(mud))))));
+
+    let mut clouds = Fbm::new(2, 2.0);
+    clouds.ramp = ColorRamp::new_two(Vec3::new(0.1, 0.3, 0.8), Vec3::new(1.0, 1.0, 1.0));
+    world.add(Arc::new(Sphere::new(Vec3::new(-4.4, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(clouds))))));
+
+    let mut ridges = RidgedMultifractal::new(3, 1.5);
+    ridges.ramp = ColorRamp::new(vec![(0.0, Vec3::new(0.05, 0.05, 0.1)), (0.6, Vec3::new(0.3, 0.2, 0.5)), (1.0, Vec3::new(1.0, 0.9, 0.6))]);
+    world.add(Arc::new(Sphere::new(Vec3::new(-2.2, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(ridges))))));
+
+    let mut marble = Marble::new(4, 4.0);
+    marble.direction = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+    marble.turbulence = 12.0;
+    marble.ramp = ColorRamp::new(vec![(0.0, Vec3::new(0.2, 0.25, 0.2)), (0.3, Vec3::new(0.85, 0.85, 0.8)), (1.0, Vec3::new(0.95, 0.95, 0.92))]);
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(marble))))));
+
+    let wood = Wood::new(5, 0.12);
+    world.add(Arc::new(Sphere::new(Vec3::new(2.2, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(wood))))));
+
+    //cells in texture space follow the sphere's uv lines instead of cutting through it
+    let mut scales = Worley::new(6, 12.0);
+    scales.space = TextureSpace::Uv;
+    scales.ramp = ColorRamp::new_two(Vec3::new(0.05, 0.3, 0.1), Vec3::new(0.6, 0.8, 0.3));
+    world.add(Arc::new(Sphere::new(Vec3::new(4.4, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(scales))))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn noise_spheres(time: f64) {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 30.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 3.0, 12.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    //seeded noise looks the same on every render, `time` moves through its 4th dimension
+    let improved: Arc<dyn noise_source> = Arc::new(improved_perlin::new_seeded(21));
+    let open_simplex_noise: Arc<dyn noise_source> = Arc::new(open_simplex::new_seeded(22));
+
+    let mut marble = Noise::new_with_noise(improved.clone(), 4.0);
+    marble.time = time;
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(-2.5, 1.0, 0.0), 1.0, Arc::new(lambertian::new_from_texture(Arc::new(marble))))));
+
+    //hammered metal from the exact gradient of the OpenSimplex noise
+    let hammered = noise_bumped::new(Arc::new(metal::new(Vec3::new(0.8, 0.6, 0.2), 0.0)), open_simplex_noise.clone(), 6.0, 0.03);
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, Arc::new(hammered))));
+
+    //smoke that churns as time goes on instead of sliding past
+    let mut smoke = TurbulenceDensity::new_with_noise(open_simplex_noise.clone(), 2.0, 5, 4.0);
+    smoke.time = time;
+    let puff = Sphere::new(Vec3::new(2.5, 1.0, 0.0), 1.0, Arc::new(lambertian::new(Vec3::zero())));
+    world.add(Arc::new(heterogeneous_medium::new(Arc::new(puff), Arc::new(smoke), Vec3::new(0.8, 0.8, 0.8))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn subsurface_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let pertext = Arc::new(Noise::new(4.0));
+    let ground = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, ground.clone())));
+    //marble with the veins of the Perlin marble texture, skin with red travelling furthest, and candle wax
+    let marble = subsurface_volume::new_from_texture(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, -2.5), 1.0, ground.clone())),
+                                                     pertext, Vec3::new(0.1, 0.1, 0.1), 1.5);
+    let skin = subsurface_volume::new(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, ground.clone())),
+                                      Vec3::new(0.85, 0.55, 0.45), Vec3::new(0.37, 0.14, 0.08), 1.4);
+    let wax = subsurface_volume::new(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 2.5), 1.0, ground.clone())),
+                                     Vec3::new(0.95, 0.9, 0.7), Vec3::new(0.5, 0.4, 0.25), 1.45);
+    world.add(Arc::new(marble));
+    world.add(Arc::new(skin));
+    world.add(Arc::new(wax));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+    //lit from behind so the light bleeding through the spheres shows
+    cam.environment = Some(Arc::new(Sky::new(25.0, 250.0, 3.0)));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn bump_spheres() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
+    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    //the marble noise as a height field, the spheres stay smooth and only their shading is bumpy
+    let pertext = Arc::new(Noise::new(4.0));
+    let bumpy_ground = Arc::new(bump_mapped::new(Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5))), pertext.clone(), 0.5));
+    let bumpy_metal = Arc::new(bump_mapped::new(Arc::new(metal::new(Vec3::new(0.8, 0.6, 0.2), 0.0)), pertext.clone(), 0.05));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, bumpy_ground)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, bumpy_metal)));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
@@ -129,15 +852,15 @@ fn checkered_spheres() {
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
-fn earth() {
-    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+fn quads() {
+    let ASPECT_RATIO = 1.0 as f64;
     let IMAGE_WIDTH = 400 as u32;
 
     let SAMPLES_PER_PIXEL = 100 as u32;
     let MAX_DEPTH = 50 as u32;
-    let VFOV = 20.0 as f64;
+    let VFOV = 80.0 as f64;
 
-    let LOOKFROM = Vec3::new(12.0, 0.0, 12.0);
+    let LOOKFROM = Vec3::new(0.0, 0.0, 9.0);
     let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
     let VUP = Vec3::new(0.0, 1.0, 0.0);
 
@@ -148,9 +871,19 @@ fn earth() {
 
     let world = &mut (hittable_list::new());
 
-    let earth_texture = Arc::new(Image::new("double_baihua.png"));
-    let earth_surface = Arc::new(lambertian::new_from_texture(earth_texture.clone()));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 2.0, earth_surface)));
+    let left_red = Arc::new(lambertian::new(Vec3::new(1.0, 0.2, 0.2)));
+    let back_green = Arc::new(lambertian::new(Vec3::new(0.2, 1.0, 0.2)));
+    let right_blue = Arc::new(lambertian::new(Vec3::new(0.2, 0.2, 1.0)));
+    let upper_orange = Arc::new(lambertian::new(Vec3::new(1.0, 0.5, 0.0)));
+    let lower_teal = Arc::new(lambertian::new(Vec3::new(0.2, 0.8, 0.8)));
+
+    let sphere_material = Arc::new(dielectric::new(1.5));
+    
+    world.add(Arc::new(quad::new(Vec3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
+    world.add(Arc::new(quad::new(Vec3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
+    world.add(Arc::new(quad::new(Vec3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
+    world.add(Arc::new(quad::new(Vec3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0), upper_orange)));
+    world.add(Arc::new(quad::new(Vec3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -4.0), lower_teal)));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
@@ -160,80 +893,47 @@ fn earth() {
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
-fn baihua() {
+fn primitives() {
     let ASPECT_RATIO = 16.0 / 9.0 as f64;
-    let IMAGE_WIDTH = 1600 as u32;
+    let IMAGE_WIDTH = 400 as u32;
 
     let SAMPLES_PER_PIXEL = 100 as u32;
     let MAX_DEPTH = 50 as u32;
-    let VFOV = 20.0 as f64;
+    let VFOV = 30.0 as f64;
 
-    let LOOKFROM = Vec3::new(13.0, 2.0, 10.0);
-    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let LOOKFROM = Vec3::new(0.0, 4.0, 14.0);
+    let LOOKAT = Vec3::new(0.0, 1.0, 0.0);
     let VUP = Vec3::new(0.0, 1.0, 0.0);
 
-    let DEFOCUS_ANGLE = 0.6;
+    let DEFOCUS_ANGLE = 0.0;
     let FOCUS_DIST = 10.0;
 
     let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
 
     let world = &mut (hittable_list::new());
 
-    // let ground_material = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
-    let checker = Arc::new(Checker::new_from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::new(0.9, 0.9, 0.9)));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(checker)))));
-
-    for i in -11..11 {
-        for j in -11..11 {
-            let choose_mat = util::random_f64_0_1();
-            let center = Vec3::new(i as f64 + 0.9 * util::random_f64_0_1(), 0.2, j as f64 + 0.9 * util::random_f64_0_1());
-            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Arc<dyn material>;
-                if choose_mat < 0.8 {
-                    //diffuse
-                    let albedo = Vec3::new(util::random_f64_0_1() * util::random_f64_0_1(), 
-                                           util::random_f64_0_1() * util::random_f64_0_1(), 
-                                           util::random_f64_0_1() * util::random_f64_0_1());
-                    // sphere_material = Arc::new(lambertian::new(albedo));
-                    let earth_texture = Arc::new(Image::new("double_baihua.png"));
-                    let earth_surface = Arc::new(lambertian::new_from_texture(earth_texture.clone()));
-
-                    //bouncy balls
-                    let center2 = center + Vec3::new(0.0, util::random_range(0.0, 0.01), 0.0);
-                    world.add(Arc::new(Sphere::new_moving(center, center2, 0.2, earth_surface)));
-                } else if choose_mat < 0.95 {
-                    //metal
-                    let albedo = Vec3::new(util::random_range(0.5, 1.0), util::random_range(0.5, 1.0), util::random_range(0.5, 1.0));
-                    let fuzz = util::random_range(0.0, 0.5);
-                    sphere_material = Arc::new(metal::new(albedo, fuzz));
-                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
-                } else {
-                    //glass
-                    sphere_material = Arc::new(dielectric::new(1.5));
-                    world.add(Arc::new(Sphere::new(center, 0.2, sphere_material)));
-                }
-            }
-        }
-    }
-
-    let material1 = Arc::new(dielectric::new(1.5));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, material1)));
+    world.add(Arc::new(quad::new_disk(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), 8.0, Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5))))));
 
-    let material2 = Arc::new(lambertian::new(Vec3::new(0.4, 0.2, 0.1)));
-    world.add(Arc::new(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, material2)));
+    //flat shapes in the back row, the checker shows their (u, v)
+    let checker = Arc::new(lambertian::new_from_texture(Arc::new(UvChecker::new_from_color(8.0, 8.0, Vec3::new(0.9, 0.9, 0.9), Vec3::new(0.2, 0.3, 0.1)))));
+    world.add(Arc::new(quad::new_triangle(Vec3::new(-4.5, 0.5, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 0.0), checker.clone())));
+    world.add(Arc::new(quad::new_ellipse(Vec3::new(0.0, 1.5, -2.0), Vec3::new(1.2, 0.0, 0.0), Vec3::new(0.0, 0.8, 0.0), checker.clone())));
+    world.add(Arc::new(quad::new_disk(Vec3::new(3.5, 1.5, -2.0), Vec3::new(-0.3, 0.2, 1.0), 1.0, checker.clone())));
 
-    let material3 = Arc::new(metal::new(Vec3::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Arc::new(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, material3)));
+    //solids in front, tilted so the caps show
+    world.add(Arc::new(cylinder::new(Vec3::new(-3.5, 0.0, 1.5), Vec3::new(-3.0, 2.0, 1.0), 0.7, checker.clone())));
+    world.add(Arc::new(cone::new(Vec3::new(0.0, 0.0, 1.5), Vec3::new(0.0, 2.2, 1.5), 0.9, Arc::new(metal::new(Vec3::new(0.8, 0.6, 0.2), 0.1)))));
+    world.add(Arc::new(torus::new(Vec3::new(3.5, 0.9, 1.5), Vec3::new(0.0, 1.0, 1.0), 0.8, 0.3, Arc::new(dielectric::new(1.5)))));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
-                                        VFOV, LOOKFROM, LOOKAT, VUP,
-                                        DEFOCUS_ANGLE, FOCUS_DIST,
-                                        BACKGROUND);
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
-fn perlin_spheres() {
+fn simple_light() {
     let ASPECT_RATIO = 16.0 / 9.0 as f64;
     let IMAGE_WIDTH = 400 as u32;
 
@@ -241,20 +941,24 @@ fn perlin_spheres() {
     let MAX_DEPTH = 50 as u32;
     let VFOV = 20.0 as f64;
 
-    let LOOKFROM = Vec3::new(13.0, 2.0, 3.0);
-    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let LOOKFROM = Vec3::new(26.0, 3.0, 6.0);
+    let LOOKAT = Vec3::new(0.0, 2.0, 0.0);
     let VUP = Vec3::new(0.0, 1.0, 0.0);
 
     let DEFOCUS_ANGLE = 0.0;
     let FOCUS_DIST = 10.0;
 
-    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
 
     let world = &mut (hittable_list::new());
 
-    let pertext = Arc::new(Noise::new(4.0));
+    let pertext = Arc::new(Noise::new(4.0)); 
     world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
     world.add(Arc::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
+    
+    let difflight = Arc::new(diffuse_light::new_from_color(Vec3::new(4.0, 4.0, 4.0)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 7.0, 0.0), 2.0, difflight.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight.clone())));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
@@ -264,57 +968,98 @@ fn perlin_spheres() {
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
-fn quads() {
-    let ASPECT_RATIO = 1.0 as f64;
+fn point_lights() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
     let IMAGE_WIDTH = 400 as u32;
 
     let SAMPLES_PER_PIXEL = 100 as u32;
     let MAX_DEPTH = 50 as u32;
-    let VFOV = 80.0 as f64;
+    let VFOV = 20.0 as f64;
 
-    let LOOKFROM = Vec3::new(0.0, 0.0, 9.0);
-    let LOOKAT = Vec3::new(0.0, 0.0, 0.0);
+    let LOOKFROM = Vec3::new(0.0, 2.0, 10.0);
+    let LOOKAT = Vec3::new(0.0, 0.5, 0.0);
     let VUP = Vec3::new(0.0, 1.0, 0.0);
 
     let DEFOCUS_ANGLE = 0.0;
     let FOCUS_DIST = 10.0;
 
-    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
 
     let world = &mut (hittable_list::new());
 
-    let left_red = Arc::new(lambertian::new(Vec3::new(1.0, 0.2, 0.2)));
-    let back_green = Arc::new(lambertian::new(Vec3::new(0.2, 1.0, 0.2)));
-    let right_blue = Arc::new(lambertian::new(Vec3::new(0.2, 0.2, 1.0)));
-    let upper_orange = Arc::new(lambertian::new(Vec3::new(1.0, 0.5, 0.0)));
-    let lower_teal = Arc::new(lambertian::new(Vec3::new(0.2, 0.8, 0.8)));
+    let ground = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, ground)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-1.2, 0.5, 0.0), 0.5, Arc::new(lambertian::new(Vec3::new(0.8, 0.3, 0.2))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.5, 0.0), 0.5, Arc::new(dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(1.2, 0.5, 0.0), 0.5, Arc::new(metal::new(Vec3::new(0.8, 0.8, 0.8), 0.1)))));
 
-    let sphere_material = Arc::new(dielectric::new(1.5));
-    
-    world.add(Arc::new(quad::new(Vec3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
-    world.add(Arc::new(quad::new(Vec3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
-    world.add(Arc::new(quad::new(Vec3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
-    world.add(Arc::new(quad::new(Vec3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0), upper_orange)));
-    world.add(Arc::new(quad::new(Vec3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -4.0), lower_teal)));
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+    //the two lights of the Games101 phong shader
+    cam.lights.push(Arc::new(point_light::new(Vec3::new(20.0, 20.0, 20.0), Vec3::new(500.0, 500.0, 500.0))));
+    cam.lights.push(Arc::new(point_light::new(Vec3::new(-20.0, 20.0, 0.0), Vec3::new(500.0, 500.0, 500.0))));
+    //a warm spot on the middle sphere and a dim blue fill light
+    cam.lights.push(Arc::new(spot_light::new(Vec3::new(0.0, 4.0, 2.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(30.0, 20.0, 10.0), 20.0, 12.0)));
+    cam.lights.push(Arc::new(directional_light::new(Vec3::new(1.0, -1.0, -1.0), Vec3::new(0.05, 0.05, 0.15))));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
+fn foggy_lights() {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 400 as u32;
+
+    let SAMPLES_PER_PIXEL = 200 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 20.0 as f64;
+
+    let LOOKFROM = Vec3::new(0.0, 2.0, 10.0);
+    let LOOKAT = Vec3::new(0.0, 0.5, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let ground = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, ground.clone())));
+    world.add(Arc::new(Sphere::new(Vec3::new(-1.2, 0.5, 0.0), 0.5, Arc::new(lambertian::new(Vec3::new(0.8, 0.3, 0.2))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.5, 0.0), 0.5, Arc::new(dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(1.2, 0.5, 0.0), 0.5, Arc::new(metal::new(Vec3::new(0.8, 0.8, 0.8), 0.1)))));
+
+    //forward scattering haze around the camera and the lights, so the spot light shows its cone
+    let haze = newbox(Vec3::new(-8.0, 0.001, -8.0), Vec3::new(8.0, 6.0, 12.0), ground.clone());
+    world.add(Arc::new(constant_medium::new_with_phase(Arc::new(haze), 0.04, Vec3::new(1.0, 1.0, 1.0), Arc::new(HenyeyGreenstein::new(0.7)))));
+    //a cloud with a bright silver lining: strong forward and weak backward lobe
+    let puff = Sphere::new(Vec3::new(0.0, 3.0, -3.0), 1.5, ground.clone());
+    let cloud = heterogeneous_medium::new_with_phase(Arc::new(puff), Arc::new(TurbulenceDensity::new(1.5, 7, 2.0)), Vec3::new(1.0, 1.0, 1.0), Arc::new(DoubleHenyeyGreenstein::new(0.8, -0.3, 0.9)));
+    world.add(Arc::new(cloud));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
         DEFOCUS_ANGLE, FOCUS_DIST,
         BACKGROUND);
+    cam.lights.push(Arc::new(spot_light::new(Vec3::new(0.0, 4.0, 2.0), Vec3::new(0.0, 0.0, 0.0), Vec3::new(30.0, 20.0, 10.0), 20.0, 12.0)));
+    cam.lights.push(Arc::new(point_light::new(Vec3::new(0.0, 4.0, -5.0), Vec3::new(20.0, 20.0, 25.0))));
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
-fn simple_light() {
+fn photometric_lights() {
     let ASPECT_RATIO = 16.0 / 9.0 as f64;
     let IMAGE_WIDTH = 400 as u32;
 
-    let SAMPLES_PER_PIXEL = 100 as u32;
+    let SAMPLES_PER_PIXEL = 200 as u32;
     let MAX_DEPTH = 50 as u32;
     let VFOV = 20.0 as f64;
 
-    let LOOKFROM = Vec3::new(26.0, 3.0, 6.0);
-    let LOOKAT = Vec3::new(0.0, 2.0, 0.0);
+    let LOOKFROM = Vec3::new(0.0, 2.0, 10.0);
+    let LOOKAT = Vec3::new(0.0, 0.5, 0.0);
     let VUP = Vec3::new(0.0, 1.0, 0.0);
 
     let DEFOCUS_ANGLE = 0.0;
@@ -324,18 +1069,34 @@ fn simple_light() {
 
     let world = &mut (hittable_list::new());
 
-    let pertext = Arc::new(Noise::new(4.0)); 
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 2.0, 0.0), 2.0, Arc::new(lambertian::new_from_texture(pertext.clone())))));
-    
-    let difflight = Arc::new(diffuse_light::new_from_color(Vec3::new(4.0, 4.0, 4.0)));
-    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 7.0, 0.0), 2.0, difflight.clone())));
-    world.add(Arc::new(quad::new(Vec3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight.clone())));
+    let ground = Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, -1000.0, 0.0), 1000.0, ground)));
+    world.add(Arc::new(Sphere::new(Vec3::new(-1.2, 0.5, 0.0), 0.5, Arc::new(lambertian::new(Vec3::new(0.8, 0.8, 0.8))))));
+    world.add(Arc::new(Sphere::new(Vec3::new(0.0, 0.5, 0.0), 0.5, Arc::new(dielectric::new(1.5)))));
+    world.add(Arc::new(Sphere::new(Vec3::new(1.2, 0.5, 0.0), 0.5, Arc::new(metal::new(Vec3::new(0.8, 0.8, 0.8), 0.1)))));
+
+    //a 1 x 0.5 ceiling panel giving 60 W of incandescent light, only downwards
+    let panel = Arc::new(diffuse_light::emitter_from_power(spectrum::blackbody_color(2700.0), 60.0, false,
+        |mat| quad::new(Vec3::new(-0.5, 3.0, -0.25), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.5), mat)));
+    world.add(panel.clone());
+    //a small cool white strip behind the spheres
+    world.add(Arc::new(quad::new(Vec3::new(-2.0, 0.2, -2.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.1, 0.0), Arc::new(diffuse_light::new_blackbody(9000.0, 4.0)))));
 
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
         DEFOCUS_ANGLE, FOCUS_DIST,
         BACKGROUND);
+    cam.area_lights = Some(panel);
+    //a narrow downlight: a real fixture would come from IesProfile::new("downlight.ies")
+    let downlight = Arc::new(IesProfile::new_from_str("IESNA:LM-63-2002
+[TEST] narrow beam
+TILT=NONE
+1 1000 1 10 1 1 2 0 0 0
+1 1 20
+0 10 20 30 40 50 60 70 80 90
+0
+1200 1100 800 400 150 60 30 10 2 0"));
+    cam.lights.push(Arc::new(spot_light::new_ies(Vec3::new(1.2, 4.0, 0.0), Vec3::new(1.2, 0.0, 0.0), spectrum::blackbody_color(4000.0), downlight)));
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
@@ -366,7 +1127,8 @@ fn cornell_box() {
 
     world.add(Arc::new(quad::new(Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green.clone())));
     world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red.clone())));
-    world.add(Arc::new(quad::new(Vec3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light.clone())));
+    let ceiling_light = Arc::new(quad::new(Vec3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light.clone()));
+    world.add(ceiling_light.clone());
     world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
     world.add(Arc::new(quad::new(Vec3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone())));
     world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
@@ -387,6 +1149,8 @@ fn cornell_box() {
         VFOV, LOOKFROM, LOOKAT, VUP,
         DEFOCUS_ANGLE, FOCUS_DIST,
         BACKGROUND);
+    //aim part of the diffuse rays at the light, much less noise than waiting for rays to find it
+    cam.area_lights = Some(ceiling_light);
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
@@ -443,6 +1207,68 @@ fn cornell_smoke() {
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
     cam.render(world);
 }
+fn cornell_clouds() {
+    let ASPECT_RATIO = 1.0 as f64;
+    let IMAGE_WIDTH = 600 as u32;
+
+    let SAMPLES_PER_PIXEL = 200 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 40.0 as f64;
+
+    let LOOKFROM = Vec3::new(278.0, 278.0, -800.0);
+    let LOOKAT = Vec3::new(278.0, 278.0, 0.0);
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.0, 0.0, 0.0);
+
+    let world = &mut (hittable_list::new());
+
+    let red = Arc::new(lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(diffuse_light::new_from_color(Vec3::new(7.0, 7.0, 7.0)));
+
+    //The Cornell Box
+    world.add(Arc::new(quad::new(Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone())));
+    world.add(Arc::new(quad::new(Vec3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone())));
+
+    //a cloud in three separate puffs: one boundary that is not convex
+    let puffs = &mut (hittable_list::new());
+    puffs.add(Arc::new(Sphere::new(Vec3::new(170.0, 400.0, 300.0), 80.0, white.clone())));
+    puffs.add(Arc::new(Sphere::new(Vec3::new(290.0, 420.0, 320.0), 95.0, white.clone())));
+    puffs.add(Arc::new(Sphere::new(Vec3::new(410.0, 390.0, 290.0), 75.0, white.clone())));
+    let cloud_density = Arc::new(TurbulenceDensity::new(0.02, 7, 0.05));
+    world.add(Arc::new(heterogeneous_medium::new(Arc::new(BVHNode::new_from_list(puffs)), cloud_density, Vec3::new(1.0, 1.0, 1.0))));
+
+    //a smoke column thinning out with height
+    let column = newbox(Vec3::new(80.0, 0.0, 150.0), Vec3::new(230.0, 300.0, 300.0), white.clone());
+    let smoke_density = Arc::new(FunctionDensity::new(Box::new(|p: &Vec3| 0.03 * (-p.y / 120.0).exp()), 0.03));
+    world.add(Arc::new(heterogeneous_medium::new(Arc::new(column), smoke_density, Vec3::new(0.4, 0.4, 0.45))));
+
+    //a soft ball stored on a density grid
+    let (min, max) = (Vec3::new(320.0, 0.0, 150.0), Vec3::new(480.0, 160.0, 310.0));
+    let center = Vec3::new(400.0, 80.0, 230.0);
+    let ball_density = Arc::new(GridDensity::new_from_fn(32, 32, 32, min, max, |p: &Vec3| 0.04 * util::fmax(0.0, 1.0 - (*p - center).length() / 80.0)));
+    let ball = newbox(min, max, white.clone());
+    world.add(Arc::new(heterogeneous_medium::new(Arc::new(ball), ball_density, Vec3::new(0.9, 0.6, 0.3))));
+
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+    //the shadow rays of the point light go through the media by ratio tracking
+    cam.lights.push(Arc::new(point_light::new(Vec3::new(278.0, 500.0, 100.0), Vec3::new(20000.0, 20000.0, 20000.0))));
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    cam.render(world);
+}
 fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
     let ASPECT_RATIO = 1.0 as f64;
     let IMAGE_WIDTH = image_width as u32;
@@ -527,6 +1353,8 @@ fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
         BACKGROUND);
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    //the light quad and any other emitter, picked by how much they light each point
+    cam.area_lights = Some(Arc::new(LightBVH::new_from_world(world)));
     cam.render(world);
 }
 fn final_scene_mod(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
@@ -618,6 +1446,8 @@ fn final_scene_mod(image_width: u32, samples_per_pixel: u32, max_depth: u32) {
         BACKGROUND);
 
     let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    //the light quad and any other emitter, picked by how much they light each point
+    cam.area_lights = Some(Arc::new(LightBVH::new_from_world(world)));
     cam.render(world);
 }
 
@@ -642,88 +1472,111 @@ fn minecraft() {
 
     let dirt_tex = Arc::new(Image::new("dirt.png"));
     let dirt = Arc::new(lambertian::new_from_texture(dirt_tex.clone()));
-
-    for i in 0..3 {
-        for j in 0..3 {
-            for k in 0..3 {
-                world.add(Arc::new(newbox(Vec3::new(i as f64, j as f64, k as f64), Vec3::new(i as f64 + 1.0, j as f64 + 1.0, k as f64 + 1.0), dirt.clone())));
-            }
-        }
-    }
-
-    for i in 3..6 {
-        for j in 0..3 {
-            for k in 0..3 {
-                world.add(Arc::new(newbox(Vec3::new(i as f64, j as f64, k as f64), Vec3::new(i as f64 + 1.0, j as f64 + 1.0, k as f64 + 1.0), dirt.clone())));
-            }
-        }
-    }
-
-    for i in 0..3 {
-        for j in 0..3 {
-            for k in 3..6 {
-                world.add(Arc::new(newbox(Vec3::new(i as f64, j as f64, k as f64), Vec3::new(i as f64 + 1.0, j as f64 + 1.0, k as f64 + 1.0), dirt.clone())));
-            }
-        }
-    }
-
+    let grass_top_tex = Arc::new(Image::new("grass_block_top.png"));
+    let grass_top = Arc::new(lambertian::new_from_texture(grass_top_tex.clone()));
+    let grass_side_tex = Arc::new(Image::new("grass_block_side.png"));
+    let grass_side = Arc::new(lambertian::new_from_texture(grass_side_tex.clone()));
     let oak_tex = Arc::new(Image::new("oak_log.png"));
     let oak = Arc::new(lambertian::new_from_texture(oak_tex.clone()));
-    for j in 3..7 {
-        let i = 1.0 as f64;
-        let k = 4.0 as f64;
-        world.add(Arc::new(newbox(Vec3::new(i, j as f64, k), Vec3::new(i + 1.0, j as f64 + 1.0, k + 1.0), oak.clone())));
-    }
-
     let oak_leaves_tex = Arc::new(Image::new("oak_leaves.png"));
-    let oak_leaves = Arc::new(lambertian::new_from_texture(oak_leaves_tex.clone()));
-    for i in -1..4 {
-        for k in 2..7 {
-            let j = 6.0 as f64;
-            if (i == -1 && k == 2) || (i == 3 && k == 2) || (i == -1 && k == 6) || (i == 3 && k == 6) {
-                continue;
-            }
-            if i == 1 && k == 4 {
-                continue;
-            }
-            world.add(Arc::new(newbox(Vec3::new(i as f64, j, k as f64), Vec3::new(i as f64 + 1.0, j + 1.0, k as f64 + 1.0), oak_leaves.clone())));
-        }
-    }
-
-    for i in -1..4 {
-        for k in 2..7 {
-            let j = 7.0 as f64;
-            if (i == -1 && k == 2) || (i == 3 && k == 2) || (i == -1 && k == 6) || (i == 3 && k == 6) {
-                continue;
-            }
-            world.add(Arc::new(newbox(Vec3::new(i as f64, j, k as f64), Vec3::new(i as f64 + 1.0, j + 1.0, k as f64 + 1.0), oak_leaves.clone())));
-        }
+    let oak_leaves = Arc::new(alpha_mask::new(Arc::new(lambertian::new_from_texture(oak_leaves_tex.clone())), oak_leaves_tex.clone(), 0.5));
+    let chest_top_tex = Arc::new(Image::new("chest_top.png"));
+    let chest_top = Arc::new(lambertian::new_from_texture(chest_top_tex.clone()));
+    let chest_side_tex = Arc::new(Image::new("chest_side.png"));
+    let chest_side = Arc::new(lambertian::new_from_texture(chest_side_tex.clone()));
+    let chest_front_tex = Arc::new(Image::new("chest_front.png"));
+    let chest_front = Arc::new(lambertian::new_from_texture(chest_front_tex.clone()));
+
+    let mut blocks = VoxelGrid::new([-1, 0, 0], [6, 10, 7], 1.0);
+    let dirt_id = blocks.register_block(Block::new(dirt.clone()));
+    let grass_id = blocks.register_block(Block::new_top_side_bottom(grass_top.clone(), grass_side.clone(), dirt.clone()));
+    let oak_id = blocks.register_block(Block::new(oak.clone()));
+    let mut leaves = Block::new(oak_leaves.clone());
+    leaves.cutout = true;
+    let leaves_id = blocks.register_block(leaves);
+    //the chest looks towards -x
+    let chest_id = blocks.register_block(Block::new_from_faces([chest_side.clone(), chest_front.clone(), 
+                                                            chest_top.clone(), chest_top.clone(), 
+                                                            chest_side.clone(), chest_side.clone()]));
+
+    //L-shaped ground, grass on the top layer
+    blocks.fill([0, 0, 0], [6, 2, 3], dirt_id);
+    blocks.fill([0, 2, 0], [6, 3, 3], grass_id);
+    blocks.fill([0, 0, 3], [3, 2, 6], dirt_id);
+    blocks.fill([0, 2, 3], [3, 3, 6], grass_id);
+
+    //the tree
+    blocks.fill([-1, 6, 2], [4, 8, 7], leaves_id);
+    blocks.fill([0, 8, 3], [3, 10, 6], leaves_id);
+    for (i, k) in [(-1, 2), (3, 2), (-1, 6), (3, 6)] {
+        blocks.set(i, 6, k, 0);
+        blocks.set(i, 7, k, 0);
     }
-
-    for i in 0..3 {
-        for k in 3..6 {
-            let j = 8.0 as f64;
-            world.add(Arc::new(newbox(Vec3::new(i as f64, j, k as f64), Vec3::new(i as f64 + 1.0, j + 1.0, k as f64 + 1.0), oak_leaves.clone())));
-        }
+    for (i, k) in [(0, 3), (2, 3), (0, 5), (2, 5)] {
+        blocks.set(i, 9, k, 0);
     }
+    blocks.fill([1, 3, 4], [2, 7, 5], oak_id);
 
-    for i in 0..3 {
-        for k in 3..6 {
-            let j = 9.0 as f64;
-            if (i == 0 && k == 3) || (i == 2 && k == 3) || (i == 0 && k == 5) || (i == 2 && k == 5) {
-                continue;
-            }
-            world.add(Arc::new(newbox(Vec3::new(i as f64, j, k as f64), Vec3::new(i as f64 + 1.0, j + 1.0, k as f64 + 1.0), oak_leaves.clone())));
-        }
-    }
+    blocks.set(5, 3, 1, chest_id);
 
-    world.add(Arc::new(newbox(Vec3::new(5.0, 3.0, 1.0), Vec3::new(6.0, 4.0, 2.0), oak_leaves.clone())));
+    world.add(Arc::new(blocks));
 
     let light = Arc::new(diffuse_light::new_from_color(Vec3::new(50.0, 50.0, 50.0)));
     world.add(Arc::new(Sphere::new(Vec3::new(-20.0, 50.0, 10.0), 10.0, light.clone())));
     world.add(Arc::new(Sphere::new(Vec3::new(2.0, 50.0, -10.0), 10.0, light.clone())));
 
 
+    let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
+        VFOV, LOOKFROM, LOOKAT, VUP,
+        DEFOCUS_ANGLE, FOCUS_DIST,
+        BACKGROUND);
+
+    let world = &mut (hittable_list::new_from_object(Arc::new(BVHNode::new_from_list(world))));
+    //the light quad and any other emitter, picked by how much they light each point
+    cam.area_lights = Some(Arc::new(LightBVH::new_from_world(world)));
+    cam.render(world);
+}
+
+//render a real build: a Sponge .schem, a structure block .nbt or a MagicaVoxel .vox
+fn minecraft_build(filename: &str) {
+    let ASPECT_RATIO = 16.0 / 9.0 as f64;
+    let IMAGE_WIDTH = 800 as u32;
+
+    let SAMPLES_PER_PIXEL = 100 as u32;
+    let MAX_DEPTH = 50 as u32;
+    let VFOV = 40.0 as f64;
+
+    let VUP = Vec3::new(0.0, 1.0, 0.0);
+
+    let DEFOCUS_ANGLE = 0.0;
+    let FOCUS_DIST = 10.0;
+
+    let BACKGROUND = Vec3::new(0.7, 0.8, 1.0);
+
+    let world = &mut (hittable_list::new());
+
+    let blocks = if filename.ends_with(".vox") {
+        VoxModel::load(filename).to_voxel_grid(1.0)
+    } else {
+        let mut textures = BlockTextures::new();
+        let grid = Structure::load(filename).to_voxel_grid(&mut textures, 1.0);
+        if !textures.missing.is_empty() {
+            let mut missing: Vec<&String> = textures.missing.iter().collect();
+            missing.sort();
+            println!("No texture for {} block(s), drawn gray: {:?}", missing.len(), missing);
+        }
+        grid
+    };
+
+    //look at the build from a corner, far enough to see all of it
+    let bbox = blocks.bbox();
+    let center = Vec3::new((bbox.x.tmin + bbox.x.tmax) / 2.0, (bbox.y.tmin + bbox.y.tmax) / 2.0, (bbox.z.tmin + bbox.z.tmax) / 2.0);
+    let radius = Vec3::new(bbox.x.size(), bbox.y.size(), bbox.z.size()).length() / 2.0;
+    let LOOKAT = center;
+    let LOOKFROM = center + Vec3::new(-1.0, 0.8, -1.0).unit_vector() * (radius * 2.5);
+
+    world.add(Arc::new(blocks));
+
     let mut cam: Camera = Camera::new(ASPECT_RATIO, IMAGE_WIDTH, 100 as u8, SAMPLES_PER_PIXEL, MAX_DEPTH, 
         VFOV, LOOKFROM, LOOKAT, VUP,
         DEFOCUS_ANGLE, FOCUS_DIST,
@@ -735,16 +1588,35 @@ fn minecraft() {
 
 fn main() {
     // bouncing_spheres();
+    // environment_spheres("sky.hdr");
+    // sky_spheres(30.0);
+    // hazy_spheres();
+    // metal_spheres();
+    // glass();
+    // dispersion();
+    // principled_spheres();
+    // layered_materials();
     // checkered_spheres();
+    // texture_graph();
     // earth();
     // baihua();
     // perlin_spheres();
+    // procedural_spheres();
+    // noise_spheres(0.0);
+    // subsurface_spheres();
+    // bump_spheres();
     // quads();
+    // primitives();
     // simple_light();
+    // point_lights();
+    // foggy_lights();
+    // photometric_lights();
     cornell_box();
     // cornell_smoke();
+    // cornell_clouds();
     // final_scene(800, 5000, 40);
     // final_scene_mod(800, 5000, 40);
     // final_scene(200, 50, 40)
     // minecraft();
+    // minecraft_build("./mybuilds/house.schem");
 }
@@ -1,13 +1,41 @@
 pub use crate::ray::Ray;
 pub use crate::vec3::Vec3;
 use crate::util;
+use crate::color;
 use crate::hit_record;
+use crate::hittables::hittable;
 use std::sync::Arc;
-use crate::textures::{texture, SolidColor};
+use crate::textures::{texture, SolidColor, Image};
+use crate::phase::phase_function;
+use crate::perlins::noise_source;
+use crate::spectrum::{self, Dispersion};
+use crate::lights::IesProfile;
+use crate::microfacet::{Frame, TrowbridgeReitz, fresnel_conductor, fresnel_dielectric, reflect, sample_dielectric, dielectric_pdf, dielectric_eval};
 
 pub trait material : Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool;
     fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+    //emission seen by r_in at the hit, for emitters that shine only one way or not equally in all directions
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.emitted(rec.u, rec.v, &rec.p)
+    }
+    //false when emitted_toward() is dark behind the front face, for the light BVH's bounds
+    fn emits_two_sided(&self) -> bool {
+        true
+    }
+    //false lets the ray pass through the surface at (u, v) as if nothing was hit
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        true
+    }
+    //solid angle density with which scatter() picks the direction of `scattered`
+    //0 for specular materials, they cannot be combined with light sampling
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        0.0
+    }
+    //BSDF times cosine for the direction of `scattered`, attenuation = eval / scattering_pdf for scatter()'s own rays
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        Vec3::zero()
+    }
 }
 
 pub struct lambertian {
@@ -34,12 +62,20 @@ impl material for lambertian {
             scatter_direction = rec.normal;
         }
         *scattered = Ray::new(rec.p, scatter_direction, r_in.time);
-        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        *attenuation = self.tex.value_at(rec);
         true
     }
     fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
         Vec3::zero()
     }
+    //normal + random unit vector is cosine distributed
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        let cos_theta = rec.normal * scattered.b_direction.unit_vector();
+        util::fmax(0.0, cos_theta / std::f64::consts::PI)
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        self.tex.value_at(rec) * self.scattering_pdf(r_in, rec, scattered)
+    }
 }
 
 pub struct metal {
@@ -69,58 +105,883 @@ impl material for metal {
     }
 }
 
+//Rough metal: Cook-Torrance microfacet reflection with a GGX distribution and the Fresnel term of a complex
+//index of refraction eta + i k, per color channel (roughly 650, 550 and 450 nm)
+//roughness 0 is a perfect mirror; different roughness along u and v gives brushed-metal highlights
+pub struct conductor {
+    pub eta: Vec3,
+    pub k: Vec3,
+    //perceptual roughness along dpdu and dpdv
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+    //when set, the average of its rgb multiplies both roughness values
+    pub roughness_tex: Option<Arc<dyn texture + Send + Sync>>,
+}
+
+impl conductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f64) -> Self {
+        Self::new_anisotropic(eta, k, roughness, roughness)
+    }
+    pub fn new_anisotropic(eta: Vec3, k: Vec3, roughness_u: f64, roughness_v: f64) -> Self {
+        Self {
+            eta,
+            k,
+            roughness_u,
+            roughness_v,
+            roughness_tex: None,
+        }
+    }
+    pub fn new_from_texture(eta: Vec3, k: Vec3, roughness: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            eta,
+            k,
+            roughness_u: 1.0,
+            roughness_v: 1.0,
+            roughness_tex: Some(roughness),
+        }
+    }
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(Vec3::new(0.143, 0.374, 1.442), Vec3::new(3.983, 2.385, 1.603), roughness)
+    }
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(Vec3::new(0.200, 0.924, 1.102), Vec3::new(3.912, 2.452, 2.142), roughness)
+    }
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(Vec3::new(0.155, 0.117, 0.138), Vec3::new(4.828, 3.122, 2.147), roughness)
+    }
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    fn distribution(&self, rec: &hit_record) -> TrowbridgeReitz {
+        let scale = match &self.roughness_tex {
+            Some(tex) => {
+                let c = tex.value_at(rec);
+                (c.x + c.y + c.z) / 3.0
+            }
+            None => 1.0,
+        };
+        TrowbridgeReitz::new(TrowbridgeReitz::roughness_to_alpha(self.roughness_u * scale),
+                             TrowbridgeReitz::roughness_to_alpha(self.roughness_v * scale))
+    }
+}
+
+impl material for conductor {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let distrib = self.distribution(rec);
+        if distrib.effectively_smooth() {
+            let wi = Vec3::new(-wo.x, -wo.y, wo.z);
+            *attenuation = fresnel_conductor(wo.z, self.eta, self.k);
+            *scattered = Ray::new(rec.p, frame.to_world(wi), r_in.time);
+            return true;
+        }
+        //reflect about a visible microfacet normal, the weight f cos / pdf reduces to F G2 / G1
+        let wm = distrib.sample_visible(wo);
+        let wi = reflect(wo, wm);
+        if wi.z <= 0.0 {
+            return false;
+        }
+        *attenuation = fresnel_conductor(wo * wm, self.eta, self.k) * (distrib.g(wo, wi) / distrib.g1(wo));
+        *scattered = Ray::new(rec.p, frame.to_world(wi), r_in.time);
+        true
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        let distrib = self.distribution(rec);
+        if distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vector();
+        distrib.d_visible(wo, wm) / (4.0 * (wo * wm))
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        let distrib = self.distribution(rec);
+        if distrib.effectively_smooth() {
+            return Vec3::zero();
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Vec3::zero();
+        }
+        let wm = (wo + wi).unit_vector();
+        //F D G / (4 cos_o cos_i), times cos_i
+        fresnel_conductor(wo * wm, self.eta, self.k) * (distrib.d(wm) * distrib.g(wo, wi) / (4.0 * wo.z))
+    }
+}
+
+//Glass and water: reflects or refracts by the Fresnel term
+//roughness above 0 gives frosted glass (GGX microfacet transmission, Walter et al. 2007), and absorption
+//tints the light by Beer-Lambert while it travels inside, so thick glass gets darker than thin glass
+//with a dispersion the index depends on the wavelength, which splits light into colors in spectral mode
 pub struct dielectric {
     pub refraction_index: f64,
+    //perceptual roughness, 0 is perfectly smooth
+    pub roughness: f64,
+    //absorption coefficient per unit distance for each color channel
+    pub absorption: Vec3,
+    pub dispersion: Option<Dispersion>,
 }
 
 impl dielectric {
     pub fn new(refraction_index: f64) -> Self {
         Self {
             refraction_index,
+            roughness: 0.0,
+            absorption: Vec3::zero(),
+            dispersion: None,
+        }
+    }
+    pub fn new_rough(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            roughness,
+            absorption: Vec3::zero(),
+            dispersion: None,
+        }
+    }
+    //colored glass: light that has travelled `distance` inside keeps `color` of its energy
+    pub fn new_tinted(refraction_index: f64, color: Vec3, distance: f64) -> Self {
+        let coefficient = |c: f64| -> f64 { -util::fmax(c, 1e-6).min(1.0).ln() / distance };
+        Self {
+            refraction_index,
+            roughness: 0.0,
+            absorption: Vec3::new(coefficient(color.x), coefficient(color.y), coefficient(color.z)),
+            dispersion: None,
+        }
+    }
+    //prisms and diamonds, refraction_index is the index at the sodium D line used in RGB mode
+    pub fn new_dispersive(dispersion: Dispersion) -> Self {
+        Self {
+            refraction_index: dispersion.ior_d(),
+            roughness: 0.0,
+            absorption: Vec3::zero(),
+            dispersion: Some(dispersion),
+        }
+    }
+
+    //Beer-Lambert transmittance of the path through the inside that ends at this hit, 1 when hit from outside
+    fn transmittance(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        if rec.front_face || self.absorption.near_zero() {
+            return Vec3::new(1.0, 1.0, 1.0);
+        }
+        let distance = (rec.p - r_in.a_origin).length();
+        Vec3::new(
+            (-self.absorption.x * distance).exp(),
+            (-self.absorption.y * distance).exp(),
+            (-self.absorption.z * distance).exp(),
+        )
+    }
+    fn distribution(&self) -> TrowbridgeReitz {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(self.roughness);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+    //the index at the hero wavelength of a spectral ray
+    fn ior(&self, r_in: &Ray) -> f64 {
+        match (&self.dispersion, &r_in.wavelengths) {
+            (Some(dispersion), Some(wavelengths)) => dispersion.ior(wavelengths.hero()),
+            _ => self.refraction_index,
+        }
+    }
+    //index of refraction of the far side over the near side
+    fn relative_ior(&self, r_in: &Ray, rec: &hit_record) -> f64 {
+        if rec.front_face {
+            self.ior(r_in)
+        } else {
+            1.0 / self.ior(r_in)
+        }
+    }
+    //the other wavelengths would have gone elsewhere, the path only follows the hero from here on
+    fn split_wavelengths(&self, r_in: &Ray, scattered: &mut Ray) {
+        if let (Some(_), Some(mut wavelengths)) = (&self.dispersion, r_in.wavelengths) {
+            wavelengths.terminate_secondary();
+            scattered.wavelengths = Some(wavelengths);
         }
     }
 }
 
 impl material for dielectric {
     fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
-        *attenuation = Vec3::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.refraction_index
-        } else {
-            self.refraction_index
+        let transmittance = self.transmittance(r_in, rec);
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            *attenuation = transmittance;
+            let refraction_ratio = 1.0 / self.relative_ior(r_in, rec);
+            let unit_direction = r_in.b_direction.unit_vector();
+            let cos_theta = util::fmin((unit_direction * (-1.0)) * rec.normal, 1.0);
+            let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+
+            let cannot_refract = refraction_ratio * sin_theta > 1.0;
+            let refracted = if cannot_refract || util::reflectance(cos_theta, refraction_ratio) > util::random_f64_0_1(){
+                util::reflect(unit_direction, rec.normal)
+            } else {
+                util::refract(unit_direction, rec.normal, refraction_ratio)
+            };
+            *scattered = Ray::new(rec.p, refracted, r_in.time);
+            self.split_wavelengths(r_in, scattered);
+            return true;
+        }
+
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let wi = match sample_dielectric(&distrib, wo, self.relative_ior(r_in, rec)) {
+            Some(wi) => wi,
+            None => return false,
         };
+        *attenuation = transmittance * (distrib.g(wo, wi) / distrib.g1(wo));
+        *scattered = Ray::new(rec.p, frame.to_world(wi), r_in.time);
+        self.split_wavelengths(r_in, scattered);
+        true
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        dielectric_pdf(&distrib, wo, wi, self.relative_ior(r_in, rec))
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            return Vec3::zero();
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        self.transmittance(r_in, rec) * dielectric_eval(&distrib, wo, wi, self.relative_ior(r_in, rec))
+    }
+}
+
+//Infinitely thin glass such as a window pane made of a quad: light is reflected or passes straight through,
+//the reflections bouncing between the two faces of the pane are summed up (PBRT's ThinDielectricBxDF)
+pub struct thin_dielectric {
+    pub refraction_index: f64,
+}
+
+impl thin_dielectric {
+    pub fn new(refraction_index: f64) -> Self {
+        Self {
+            refraction_index,
+        }
+    }
+}
+
+impl material for thin_dielectric {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
         let unit_direction = r_in.b_direction.unit_vector();
         let cos_theta = util::fmin((unit_direction * (-1.0)) * rec.normal, 1.0);
-        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
-
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let refracted = if cannot_refract || util::reflectance(cos_theta, refraction_ratio) > util::random_f64_0_1(){
+        let mut r = fresnel_dielectric(cos_theta, self.refraction_index);
+        if r < 1.0 {
+            let t = 1.0 - r;
+            r += t * t * r / (1.0 - r * r);
+        }
+        let direction = if util::random_f64_0_1() < r {
             util::reflect(unit_direction, rec.normal)
         } else {
-            util::refract(unit_direction, rec.normal, refraction_ratio)
+            unit_direction
+        };
+        *attenuation = Vec3::new(1.0, 1.0, 1.0);
+        *scattered = Ray::new(rec.p, direction, r_in.time);
+        true
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+}
+
+//One material for most surfaces, after Burley's "Physically Based Shading at Disney" (2012, 2015):
+//a diffuse base with a subsurface look and sheen, a GGX specular layer that becomes metal with `metallic`,
+//a clearcoat on top, and rough glass for `transmission`
+//every parameter is a texture, scalar ones use the average of its rgb
+pub struct principled {
+    pub base_color: Arc<dyn texture + Send + Sync>,
+    pub metallic: Arc<dyn texture + Send + Sync>,
+    pub roughness: Arc<dyn texture + Send + Sync>,
+    //strength of the dielectric reflection, 0.5 is a reflectance of 4% (index of refraction 1.5)
+    pub specular: Arc<dyn texture + Send + Sync>,
+    //tints that reflection towards the base color
+    pub specular_tint: Arc<dyn texture + Send + Sync>,
+    //extra reflection at grazing angles for cloth
+    pub sheen: Arc<dyn texture + Send + Sync>,
+    pub sheen_tint: Arc<dyn texture + Send + Sync>,
+    //a second, white specular layer
+    pub clearcoat: Arc<dyn texture + Send + Sync>,
+    pub clearcoat_gloss: Arc<dyn texture + Send + Sync>,
+    //blends the diffuse base into rough glass tinted by the base color
+    pub transmission: Arc<dyn texture + Send + Sync>,
+    //flattens the diffuse base like light scattered under the surface
+    pub subsurface: Arc<dyn texture + Send + Sync>,
+    //index of refraction of the transmission lobe
+    pub ior: f64,
+}
+
+//the parameters read at one hit
+struct PrincipledParams {
+    base_color: Vec3,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    sheen_tint: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    transmission: f64,
+    subsurface: f64,
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0);
+    m * m * m * m * m
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn lerp_color(a: Vec3, b: Vec3, t: f64) -> Vec3 {
+    a * (1.0 - t) + b * t
+}
+
+//Berry's distribution (GTR1) used by the clearcoat, its long tail gives a glow around the highlight
+fn gtr1(cos_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    if a2 >= 1.0 {
+        return 1.0 / std::f64::consts::PI;
+    }
+    (a2 - 1.0) / (std::f64::consts::PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+}
+
+impl principled {
+    pub fn new(base_color: Vec3, metallic: f64, roughness: f64) -> Self {
+        let mut mat = Self::new_from_texture(Arc::new(SolidColor::new(base_color)));
+        mat.metallic = Arc::new(SolidColor::new_gray(metallic));
+        mat.roughness = Arc::new(SolidColor::new_gray(roughness));
+        mat
+    }
+    pub fn new_from_texture(base_color: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            base_color,
+            metallic: Arc::new(SolidColor::new_gray(0.0)),
+            roughness: Arc::new(SolidColor::new_gray(0.5)),
+            specular: Arc::new(SolidColor::new_gray(0.5)),
+            specular_tint: Arc::new(SolidColor::new_gray(0.0)),
+            sheen: Arc::new(SolidColor::new_gray(0.0)),
+            sheen_tint: Arc::new(SolidColor::new_gray(0.5)),
+            clearcoat: Arc::new(SolidColor::new_gray(0.0)),
+            clearcoat_gloss: Arc::new(SolidColor::new_gray(1.0)),
+            transmission: Arc::new(SolidColor::new_gray(0.0)),
+            subsurface: Arc::new(SolidColor::new_gray(0.0)),
+            ior: 1.5,
+        }
+    }
+
+    fn params(&self, rec: &hit_record) -> PrincipledParams {
+        let scalar = |tex: &Arc<dyn texture + Send + Sync>| -> f64 {
+            let c = tex.value_at(rec);
+            ((c.x + c.y + c.z) / 3.0).clamp(0.0, 1.0)
+        };
+        PrincipledParams {
+            base_color: self.base_color.value_at(rec),
+            metallic: scalar(&self.metallic),
+            roughness: scalar(&self.roughness),
+            specular: scalar(&self.specular),
+            specular_tint: scalar(&self.specular_tint),
+            sheen: scalar(&self.sheen),
+            sheen_tint: scalar(&self.sheen_tint),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_gloss: scalar(&self.clearcoat_gloss),
+            transmission: scalar(&self.transmission),
+            subsurface: scalar(&self.subsurface),
+        }
+    }
+    //kept just above the mirror limit so every lobe still has a density for light sampling
+    fn distribution(params: &PrincipledParams) -> TrowbridgeReitz {
+        let alpha = util::fmax(TrowbridgeReitz::roughness_to_alpha(params.roughness), 1e-3);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+    //base color hue at full luminance
+    fn tint(params: &PrincipledParams) -> Vec3 {
+        let lum = color::luminance(params.base_color);
+        if lum > 0.0 { params.base_color / lum } else { Vec3::new(1.0, 1.0, 1.0) }
+    }
+    fn specular_color(params: &PrincipledParams) -> Vec3 {
+        let dielectric = lerp_color(Vec3::new(1.0, 1.0, 1.0), Self::tint(params), params.specular_tint) * (params.specular * 0.08);
+        lerp_color(dielectric, params.base_color, params.metallic)
+    }
+    //how strongly each lobe contributes: diffuse, specular, clearcoat, transmission
+    fn lobe_weights(params: &PrincipledParams) -> [f64; 4] {
+        let dielectric = 1.0 - params.metallic;
+        [
+            dielectric * (1.0 - params.transmission),
+            1.0 - dielectric * params.transmission,
+            0.25 * params.clearcoat,
+            dielectric * params.transmission,
+        ]
+    }
+    //probability of sampling each lobe, roughly by the energy it reflects towards wo
+    fn lobe_probabilities(params: &PrincipledParams, wo: Vec3) -> [f64; 4] {
+        let w = Self::lobe_weights(params);
+        let fresnel = schlick_weight(wo.z);
+        let spec = Self::specular_color(params);
+        let spec = spec + (Vec3::new(1.0, 1.0, 1.0) - spec) * fresnel;
+        let mut p = [
+            w[0] * color::luminance(params.base_color),
+            w[1] * color::luminance(spec),
+            w[2] * lerp(0.04, 1.0, fresnel),
+            w[3],
+        ];
+        let total: f64 = p.iter().sum();
+        if total <= 0.0 {
+            return [1.0, 0.0, 0.0, 0.0];
+        }
+        for x in p.iter_mut() {
+            *x /= total;
+        }
+        p
+    }
+
+    //BSDF times cosine in the local frame
+    fn eval_local(params: &PrincipledParams, wo: Vec3, wi: Vec3, eta: f64) -> Vec3 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Vec3::zero();
+        }
+        let w = Self::lobe_weights(params);
+        let distrib = Self::distribution(params);
+        let mut f = Vec3::zero();
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit_vector();
+            let cos_d = wi * wh;
+            let fl = schlick_weight(wi.z);
+            let fv = schlick_weight(wo.z);
+            let fh = schlick_weight(cos_d);
+
+            if w[0] > 0.0 {
+                //retro-reflection at grazing angles for rough surfaces, or Hanrahan-Krueger-like flattening
+                let fd90 = 0.5 + 2.0 * cos_d * cos_d * params.roughness;
+                let fd = lerp(1.0, fd90, fl) * lerp(1.0, fd90, fv);
+                let fss90 = cos_d * cos_d * params.roughness;
+                let fss = lerp(1.0, fss90, fl) * lerp(1.0, fss90, fv);
+                let ss = 1.25 * (fss * (1.0 / (wi.z + wo.z) - 0.5) + 0.5);
+                let diffuse = params.base_color * (lerp(fd, ss, params.subsurface) / std::f64::consts::PI);
+                let sheen = lerp_color(Vec3::new(1.0, 1.0, 1.0), Self::tint(params), params.sheen_tint) * (fh * params.sheen);
+                f = f + (diffuse + sheen) * w[0];
+            }
+            if w[1] > 0.0 {
+                let spec = Self::specular_color(params);
+                let fresnel = spec + (Vec3::new(1.0, 1.0, 1.0) - spec) * fh;
+                f = f + fresnel * (w[1] * distrib.d(wh) * distrib.g(wo, wi) / (4.0 * wo.z * wi.z));
+            }
+            if w[2] > 0.0 {
+                let d = gtr1(wh.z, lerp(0.1, 0.001, params.clearcoat_gloss));
+                let coat = TrowbridgeReitz::new(0.25, 0.25);
+                let g = coat.g1(wo) * coat.g1(wi);
+                let fresnel = lerp(0.04, 1.0, fh);
+                let value = w[2] * d * fresnel * g / (4.0 * wo.z * wi.z);
+                f = f + Vec3::new(value, value, value);
+            }
+        }
+        let mut f = f * wi.z.abs();
+        if w[3] > 0.0 {
+            let glass = dielectric_eval(&distrib, wo, wi, eta) * w[3];
+            //only light that went through is colored
+            f = f + if wi.z < 0.0 { params.base_color * glass } else { Vec3::new(glass, glass, glass) };
+        }
+        f
+    }
+    fn pdf_local(params: &PrincipledParams, wo: Vec3, wi: Vec3, eta: f64) -> f64 {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return 0.0;
+        }
+        let p = Self::lobe_probabilities(params, wo);
+        let distrib = Self::distribution(params);
+        let mut pdf = 0.0;
+        if wi.z > 0.0 {
+            let wh = (wo + wi).unit_vector();
+            pdf += p[0] * wi.z / std::f64::consts::PI;
+            pdf += p[1] * distrib.d_visible(wo, wh) / (4.0 * (wo * wh));
+            if p[2] > 0.0 {
+                let d = gtr1(wh.z, lerp(0.1, 0.001, params.clearcoat_gloss));
+                pdf += p[2] * d * wh.z / (4.0 * (wo * wh));
+            }
+        }
+        if p[3] > 0.0 {
+            pdf += p[3] * dielectric_pdf(&distrib, wo, wi, eta);
+        }
+        pdf
+    }
+    fn sample_local(params: &PrincipledParams, wo: Vec3, eta: f64) -> Option<Vec3> {
+        let p = Self::lobe_probabilities(params, wo);
+        let distrib = Self::distribution(params);
+        let xi = util::random_f64_0_1();
+        let wi = if xi < p[0] {
+            //cosine weighted
+            let d = Vec3::new(0.0, 0.0, 1.0) + util::random_on_unit_sphere();
+            if d.near_zero() { Vec3::new(0.0, 0.0, 1.0) } else { d.unit_vector() }
+        } else if xi < p[0] + p[1] {
+            reflect(wo, distrib.sample_visible(wo))
+        } else if xi < p[0] + p[1] + p[2] {
+            let alpha = lerp(0.1, 0.001, params.clearcoat_gloss);
+            let a2 = alpha * alpha;
+            let cos_h = ((1.0 - a2.powf(1.0 - util::random_f64_0_1())) / (1.0 - a2)).max(0.0).sqrt();
+            let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+            let phi = 2.0 * std::f64::consts::PI * util::random_f64_0_1();
+            reflect(wo, Vec3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h))
+        } else {
+            sample_dielectric(&distrib, wo, eta)?
         };
-        *scattered = Ray::new(rec.p, refracted, r_in.time);
+        if wi.z == 0.0 {
+            return None;
+        }
+        Some(wi)
+    }
+    fn relative_ior(&self, rec: &hit_record) -> f64 {
+        if rec.front_face { self.ior } else { 1.0 / self.ior }
+    }
+}
+
+impl material for principled {
+    //one lobe is sampled, the weight uses the density of all of them together
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        let params = self.params(rec);
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let eta = self.relative_ior(rec);
+        let wi = match Self::sample_local(&params, wo, eta) {
+            Some(wi) => wi,
+            None => return false,
+        };
+        let pdf = Self::pdf_local(&params, wo, wi, eta);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *attenuation = Self::eval_local(&params, wo, wi, eta) / pdf;
+        *scattered = Ray::new(rec.p, frame.to_world(wi), r_in.time);
         true
     }
     fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
         Vec3::zero()
     }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        Self::pdf_local(&self.params(rec), wo, wi, self.relative_ior(rec))
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        Self::eval_local(&self.params(rec), wo, wi, self.relative_ior(rec))
+    }
+}
+
+//Blend of two materials, `amount` 0 is all `a` and 1 is all `b` (the rgb average of the texture)
+//each hit picks one of them with that probability, like PBRT's MixMaterial
+//the pick is a hash of the hit point, so alpha_test, scatter, eval and scattering_pdf all agree on it
+pub struct mix {
+    pub a: Arc<dyn material + Send + Sync>,
+    pub b: Arc<dyn material + Send + Sync>,
+    pub amount: Arc<dyn texture + Send + Sync>,
+}
+
+impl mix {
+    pub fn new(a: Arc<dyn material + Send + Sync>, b: Arc<dyn material + Send + Sync>, amount: f64) -> Self {
+        Self {
+            a,
+            b,
+            amount: Arc::new(SolidColor::new_gray(amount)),
+        }
+    }
+    //painted metal, rust and so on: the mask decides where `b` shows through
+    pub fn new_from_texture(a: Arc<dyn material + Send + Sync>, b: Arc<dyn material + Send + Sync>, mask: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            a,
+            b,
+            amount: mask,
+        }
+    }
+
+    fn amount_at(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let c = self.amount.value(u, v, p);
+        ((c.x + c.y + c.z) / 3.0).clamp(0.0, 1.0)
+    }
+    //alpha_test only knows the hit point, so that is all the pick may depend on
+    fn choose_at(&self, u: f64, v: f64, p: &Vec3) -> &Arc<dyn material + Send + Sync> {
+        if util::hash_to_unit(&[p.x, p.y, p.z]) < self.amount_at(u, v, p) {
+            &self.b
+        } else {
+            &self.a
+        }
+    }
+    fn choose(&self, r_in: &Ray, rec: &hit_record) -> &Arc<dyn material + Send + Sync> {
+        self.choose_at(rec.u, rec.v, &rec.p)
+    }
+}
+
+impl material for mix {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        self.choose(r_in, rec).scatter(r_in, rec, attenuation, scattered)
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let t = self.amount_at(u, v, p);
+        self.a.emitted(u, v, p) * (1.0 - t) + self.b.emitted(u, v, p) * t
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        let t = self.amount_at(rec.u, rec.v, &rec.p);
+        self.a.emitted_toward(r_in, rec) * (1.0 - t) + self.b.emitted_toward(r_in, rec) * t
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.a.emits_two_sided() || self.b.emits_two_sided()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        self.choose(r_in, rec).scattering_pdf(r_in, rec, scattered)
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        self.choose(r_in, rec).eval(r_in, rec, scattered)
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        self.choose_at(u, v, p).alpha_test(u, v, p)
+    }
+}
+
+//A dielectric clearcoat over any base material: varnished wood, lacquered or painted surfaces
+//a hit sees the coat with the coat's Fresnel reflectance and the base otherwise,
+//light reaching the base loses what the coat reflects on the way out and is tinted by the layer
+pub struct coated {
+    pub base: Arc<dyn material + Send + Sync>,
+    pub refraction_index: f64,
+    //perceptual roughness of the coat, 0 is a perfect mirror
+    pub roughness: f64,
+    //what the coat lets through straight down to the base and back up, white is clear varnish
+    pub tint: Vec3,
+}
+
+impl coated {
+    pub fn new(base: Arc<dyn material + Send + Sync>, refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            base,
+            refraction_index,
+            roughness,
+            tint: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+    pub fn new_tinted(base: Arc<dyn material + Send + Sync>, refraction_index: f64, roughness: f64, tint: Vec3) -> Self {
+        Self {
+            base,
+            refraction_index,
+            roughness,
+            tint,
+        }
+    }
+
+    fn distribution(&self) -> TrowbridgeReitz {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(self.roughness);
+        TrowbridgeReitz::new(alpha, alpha)
+    }
+    //true when this hit is reflected by the coat, with probability F(cos_o)
+    fn sees_coat(&self, r_in: &Ray, rec: &hit_record) -> bool {
+        let d = r_in.b_direction.unit_vector();
+        let cos_o = (d * (-1.0)) * rec.normal;
+        let u = util::hash_to_unit(&[rec.p.x, rec.p.y, rec.p.z, d.x, d.y, d.z]);
+        u < fresnel_dielectric(cos_o, self.refraction_index)
+    }
+    //cosine of a direction once refracted into the coat
+    fn cos_inside(&self, cos: f64) -> f64 {
+        let sin2 = (1.0 - cos * cos) / (self.refraction_index * self.refraction_index);
+        util::fmax(1.0 - sin2, 1e-4).sqrt()
+    }
+    //what the coat does to light that went through to the base: the Fresnel loss on the way out along
+    //the scattered direction and the tint over the slanted path down and back up
+    fn through(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        let cos_o = ((r_in.b_direction.unit_vector() * (-1.0)) * rec.normal).abs();
+        let cos_i = (scattered.b_direction.unit_vector() * rec.normal).abs();
+        let transmitted = 1.0 - fresnel_dielectric(cos_i, self.refraction_index);
+        let path = 0.5 * (1.0 / self.cos_inside(cos_o) + 1.0 / self.cos_inside(cos_i));
+        Vec3::new(
+            self.tint.x.max(0.0).powf(path),
+            self.tint.y.max(0.0).powf(path),
+            self.tint.z.max(0.0).powf(path),
+        ) * transmitted
+    }
+    //GGX reflection off the coat in a local frame, divided by the probability of picking the coat
+    fn coat_eval(&self, distrib: &TrowbridgeReitz, wo: Vec3, wi: Vec3) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vector();
+        let f = fresnel_dielectric(wo * wm, self.refraction_index);
+        let p = fresnel_dielectric(wo.z, self.refraction_index);
+        f * distrib.d(wm) * distrib.g(wo, wi) / (4.0 * wo.z * p)
+    }
+}
+
+impl material for coated {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        if !self.sees_coat(r_in, rec) {
+            if !self.base.scatter(r_in, rec, attenuation, scattered) {
+                return false;
+            }
+            let through = self.through(r_in, rec, scattered);
+            *attenuation = Vec3::new(attenuation.x * through.x, attenuation.y * through.y, attenuation.z * through.z);
+            return true;
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            //F(cos_o) / F(cos_o)
+            *attenuation = Vec3::new(1.0, 1.0, 1.0);
+            *scattered = Ray::new(rec.p, frame.to_world(Vec3::new(-wo.x, -wo.y, wo.z)), r_in.time);
+            return true;
+        }
+        let wm = distrib.sample_visible(wo);
+        let wi = reflect(wo, wm);
+        if wi.z <= 0.0 {
+            return false;
+        }
+        let f = fresnel_dielectric(wo * wm, self.refraction_index);
+        let p = fresnel_dielectric(wo.z, self.refraction_index);
+        *attenuation = Vec3::new(1.0, 1.0, 1.0) * (f * distrib.g(wo, wi) / (distrib.g1(wo) * p));
+        *scattered = Ray::new(rec.p, frame.to_world(wi), r_in.time);
+        true
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.base.emitted(u, v, p)
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.base.emitted_toward(r_in, rec)
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.base.emits_two_sided()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        if !self.sees_coat(r_in, rec) {
+            return self.base.scattering_pdf(r_in, rec, scattered);
+        }
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vector();
+        distrib.d_visible(wo, wm) / (4.0 * (wo * wm))
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        if !self.sees_coat(r_in, rec) {
+            let base = self.base.eval(r_in, rec, scattered);
+            let through = self.through(r_in, rec, scattered);
+            return Vec3::new(base.x * through.x, base.y * through.y, base.z * through.z);
+        }
+        let distrib = self.distribution();
+        if distrib.effectively_smooth() {
+            return Vec3::zero();
+        }
+        let frame = Frame::new(rec);
+        let wo = frame.to_local(r_in.b_direction.unit_vector() * (-1.0));
+        let wi = frame.to_local(scattered.b_direction.unit_vector());
+        Vec3::new(1.0, 1.0, 1.0) * self.coat_eval(&distrib, wo, wi)
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        self.base.alpha_test(u, v, p)
+    }
 }
 
 pub struct diffuse_light {
     pub tex: Arc<dyn texture + Send + Sync>,
+    //false shines only from the front face (the side the normal points to)
+    pub two_sided: bool,
+    //scales the emission with the angle from the normal like a light fixture
+    pub profile: Option<Arc<IesProfile>>,
 }
 
 impl diffuse_light {
     pub fn new(tex: Arc<dyn texture + Send + Sync>) -> Self {
         Self {
             tex,
+            two_sided: true,
+            profile: None,
         }
     }
     pub fn new_from_color(color: Vec3) -> Self {
-        Self {
-            tex: Arc::new(SolidColor::new(color)),
+        Self::new(Arc::new(SolidColor::new(color)))
+    }
+    //the color of a blackbody at `kelvin` with luminance `scale`
+    pub fn new_blackbody(kelvin: f64, scale: f64) -> Self {
+        Self::new_from_color(spectrum::blackbody_color(kelvin) * scale)
+    }
+    //radiance from the total emitted power in watts over an emitter of `area`, so that resizing the light
+    //does not change how much light it gives; the color only sets the tint
+    //emitter_from_power() takes the area from the shape itself
+    pub fn new_from_power(color: Vec3, watts: f64, area: f64, two_sided: bool) -> Self {
+        let luminance = color::luminance(color);
+        let sides = if two_sided { 2.0 } else { 1.0 };
+        let radiance = if luminance > 0.0 && area > 0.0 {
+            color * (watts / (luminance * std::f64::consts::PI * area * sides))
+        } else {
+            Vec3::zero()
+        };
+        let mut light = Self::new_from_color(radiance);
+        light.two_sided = two_sided;
+        light
+    }
+    //same with the luminous power in lumens, at the 683 lm/W of the photometric standard
+    pub fn new_from_lumens(color: Vec3, lumens: f64, area: f64, two_sided: bool) -> Self {
+        Self::new_from_power(color, lumens / 683.0, area, two_sided)
+    }
+    //a shape giving off `watts` in total: `make` builds the shape around a material, once to measure its
+    //area and once more with the light, e.g. |mat| quad::new(Q, u, v, mat)
+    pub fn emitter_from_power<H: hittable>(color: Vec3, watts: f64, two_sided: bool, make: impl Fn(Arc<dyn material + Send + Sync>) -> H) -> H {
+        let area = make(Arc::new(Self::new_from_color(Vec3::zero()))).area();
+        if area <= 0.0 {
+            panic!("emitter_from_power needs a shape with a surface area");
         }
+        make(Arc::new(Self::new_from_power(color, watts, area, two_sided)))
+    }
+    pub fn emitter_from_lumens<H: hittable>(color: Vec3, lumens: f64, two_sided: bool, make: impl Fn(Arc<dyn material + Send + Sync>) -> H) -> H {
+        Self::emitter_from_power(color, lumens / 683.0, two_sided, make)
+    }
+    //with a profile the radiance along the normal stays as given and the intensity (radiance times the
+    //projected area) falls off as the profile does
+    pub fn new_with_profile(color: Vec3, profile: Arc<IesProfile>) -> Self {
+        let mut light = Self::new_from_color(color);
+        light.two_sided = false;
+        light.profile = Some(profile);
+        light
     }
 }
 
@@ -131,6 +992,37 @@ impl material for diffuse_light {
     fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
         self.tex.value(u, v, p)
     }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        if !self.two_sided && !rec.front_face {
+            return Vec3::zero();
+        }
+        let radiance = self.emitted(rec.u, rec.v, &rec.p);
+        match &self.profile {
+            Some(profile) => {
+                //angles of the direction towards the viewer in the frame of the outward normal,
+                //the horizontal angle starts at dpdu where the surface has one
+                let w = (r_in.b_direction * (-1.0)).unit_vector();
+                let n = if w * rec.normal >= 0.0 { rec.normal } else { rec.normal * (-1.0) };
+                let tangent = rec.dpdu - n * (rec.dpdu * n);
+                let (s, t) = if tangent.near_zero() {
+                    util::orthonormal_basis(n)
+                } else {
+                    let s = tangent.unit_vector();
+                    (s, n.cross(s))
+                };
+                let cos_theta = (w * n).clamp(-1.0, 1.0);
+                let theta = cos_theta.acos().to_degrees();
+                let phi = (w * t).atan2(w * s).to_degrees();
+                //the surface already looks cos(theta) smaller from the side, the radiance makes up for it so
+                //that the candela curve comes out as measured; kept finite right at grazing angles
+                radiance * (profile.relative(theta, phi) / cos_theta.max(0.05))
+            }
+            None => radiance,
+        }
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.two_sided
+    }
 }
 
 pub struct isotropic {
@@ -153,10 +1045,361 @@ impl isotropic {
 impl material for isotropic {
     fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
         *scattered = Ray::new(rec.p, util::random_on_unit_sphere(), r_in.time);
-        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        *attenuation = self.tex.value_at(rec);
+        true
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        1.0 / (4.0 * std::f64::consts::PI)
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        self.tex.value_at(rec) / (4.0 * std::f64::consts::PI)
+    }
+}
+
+//Inside of a fog, smoke or cloud: scatters with the given phase function and keeps `albedo` of the light
+//isotropic is the special case of an isotropic phase function
+pub struct volumetric {
+    pub albedo: Arc<dyn texture + Send + Sync>,
+    pub phase: Arc<dyn phase_function>,
+}
+
+impl volumetric {
+    pub fn new(color: Vec3, phase: Arc<dyn phase_function>) -> Self {
+        Self {
+            albedo: Arc::new(SolidColor::new(color)),
+            phase,
+        }
+    }
+    pub fn new_from_texture(albedo: Arc<dyn texture + Send + Sync>, phase: Arc<dyn phase_function>) -> Self {
+        Self {
+            albedo,
+            phase,
+        }
+    }
+    fn cos_theta(r_in: &Ray, scattered: &Ray) -> f64 {
+        r_in.b_direction.unit_vector() * scattered.b_direction.unit_vector()
+    }
+}
+
+impl material for volumetric {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        *scattered = Ray::new(rec.p, self.phase.sample(r_in.b_direction), r_in.time);
+        *attenuation = self.albedo.value_at(rec);
         true
     }
     fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
         Vec3::zero()
     }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        self.phase.p(Self::cos_theta(r_in, scattered))
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        self.albedo.value_at(rec) * self.phase.p(Self::cos_theta(r_in, scattered))
+    }
+}
+
+//Cut-out wrapper: texels whose alpha is below the threshold are holes in the surface
+//in stochastic mode a texel with alpha a is hit with probability a, for partial opacity
+pub struct alpha_mask {
+    pub inner: Arc<dyn material + Send + Sync>,
+    pub alpha: Arc<dyn texture + Send + Sync>,
+    pub threshold: f64,
+    pub stochastic: bool,
+}
+
+impl alpha_mask {
+    pub fn new(inner: Arc<dyn material + Send + Sync>, alpha: Arc<dyn texture + Send + Sync>, threshold: f64) -> Self {
+        Self {
+            inner,
+            alpha,
+            threshold,
+            stochastic: false,
+        }
+    }
+    pub fn new_stochastic(inner: Arc<dyn material + Send + Sync>, alpha: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            inner,
+            alpha,
+            threshold: 0.0,
+            stochastic: true,
+        }
+    }
+}
+
+impl material for alpha_mask {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        self.inner.scatter(r_in, rec, attenuation, scattered)
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.inner.emitted(u, v, p)
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.inner.emitted_toward(r_in, rec)
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.inner.emits_two_sided()
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        self.inner.scattering_pdf(r_in, rec, scattered)
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        self.inner.eval(r_in, rec, scattered)
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        let a = self.alpha.alpha(u, v, p);
+        if self.stochastic {
+            a >= 1.0 || (a > 0.0 && util::random_f64_0_1() < a)
+        } else {
+            a >= self.threshold
+        }
+    }
+}
+
+//Hit record with a shading normal in place of the geometric one
+//Guards against light leaks: the shading normal is bent back towards the viewer if it faces away
+fn shading_record(r_in: &Ray, rec: &hit_record, shading_normal: Vec3) -> hit_record {
+    let geometric = rec.normal;
+    let wo = r_in.b_direction.unit_vector() * (-1.0);
+    let mut n = shading_normal.unit_vector();
+    if n * geometric <= 0.0 {
+        n = geometric;
+    }
+    let cos_o = n * wo;
+    if cos_o < 0.01 {
+        n = (n + wo * (0.01 - cos_o)).unit_vector();
+    }
+    let mut shading_rec = rec.clone();
+    shading_rec.normal = n;
+    shading_rec
+}
+
+//a direction that is above one of the two surfaces but below the other would leak light, it gets nothing
+fn same_side(d: Vec3, geometric: Vec3, shading: Vec3) -> bool {
+    (d * geometric > 0.0) == (d * shading > 0.0)
+}
+
+fn scatter_with_shading_normal(inner: &Arc<dyn material + Send + Sync>, r_in: &Ray, rec: &hit_record, shading_normal: Vec3, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+    let shading_rec = shading_record(r_in, rec, shading_normal);
+    inner.scatter(r_in, &shading_rec, attenuation, scattered)
+        && same_side(scattered.b_direction, rec.normal, shading_rec.normal)
+}
+
+fn eval_with_shading_normal(inner: &Arc<dyn material + Send + Sync>, r_in: &Ray, rec: &hit_record, shading_normal: Vec3, scattered: &Ray) -> Vec3 {
+    let shading_rec = shading_record(r_in, rec, shading_normal);
+    if !same_side(scattered.b_direction, rec.normal, shading_rec.normal) {
+        return Vec3::zero();
+    }
+    inner.eval(r_in, &shading_rec, scattered)
+}
+
+fn pdf_with_shading_normal(inner: &Arc<dyn material + Send + Sync>, r_in: &Ray, rec: &hit_record, shading_normal: Vec3, scattered: &Ray) -> f64 {
+    let shading_rec = shading_record(r_in, rec, shading_normal);
+    inner.scattering_pdf(r_in, &shading_rec, scattered)
+}
+
+//Tangent space normal map, rgb in [0, 1] maps to xyz in [-1, 1] with z along the surface normal
+//the map is read without the gamma adjustment of Image::value
+pub struct normal_mapped {
+    pub inner: Arc<dyn material + Send + Sync>,
+    pub map: Arc<Image>,
+    //0 keeps the geometric normal, 1 uses the map as is
+    pub strength: f64,
+    //DirectX style maps have green pointing down
+    pub flip_green: bool,
+}
+
+impl normal_mapped {
+    pub fn new(inner: Arc<dyn material + Send + Sync>, map: Arc<Image>) -> Self {
+        Self {
+            inner,
+            map,
+            strength: 1.0,
+            flip_green: false,
+        }
+    }
+    //None if the map failed to load
+    fn shading_normal(&self, rec: &hit_record) -> Option<Vec3> {
+        if self.map.data.levels.is_empty() {
+            return None;
+        }
+        let c = self.map.get_color_filtered(rec.u, rec.v, &rec.uv_diff);
+        let mut m = Vec3::new(c.x * 2.0 - 1.0, c.y * 2.0 - 1.0, c.z * 2.0 - 1.0);
+        if self.flip_green {
+            m.y = -m.y;
+        }
+        let m = Vec3::new(m.x * self.strength, m.y * self.strength, 1.0 - (1.0 - m.z) * self.strength);
+        let (tangent, bitangent) = rec.tangent_frame();
+        Some(tangent * m.x + bitangent * m.y + rec.normal * m.z)
+    }
+}
+
+impl material for normal_mapped {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        match self.shading_normal(rec) {
+            Some(n) => scatter_with_shading_normal(&self.inner, r_in, rec, n, attenuation, scattered),
+            None => self.inner.scatter(r_in, rec, attenuation, scattered),
+        }
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        match self.shading_normal(rec) {
+            Some(n) => pdf_with_shading_normal(&self.inner, r_in, rec, n, scattered),
+            None => self.inner.scattering_pdf(r_in, rec, scattered),
+        }
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        match self.shading_normal(rec) {
+            Some(n) => eval_with_shading_normal(&self.inner, r_in, rec, n, scattered),
+            None => self.inner.eval(r_in, rec, scattered),
+        }
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.inner.emitted(u, v, p)
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.inner.emitted_toward(r_in, rec)
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.inner.emits_two_sided()
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        self.inner.alpha_test(u, v, p)
+    }
+}
+
+//Bump map: the texture is a height field over uv (the average of its rgb), displaced `scale` units along the normal
+//the normal comes from finite differences of the height over one pixel footprint, like PBRT
+pub struct bump_mapped {
+    pub inner: Arc<dyn material + Send + Sync>,
+    pub height: Arc<dyn texture + Send + Sync>,
+    pub scale: f64,
+}
+
+impl bump_mapped {
+    pub fn new(inner: Arc<dyn material + Send + Sync>, height: Arc<dyn texture + Send + Sync>, scale: f64) -> Self {
+        Self {
+            inner,
+            height,
+            scale,
+        }
+    }
+    fn height_at(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let h = self.height.value(u, v, p);
+        (h.x + h.y + h.z) / 3.0 * self.scale
+    }
+    //None if the surface has no uv parameterization
+    fn shading_normal(&self, rec: &hit_record) -> Option<Vec3> {
+        if rec.dpdu.near_zero() || rec.dpdv.near_zero() {
+            return None;
+        }
+        let mut du = 0.5 * (rec.uv_diff.dudx.abs() + rec.uv_diff.dudy.abs());
+        if du == 0.0 {
+            du = 0.0005;
+        }
+        let mut dv = 0.5 * (rec.uv_diff.dvdx.abs() + rec.uv_diff.dvdy.abs());
+        if dv == 0.0 {
+            dv = 0.0005;
+        }
+        let h = self.height_at(rec.u, rec.v, &rec.p);
+        let h_u = self.height_at(rec.u + du, rec.v, &(rec.p + rec.dpdu * du));
+        let h_v = self.height_at(rec.u, rec.v + dv, &(rec.p + rec.dpdv * dv));
+
+        let n = rec.normal;
+        let dpdu = rec.dpdu + n * ((h_u - h) / du);
+        let dpdv = rec.dpdv + n * ((h_v - h) / dv);
+        let mut shading_normal = dpdu.cross(dpdv).unit_vector();
+        if shading_normal * n < 0.0 {
+            shading_normal = shading_normal * (-1.0);
+        }
+        Some(shading_normal)
+    }
+}
+
+impl material for bump_mapped {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        match self.shading_normal(rec) {
+            Some(n) => scatter_with_shading_normal(&self.inner, r_in, rec, n, attenuation, scattered),
+            None => self.inner.scatter(r_in, rec, attenuation, scattered),
+        }
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        match self.shading_normal(rec) {
+            Some(n) => pdf_with_shading_normal(&self.inner, r_in, rec, n, scattered),
+            None => self.inner.scattering_pdf(r_in, rec, scattered),
+        }
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        match self.shading_normal(rec) {
+            Some(n) => eval_with_shading_normal(&self.inner, r_in, rec, n, scattered),
+            None => self.inner.eval(r_in, rec, scattered),
+        }
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.inner.emitted(u, v, p)
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.inner.emitted_toward(r_in, rec)
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.inner.emits_two_sided()
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        self.inner.alpha_test(u, v, p)
+    }
+}
+
+//Bump map from solid noise: the height is `scale` * noise(frequency * p) and the normal tilts against the
+//noise's gradient, which the noise knows exactly; needs no uv, so it works on any surface
+pub struct noise_bumped {
+    pub inner: Arc<dyn material + Send + Sync>,
+    pub noise: Arc<dyn noise_source>,
+    pub frequency: f64,
+    pub scale: f64,
+}
+
+impl noise_bumped {
+    pub fn new(inner: Arc<dyn material + Send + Sync>, noise: Arc<dyn noise_source>, frequency: f64, scale: f64) -> Self {
+        Self {
+            inner,
+            noise,
+            frequency,
+            scale,
+        }
+    }
+    fn shading_normal(&self, rec: &hit_record) -> Vec3 {
+        let n = rec.normal;
+        let gradient = self.noise.gradient(&(rec.p * self.frequency)) * (self.scale * self.frequency);
+        let tangential = gradient - n * (gradient * n);
+        let shading_normal = n - tangential;
+        if shading_normal.near_zero() {
+            return n;
+        }
+        shading_normal.unit_vector()
+    }
+}
+
+impl material for noise_bumped {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        scatter_with_shading_normal(&self.inner, r_in, rec, self.shading_normal(rec), attenuation, scattered)
+    }
+    fn scattering_pdf(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> f64 {
+        pdf_with_shading_normal(&self.inner, r_in, rec, self.shading_normal(rec), scattered)
+    }
+    fn eval(&self, r_in: &Ray, rec: &hit_record, scattered: &Ray) -> Vec3 {
+        eval_with_shading_normal(&self.inner, r_in, rec, self.shading_normal(rec), scattered)
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.inner.emitted(u, v, p)
+    }
+    fn emitted_toward(&self, r_in: &Ray, rec: &hit_record) -> Vec3 {
+        self.inner.emitted_toward(r_in, rec)
+    }
+    fn emits_two_sided(&self) -> bool {
+        self.inner.emits_two_sided()
+    }
+    fn alpha_test(&self, u: f64, v: f64, p: &Vec3) -> bool {
+        self.inner.alpha_test(u, v, p)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,511 @@
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::intervals::Interval;
+use crate::hittables::{hit_record, hittable};
+use crate::materials::{material, isotropic, volumetric};
+use crate::phase::{phase_function, IsotropicPhase};
+use crate::microfacet::fresnel_dielectric;
+use crate::textures::{texture, SolidColor};
+use crate::perlins::{perlin, noise_source};
+use crate::aabb::AABB;
+use crate::util;
+use std::sync::Arc;
+
+//Extinction coefficient (collisions per unit distance) at every point of a volume
+//max_density() must bound density() from above, it is the majorant of delta and ratio tracking
+pub trait density_field : Send + Sync {
+    fn density(&self, p: &Vec3) -> f64;
+    fn max_density(&self) -> f64;
+}
+
+//Clouds and smoke from Perlin turbulence, `density` where the turbulence reaches 1
+pub struct TurbulenceDensity {
+    noise: Arc<dyn noise_source>,
+    pub scale: f64,
+    pub depth: i32,
+    pub density: f64,
+    //the 4th dimension of the noise, stepping it between frames makes the smoke churn in place
+    pub time: f64,
+}
+
+impl TurbulenceDensity {
+    pub fn new(scale: f64, depth: i32, density: f64) -> Self {
+        Self::new_with_noise(Arc::new(perlin::new()), scale, depth, density)
+    }
+    pub fn new_with_noise(noise: Arc<dyn noise_source>, scale: f64, depth: i32, density: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            depth,
+            density,
+            time: 0.0,
+        }
+    }
+}
+
+impl density_field for TurbulenceDensity {
+    fn density(&self, p: &Vec3) -> f64 {
+        self.density * util::fmin(self.noise.turb4(&(*p * self.scale), self.time, self.depth), 1.0)
+    }
+    fn max_density(&self) -> f64 {
+        self.density
+    }
+}
+
+//Any procedural density, clamped to [0, max]
+pub struct FunctionDensity {
+    pub f: Box<dyn Fn(&Vec3) -> f64 + Send + Sync>,
+    pub max: f64,
+}
+
+impl FunctionDensity {
+    pub fn new(f: Box<dyn Fn(&Vec3) -> f64 + Send + Sync>, max: f64) -> Self {
+        Self {
+            f,
+            max,
+        }
+    }
+}
+
+impl density_field for FunctionDensity {
+    fn density(&self, p: &Vec3) -> f64 {
+        (self.f)(p).clamp(0.0, self.max)
+    }
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+//Densities stored on a regular grid of nx * ny * nz samples spanning the box from min to max,
+//trilinearly interpolated between samples and 0 outside the box
+pub struct GridDensity {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    //x changes fastest, then y, then z
+    pub values: Vec<f64>,
+    pub min: Vec3,
+    pub max: Vec3,
+    max_value: f64,
+}
+
+impl GridDensity {
+    pub fn new(nx: usize, ny: usize, nz: usize, values: Vec<f64>, min: Vec3, max: Vec3) -> Self {
+        assert_eq!(values.len(), nx * ny * nz, "grid needs nx * ny * nz values");
+        let max_value = values.iter().fold(0.0, |m: f64, v| m.max(*v));
+        Self {
+            nx,
+            ny,
+            nz,
+            values,
+            min,
+            max,
+            max_value,
+        }
+    }
+    //samples f at the center of every cell
+    pub fn new_from_fn(nx: usize, ny: usize, nz: usize, min: Vec3, max: Vec3, f: impl Fn(&Vec3) -> f64) -> Self {
+        let size = max - min;
+        let mut values = Vec::with_capacity(nx * ny * nz);
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    let p = min + Vec3::new(size.x * (i as f64 + 0.5) / nx as f64,
+                                            size.y * (j as f64 + 0.5) / ny as f64,
+                                            size.z * (k as f64 + 0.5) / nz as f64);
+                    values.push(util::fmax(f(&p), 0.0));
+                }
+            }
+        }
+        Self::new(nx, ny, nz, values, min, max)
+    }
+
+    fn value(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[(k * self.ny + j) * self.nx + i]
+    }
+}
+
+impl density_field for GridDensity {
+    fn density(&self, p: &Vec3) -> f64 {
+        let size = self.max - self.min;
+        //continuous cell coordinates, sample centers sit at integer + 0.5
+        let gx = (p.x - self.min.x) / size.x * self.nx as f64 - 0.5;
+        let gy = (p.y - self.min.y) / size.y * self.ny as f64 - 0.5;
+        let gz = (p.z - self.min.z) / size.z * self.nz as f64 - 0.5;
+        if gx < -0.5 || gy < -0.5 || gz < -0.5
+            || gx > self.nx as f64 - 0.5 || gy > self.ny as f64 - 0.5 || gz > self.nz as f64 - 0.5 {
+            return 0.0;
+        }
+        let clamp_cell = |g: f64, n: usize| -> (usize, usize, f64) {
+            let g = g.clamp(0.0, (n - 1) as f64);
+            let i0 = g.floor() as usize;
+            let i1 = (i0 + 1).min(n - 1);
+            (i0, i1, g - i0 as f64)
+        };
+        let (i0, i1, tx) = clamp_cell(gx, self.nx);
+        let (j0, j1, ty) = clamp_cell(gy, self.ny);
+        let (k0, k1, tz) = clamp_cell(gz, self.nz);
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let c00 = lerp(self.value(i0, j0, k0), self.value(i1, j0, k0), tx);
+        let c10 = lerp(self.value(i0, j1, k0), self.value(i1, j1, k0), tx);
+        let c01 = lerp(self.value(i0, j0, k1), self.value(i1, j0, k1), tx);
+        let c11 = lerp(self.value(i0, j1, k1), self.value(i1, j1, k1), tx);
+        lerp(lerp(c00, c10, ty), lerp(c01, c11, ty), tz)
+    }
+    fn max_density(&self) -> f64 {
+        self.max_value
+    }
+}
+
+//Participating medium whose density varies through space, filling any closed boundary (convex or not)
+//collisions are found by delta (Woodcock) tracking against the majorant, shadow rays use ratio tracking
+pub struct heterogeneous_medium {
+    pub boundary: Arc<dyn hittable + Send + Sync>,
+    pub density: Arc<dyn density_field>,
+    pub phase_function: Arc<dyn material + Send + Sync>,
+}
+
+impl heterogeneous_medium {
+    pub fn new(boundary: Arc<dyn hittable + Send + Sync>, density: Arc<dyn density_field>, color: Vec3) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function: Arc::new(isotropic::new(color)),
+        }
+    }
+    pub fn new_from_texture(boundary: Arc<dyn hittable + Send + Sync>, density: Arc<dyn density_field>, tex: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function: Arc::new(isotropic::new_from_texture(tex)),
+        }
+    }
+    pub fn new_with_phase(boundary: Arc<dyn hittable + Send + Sync>, density: Arc<dyn density_field>, color: Vec3, phase: Arc<dyn phase_function>) -> Self {
+        Self {
+            boundary,
+            density,
+            phase_function: Arc::new(volumetric::new(color, phase)),
+        }
+    }
+
+    //the parts of ray_t that lie inside the boundary, found by walking every boundary crossing along the ray:
+    //entering through a front face opens a segment and leaving through a back face closes it
+    fn inside_segments(&self, r: &Ray, ray_t: &Interval) -> Vec<(f64, f64)> {
+        let mut segments = Vec::new();
+        let mut t = f64::NEG_INFINITY;
+        let mut entered: Option<f64> = None;
+        //a boundary may start behind the ray origin, so the walk starts at -infinity like constant_medium
+        for _ in 0..256 {
+            let mut rec = hit_record::new();
+            if !self.boundary.hit(r, &Interval::new(t, f64::INFINITY), &mut rec) {
+                break;
+            }
+            if rec.front_face {
+                if entered.is_none() {
+                    entered = Some(rec.t);
+                }
+            } else {
+                //a back face with no front face before it means the walk started inside
+                let start = entered.unwrap_or(f64::NEG_INFINITY);
+                let (tmin, tmax) = (util::fmax(start, ray_t.tmin), util::fmin(rec.t, ray_t.tmax));
+                if tmin < tmax {
+                    segments.push((tmin, tmax));
+                }
+                entered = None;
+            }
+            if rec.t >= ray_t.tmax {
+                break;
+            }
+            t = rec.t + 0.0001;
+        }
+        segments
+    }
+}
+
+impl hittable for heterogeneous_medium {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        let majorant = self.density.max_density();
+        if majorant <= 0.0 {
+            return false;
+        }
+        let ray_length = r.b_direction.length();
+        for (tmin, tmax) in self.inside_segments(r, ray_t) {
+            //delta tracking: tentative collisions at the majorant rate, real with probability density / majorant
+            let mut t = tmin;
+            loop {
+                t -= (1.0 - util::random_f64_0_1()).ln() / (majorant * ray_length);
+                if t >= tmax {
+                    break;
+                }
+                let p = r.at(t);
+                if util::random_f64_0_1() * majorant < self.density.density(&p) {
+                    rec.t = t;
+                    rec.p = p;
+                    rec.normal = Vec3::new(1.0, 0.0, 0.0); // arbitrary
+                    rec.front_face = true; // also arbitrary
+                    rec.mat = Arc::clone(&self.phase_function);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn bbox(&self) -> &AABB {
+        self.boundary.bbox()
+    }
+    //ratio tracking: every tentative collision multiplies in the chance of it being a null collision
+    fn transmittance(&self, r: &Ray, ray_t: &Interval) -> f64 {
+        let majorant = self.density.max_density();
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+        let ray_length = r.b_direction.length();
+        let mut transmittance = 1.0;
+        for (tmin, tmax) in self.inside_segments(r, ray_t) {
+            let mut t = tmin;
+            loop {
+                t -= (1.0 - util::random_f64_0_1()).ln() / (majorant * ray_length);
+                if t >= tmax {
+                    break;
+                }
+                transmittance *= 1.0 - self.density.density(&r.at(t)) / majorant;
+                //russian roulette once little is left
+                if transmittance < 0.1 {
+                    if util::random_f64_0_1() < 0.5 {
+                        return 0.0;
+                    }
+                    transmittance *= 2.0;
+                }
+            }
+        }
+        transmittance
+    }
+}
+
+//Scene-wide fog set on the Camera: every ray segment is attenuated and may scatter inside it,
+//with no boundary, so it works wherever the camera is
+//density falls off exponentially above base_height (height_falloff 0 is a homogeneous fog)
+pub struct Fog {
+    //extinction coefficient at base_height
+    pub density: f64,
+    pub base_height: f64,
+    //how fast the fog thins out per unit of height
+    pub height_falloff: f64,
+    //what happens at a collision, isotropic unless set_phase() is called
+    pub phase_function: Arc<volumetric>,
+}
+
+impl Fog {
+    pub fn new(density: f64, albedo: Vec3) -> Self {
+        Self {
+            density,
+            base_height: 0.0,
+            height_falloff: 0.0,
+            phase_function: Arc::new(volumetric::new(albedo, Arc::new(IsotropicPhase))),
+        }
+    }
+    //ground fog and haze that is thickest in the valleys
+    pub fn new_height(density: f64, base_height: f64, height_falloff: f64, albedo: Vec3) -> Self {
+        Self {
+            density,
+            base_height,
+            height_falloff,
+            phase_function: Arc::new(volumetric::new(albedo, Arc::new(IsotropicPhase))),
+        }
+    }
+    //fog that scatters forward or backward instead of evenly, e.g. with HenyeyGreenstein
+    pub fn set_phase(&mut self, phase: Arc<dyn phase_function>) {
+        self.phase_function = Arc::new(volumetric::new_from_texture(self.phase_function.albedo.clone(), phase));
+    }
+
+    pub fn density_at(&self, p: &Vec3) -> f64 {
+        self.density * (-self.height_falloff * (p.y - self.base_height)).exp()
+    }
+    //optical depth over `distance` from origin along the unit vector d, in closed form
+    fn optical_depth(&self, origin: &Vec3, d: Vec3, distance: f64) -> f64 {
+        let c = self.density_at(origin);
+        if c <= 0.0 || distance <= 0.0 {
+            return 0.0;
+        }
+        let k = self.height_falloff * d.y;
+        if k.abs() < 1e-9 {
+            c * distance
+        } else {
+            c * (1.0 - (-k * distance).exp()) / k
+        }
+    }
+    //fraction of light that gets through the fog between r.at(0) and r.at(t_max)
+    pub fn transmittance(&self, r: &Ray, t_max: f64) -> f64 {
+        let length = r.b_direction.length();
+        (-self.optical_depth(&r.a_origin, r.b_direction / length, t_max * length)).exp()
+    }
+    //ray parameter of the next scattering event before t_max, None if the ray gets through;
+    //the optical depth is inverted exactly, so no majorant is needed
+    pub fn sample_collision(&self, r: &Ray, t_max: f64) -> Option<f64> {
+        let c = self.density_at(&r.a_origin);
+        if c <= 0.0 {
+            return None;
+        }
+        let length = r.b_direction.length();
+        let k = self.height_falloff * r.b_direction.y / length;
+        let tau = -(1.0 - util::random_f64_0_1()).ln();
+        let distance = if k.abs() < 1e-9 {
+            tau / c
+        } else {
+            let x = 1.0 - tau * k / c;
+            //rising into thinner fog, the total optical depth to infinity is less than tau
+            if x <= 0.0 {
+                return None;
+            }
+            -x.ln() / k
+        };
+        let t = distance / length;
+        if t < t_max { Some(t) } else { None }
+    }
+}
+
+//Marble, skin, wax: light enters through a smooth dielectric boundary, scatters around inside and leaves
+//somewhere else, traced as a volumetric random walk against the object's own boundary
+//the boundary is used in world space, so move or rotate the boundary and not the volume
+pub struct subsurface_volume {
+    pub boundary: Arc<dyn hittable + Send + Sync>,
+    material: Arc<subsurface>,
+}
+
+impl subsurface_volume {
+    //albedo is the color the object ends up with, mean_free_path how far light travels inside
+    //between scattering events for each color channel (longer for red in skin)
+    pub fn new(boundary: Arc<dyn hittable + Send + Sync>, albedo: Vec3, mean_free_path: Vec3, refraction_index: f64) -> Self {
+        Self::new_from_texture(boundary, Arc::new(SolidColor::new(albedo)), mean_free_path, refraction_index)
+    }
+    pub fn new_from_texture(boundary: Arc<dyn hittable + Send + Sync>, albedo: Arc<dyn texture + Send + Sync>, mean_free_path: Vec3, refraction_index: f64) -> Self {
+        Self::new_with_phase(boundary, albedo, mean_free_path, refraction_index, Arc::new(IsotropicPhase))
+    }
+    pub fn new_with_phase(boundary: Arc<dyn hittable + Send + Sync>, albedo: Arc<dyn texture + Send + Sync>, mean_free_path: Vec3, refraction_index: f64, phase: Arc<dyn phase_function>) -> Self {
+        Self {
+            boundary: boundary.clone(),
+            material: Arc::new(subsurface {
+                albedo,
+                mean_free_path,
+                refraction_index,
+                phase,
+                max_steps: 256,
+                boundary,
+            }),
+        }
+    }
+}
+
+impl hittable for subsurface_volume {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        if !self.boundary.hit(r, ray_t, rec) {
+            return false;
+        }
+        rec.mat = self.material.clone();
+        true
+    }
+    fn bbox(&self) -> &AABB {
+        self.boundary.bbox()
+    }
+}
+
+//The material handed out by subsurface_volume, it keeps the boundary to find where the walk leaves
+pub struct subsurface {
+    pub albedo: Arc<dyn texture + Send + Sync>,
+    pub mean_free_path: Vec3,
+    pub refraction_index: f64,
+    pub phase: Arc<dyn phase_function>,
+    //walks still inside after this many scattering events are absorbed
+    pub max_steps: u32,
+    boundary: Arc<dyn hittable + Send + Sync>,
+}
+
+impl subsurface {
+    //single scattering albedo that gives roughly `albedo` after many bounces
+    //(Chiang et al. 2016, "Practical and Controllable Subsurface Scattering for Production Path Tracing")
+    fn single_scattering_albedo(albedo: f64) -> f64 {
+        let a = albedo.clamp(0.0, 0.999);
+        let t = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+        1.0 - t * t
+    }
+}
+
+//per channel product of two colors
+fn mul(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(a.x * b.x, a.y * b.y, a.z * b.z)
+}
+fn exp_neg(v: Vec3, distance: f64) -> Vec3 {
+    Vec3::new((-v.x * distance).exp(), (-v.y * distance).exp(), (-v.z * distance).exp())
+}
+
+impl material for subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &hit_record, attenuation: &mut Vec3, scattered: &mut Ray) -> bool {
+        let unit_direction = r_in.b_direction.unit_vector();
+        //a ray that starts inside (e.g. the camera is in the object) just leaves
+        if !rec.front_face {
+            *attenuation = Vec3::new(1.0, 1.0, 1.0);
+            *scattered = Ray::new(rec.p, unit_direction, r_in.time);
+            return true;
+        }
+        let cos_theta = util::fmin((unit_direction * (-1.0)) * rec.normal, 1.0);
+        if util::random_f64_0_1() < fresnel_dielectric(cos_theta, self.refraction_index) {
+            *attenuation = Vec3::new(1.0, 1.0, 1.0);
+            *scattered = Ray::new(rec.p, util::reflect(unit_direction, rec.normal), r_in.time);
+            return true;
+        }
+
+        let albedo = self.albedo.value_at(rec);
+        let sigma_t = Vec3::new(1.0 / util::fmax(self.mean_free_path.x, 1e-6),
+                                1.0 / util::fmax(self.mean_free_path.y, 1e-6),
+                                1.0 / util::fmax(self.mean_free_path.z, 1e-6));
+        let sigma_s = mul(sigma_t, Vec3::new(Self::single_scattering_albedo(albedo.x),
+                                             Self::single_scattering_albedo(albedo.y),
+                                             Self::single_scattering_albedo(albedo.z)));
+        //free flights follow one randomly picked channel, the others are weighted by the balance heuristic:
+        //f holds the path contribution and pdf the path density for every channel
+        let hero = util::random_range_int(0, 3) as u8;
+        let sigma_hero = sigma_t.lp(hero);
+        let mut f = Vec3::new(1.0, 1.0, 1.0);
+        let mut pdf = Vec3::new(1.0, 1.0, 1.0);
+
+        let mut p = rec.p;
+        let mut d = util::refract(unit_direction, rec.normal, 1.0 / self.refraction_index).unit_vector();
+        for _ in 0..self.max_steps {
+            let distance = -(1.0 - util::random_f64_0_1()).ln() / sigma_hero;
+            let walk = Ray::new(p, d, r_in.time);
+            let mut exit = hit_record::new();
+            if self.boundary.hit(&walk, &Interval::new(0.0001, distance), &mut exit) {
+                //reaches the boundary first, the probability of that is the transmittance
+                let transmittance = exp_neg(sigma_t, exit.t);
+                f = mul(f, transmittance);
+                pdf = mul(pdf, transmittance);
+                p = exit.p;
+                //leave through the boundary, or be reflected back inside (total internal reflection included)
+                let cos_i = util::fmin((d * (-1.0)) * exit.normal, 1.0);
+                if util::random_f64_0_1() < fresnel_dielectric(cos_i, 1.0 / self.refraction_index) {
+                    d = util::reflect(d, exit.normal);
+                    continue;
+                }
+                let out = util::refract(d, exit.normal, self.refraction_index);
+                let weight = (pdf.x + pdf.y + pdf.z) / 3.0;
+                if weight <= 0.0 {
+                    return false;
+                }
+                *attenuation = f / weight;
+                *scattered = Ray::new(p, out, r_in.time);
+                return true;
+            }
+            //scattering event inside, the phase function is sampled exactly and cancels
+            let transmittance = exp_neg(sigma_t, distance);
+            f = mul(f, mul(sigma_s, transmittance));
+            pdf = mul(pdf, mul(sigma_t, transmittance));
+            p = walk.at(distance);
+            d = self.phase.sample(d).unit_vector();
+        }
+        false
+    }
+    fn emitted(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+}
\ No newline at end of file
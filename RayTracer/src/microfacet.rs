@@ -0,0 +1,224 @@
+use crate::vec3::Vec3;
+use crate::hittables::hit_record;
+use crate::util;
+
+//Orthonormal shading frame at a hit: x along the tangent (dpdu), y along the bitangent, z the normal
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl Frame {
+    pub fn new(rec: &hit_record) -> Self {
+        let (tangent, bitangent) = rec.tangent_frame();
+        Self {
+            tangent,
+            bitangent,
+            normal: rec.normal,
+        }
+    }
+    pub fn to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v * self.tangent, v * self.bitangent, v * self.normal)
+    }
+    pub fn to_world(&self, v: Vec3) -> Vec3 {
+        self.tangent * v.x + self.bitangent * v.y + self.normal * v.z
+    }
+}
+
+//Trowbridge-Reitz (GGX) distribution of microfacet normals with Smith's height-correlated masking-shadowing
+//all directions are unit vectors in a local Frame, alpha_x along the tangent and alpha_y along the bitangent
+#[derive(Clone, Copy)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self {
+            alpha_x: util::fmax(alpha_x, 0.0),
+            alpha_y: util::fmax(alpha_y, 0.0),
+        }
+    }
+    //perceptual roughness in [0, 1] to alpha, squared like Disney and most texture authoring tools
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        let r = roughness.clamp(0.0, 1.0);
+        r * r
+    }
+    //so smooth that the surface is treated as a perfect mirror, the density would be too peaked to evaluate
+    pub fn effectively_smooth(&self) -> bool {
+        util::fmax(self.alpha_x, self.alpha_y) < 1e-3
+    }
+
+    //density of microfacet normals, D(wm)
+    pub fn d(&self, wm: Vec3) -> f64 {
+        if wm.z <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x / self.alpha_x;
+        let y = wm.y / self.alpha_y;
+        let t = x * x + y * y + wm.z * wm.z;
+        1.0 / (std::f64::consts::PI * self.alpha_x * self.alpha_y * t * t)
+    }
+    fn lambda(&self, w: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return f64::INFINITY;
+        }
+        let alpha2_tan2 = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / (w.z * w.z);
+        ((1.0 + alpha2_tan2).sqrt() - 1.0) / 2.0
+    }
+    //masking of one direction
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+    //masking and shadowing together
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+    //density of the normals visible from w, D_w(wm) = G1(w) max(0, w.wm) D(wm) / cos(w)
+    pub fn d_visible(&self, w: Vec3, wm: Vec3) -> f64 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) * util::fmax(0.0, w * wm) * self.d(wm) / w.z.abs()
+    }
+    //a microfacet normal distributed by d_visible(w, .) (Heitz 2018, "Sampling the GGX Distribution of Visible Normals")
+    pub fn sample_visible(&self, w: Vec3) -> Vec3 {
+        //stretch to the hemisphere configuration
+        let mut wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).unit_vector();
+        if wh.z < 0.0 {
+            wh = wh * (-1.0);
+        }
+        let lensq = wh.x * wh.x + wh.y * wh.y;
+        let t1 = if lensq > 0.0 { Vec3::new(-wh.y, wh.x, 0.0) / lensq.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = wh.cross(t1);
+
+        //uniform point on the disk, warped onto the part of the hemisphere that is seen from w
+        let r = util::random_f64_0_1().sqrt();
+        let phi = 2.0 * std::f64::consts::PI * util::random_f64_0_1();
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + wh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * p2;
+        let nh = t1 * p1 + t2 * p2 + wh * util::fmax(0.0, 1.0 - p1 * p1 - p2 * p2).sqrt();
+
+        //unstretch
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, util::fmax(1e-6, nh.z)).unit_vector()
+    }
+}
+
+//mirror w about the normal n
+pub fn reflect(w: Vec3, n: Vec3) -> Vec3 {
+    n * (2.0 * (w * n)) - w
+}
+
+//Fresnel reflectance of a conductor with complex index of refraction eta + i k, one color channel
+fn fresnel_conductor_channel(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = util::fmax(0.0, 0.5 * (a2b2 + t0)).sqrt();
+    let t1 = a2b2 + cos2;
+    let t2 = 2.0 * a * cos_i;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rp + rs)
+}
+
+pub fn fresnel_conductor(cos_i: f64, eta: Vec3, k: Vec3) -> Vec3 {
+    Vec3::new(
+        fresnel_conductor_channel(cos_i, eta.x, k.x),
+        fresnel_conductor_channel(cos_i, eta.y, k.y),
+        fresnel_conductor_channel(cos_i, eta.z, k.z),
+    )
+}
+
+//exact Fresnel reflectance of unpolarized light at a dielectric boundary, eta = n_t / n_i
+//a negative cos_i means the light arrives from the other side
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_i < 0.0 { (-cos_i, 1.0 / eta) } else { (cos_i, eta) };
+    let cos_i = util::fmin(cos_i, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        //total internal reflection
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+//Rough dielectric boundary (Walter et al. 2007, "Microfacet Models for Refraction through Rough Surfaces")
+//in a local frame with wo on the incident side (wo.z > 0) and eta = n_t / n_i
+//the microfacet normal that turns wo into wi, None for microfacets seen from behind
+fn dielectric_half_vector(wo: Vec3, wi: Vec3, eta: f64) -> Option<Vec3> {
+    let wm = if wi.z > 0.0 { wo + wi } else { wi * eta + wo };
+    if wm.near_zero() {
+        return None;
+    }
+    let mut wm = wm.unit_vector();
+    if wm.z < 0.0 {
+        wm = wm * (-1.0);
+    }
+    if (wm * wi) * wi.z < 0.0 || wm * wo <= 0.0 {
+        return None;
+    }
+    Some(wm)
+}
+
+//reflects or refracts through a visible microfacet by its Fresnel term, f cos / pdf is G2 / G1 for both
+pub fn sample_dielectric(distrib: &TrowbridgeReitz, wo: Vec3, eta: f64) -> Option<Vec3> {
+    let wm = distrib.sample_visible(wo);
+    if util::random_f64_0_1() < fresnel_dielectric(wo * wm, eta) {
+        let wi = reflect(wo, wm);
+        if wi.z <= 0.0 { None } else { Some(wi) }
+    } else {
+        let wi = util::refract(wo * (-1.0), wm, 1.0 / eta);
+        if wi.z >= 0.0 { None } else { Some(wi) }
+    }
+}
+
+pub fn dielectric_pdf(distrib: &TrowbridgeReitz, wo: Vec3, wi: Vec3, eta: f64) -> f64 {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return 0.0;
+    }
+    let wm = match dielectric_half_vector(wo, wi, eta) {
+        Some(wm) => wm,
+        None => return 0.0,
+    };
+    let f = fresnel_dielectric(wo * wm, eta);
+    if wi.z > 0.0 {
+        f * distrib.d_visible(wo, wm) / (4.0 * (wo * wm))
+    } else {
+        //change of variables from the microfacet normal to the refracted direction
+        let denom = wi * wm + (wo * wm) / eta;
+        (1.0 - f) * distrib.d_visible(wo, wm) * (wi * wm).abs() / (denom * denom)
+    }
+}
+
+//BSDF times |cos_i|; like the smooth dielectric there is no 1/eta^2 radiance scaling, it cancels for closed objects
+pub fn dielectric_eval(distrib: &TrowbridgeReitz, wo: Vec3, wi: Vec3, eta: f64) -> f64 {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return 0.0;
+    }
+    let wm = match dielectric_half_vector(wo, wi, eta) {
+        Some(wm) => wm,
+        None => return 0.0,
+    };
+    let f = fresnel_dielectric(wo * wm, eta);
+    let d = distrib.d(wm);
+    let g = distrib.g(wo, wi);
+    if wi.z > 0.0 {
+        f * d * g / (4.0 * wo.z)
+    } else {
+        let denom = wi * wm + (wo * wm) / eta;
+        (1.0 - f) * d * g * (wi * wm).abs() * (wo * wm).abs() / (wo.z * denom * denom)
+    }
+}
\ No newline at end of file
@@ -1,6 +1,97 @@
 use crate::util;
 use crate::vec3::Vec3;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+//Gradient noise that can be sampled anywhere; the fractal sums on top of it are shared by all kinds
+pub trait noise_source : Send + Sync {
+    //about [-1, 1], 0 at the lattice points
+    fn noise(&self, p: &Vec3) -> f64;
+    fn noise2(&self, x: f64, y: f64) -> f64 {
+        self.noise(&Vec3::new(x, y, 0.0))
+    }
+    //w is usually time, so smoke can churn in place; noise without a 4th dimension slides through 3D instead
+    fn noise4(&self, p: &Vec3, w: f64) -> f64 {
+        self.noise(&(*p + Vec3::new(0.31, 0.57, 0.83) * w))
+    }
+    //gradient of noise() at p, by central differences unless the noise knows it exactly
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        let h = 1e-4;
+        let dx = Vec3::new(h, 0.0, 0.0);
+        let dy = Vec3::new(0.0, h, 0.0);
+        let dz = Vec3::new(0.0, 0.0, h);
+        Vec3::new(
+            self.noise(&(*p + dx)) - self.noise(&(*p - dx)),
+            self.noise(&(*p + dy)) - self.noise(&(*p - dy)),
+            self.noise(&(*p + dz)) - self.noise(&(*p - dz)),
+        ) / (2.0 * h)
+    }
+    fn turb(&self, p: &Vec3, depth: i32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        for i in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+        util::fabs(accum)
+    }
+    //4D turbulence, w is usually time
+    fn turb4(&self, p: &Vec3, w: f64, depth: i32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut temp_w = w;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            accum += weight * self.noise4(&temp_p, temp_w);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+            temp_w *= 2.0;
+        }
+        util::fabs(accum)
+    }
+    //fractal Brownian motion: octaves of noise, each lacunarity times finer and gain times weaker,
+    //divided by the sum of the weights so it keeps the range of a single octave
+    fn fbm(&self, p: &Vec3, octaves: u32, lacunarity: f64, gain: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+        for _ in 0..octaves.max(1) {
+            accum += weight * self.noise(&temp_p);
+            total += weight;
+            weight *= gain;
+            temp_p = temp_p * lacunarity;
+        }
+        accum / total
+    }
+    //Musgrave's ridged multifractal: sharp crests where the noise crosses zero, and finer octaves only
+    //show up on the crests; in [0, 1]
+    fn ridged(&self, p: &Vec3, octaves: u32, lacunarity: f64, gain: f64, offset: f64) -> f64 {
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut temp_p = *p;
+        let mut amplitude = 1.0;
+        let mut previous = 1.0;
+        for _ in 0..octaves.max(1) {
+            let ridge = (offset - util::fabs(self.noise(&temp_p))).max(0.0);
+            let signal = ridge * ridge * previous;
+            accum += amplitude * signal;
+            total += amplitude * offset * offset;
+            previous = (signal * 2.0).clamp(0.0, 1.0);
+            amplitude *= gain;
+            temp_p = temp_p * lacunarity;
+        }
+        if total <= 0.0 {
+            return 0.0;
+        }
+        accum / total
+    }
+}
+
+//The noise of Ray Tracing: The Next Week, random unit gradients and 256 entry permutation tables
+//that repeat every 256 units
 pub struct perlin {
     randvec: Vec<Vec3>,
     perm_x: Vec<i32>,
@@ -10,13 +101,20 @@ pub struct perlin {
 
 impl perlin {
     pub fn new() -> Self {
+        Self::new_from_rng(&mut rand::thread_rng())
+    }
+    //the same seed always gives the same noise, so a scene looks the same on every render
+    pub fn new_seeded(seed: u64) -> Self {
+        Self::new_from_rng(&mut StdRng::seed_from_u64(seed))
+    }
+    fn new_from_rng<R: Rng>(rng: &mut R) -> Self {
         let mut randvec: Vec<Vec3> = Vec::new();
         for i in 0..256 {
-            randvec.push(util::random_vec3().unit_vector());
+            randvec.push(Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).unit_vector());
         }
-        let perm_x = Self::perlin_generate_perm();
-        let perm_y = Self::perlin_generate_perm();
-        let perm_z = Self::perlin_generate_perm();
+        let perm_x = Self::perlin_generate_perm(rng);
+        let perm_y = Self::perlin_generate_perm(rng);
+        let perm_z = Self::perlin_generate_perm(rng);
         Self {
             randvec,
             perm_x,
@@ -24,51 +122,20 @@ impl perlin {
             perm_z,
         }
     }
-    pub fn noise(&self, p: &Vec3) -> f64 {
-        let u = p.x - p.x.floor();
-        let v = p.y - p.y.floor();
-        let w = p.z - p.z.floor();        
-
-        let i = p.x.floor() as i32;
-        let j = p.y.floor() as i32;
-        let k = p.z.floor() as i32;
-        let mut c = [[[Vec3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
-        for di in 0..2 {
-            for dj in 0..2 {
-                for dk in 0..2 {
-                    c[di as usize][dj as usize][dk as usize] = self.randvec[(self.perm_x[((i + di) & 255) as usize] 
-                                                                ^ self.perm_y[((j + dj) & 255) as usize] 
-                                                                ^ self.perm_z[((k + dk) & 255) as usize]) as usize];
-                }
-            }
-        }
-        Self::perlin_interp(c, u, v, w)
-    }
-    pub fn turb(&self, p: &Vec3, depth: i32) -> f64 {
-        let mut accum = 0.0;
-        let mut temp_p = *p;
-        let mut weight = 1.0;
-        for i in 0..depth {
-            accum += weight * self.noise(&temp_p);
-            weight *= 0.5;
-            temp_p = temp_p * 2.0;
-        }
-        util::fabs(accum)
-    }
-    fn permute(p: &mut Vec<i32>, n: i32) {
+    fn permute<R: Rng>(p: &mut Vec<i32>, n: i32, rng: &mut R) {
         for i in (1..n).rev() {
-            let target = util::random_range(0.0, i as f64) as i32;
+            let target = rng.gen_range(0..=i);
             let tmp = p[i as usize];
             p[i as usize] = p[target as usize];
             p[target as usize] = tmp;
         }
     }
-    fn perlin_generate_perm() -> Vec<i32> {
+    fn perlin_generate_perm<R: Rng>(rng: &mut R) -> Vec<i32> {
         let mut p = Vec::new();
         for i in 0..256 {
             p.push(i);
         }
-        Self::permute(&mut p, 256);
+        Self::permute(&mut p, 256, rng);
         p
     }
     fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
@@ -90,4 +157,496 @@ impl perlin {
         }
         accum
     }
+}
+
+impl noise_source for perlin {
+    fn noise(&self, p: &Vec3) -> f64 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();        
+
+        let i = p.x.floor() as i32;
+        let j = p.y.floor() as i32;
+        let k = p.z.floor() as i32;
+        let mut c = [[[Vec3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    c[di as usize][dj as usize][dk as usize] = self.randvec[(self.perm_x[((i + di) & 255) as usize] 
+                                                                ^ self.perm_y[((j + dj) & 255) as usize] 
+                                                                ^ self.perm_z[((k + dk) & 255) as usize]) as usize];
+                }
+            }
+        }
+        perlin::perlin_interp(c, u, v, w)
+    }
+}
+
+//splitmix64 of a seed and lattice coordinates, the lattice noises hash their corners instead of
+//looking them up in a 256 entry table, so they only repeat when the i64 coordinates wrap
+fn lattice_hash(seed: u64, coords: &[i64]) -> u64 {
+    let mut h = seed.wrapping_mul(0x9E3779B97F4A7C15) ^ 0xD1B54A32D192ED03;
+    for &c in coords {
+        h ^= c as u64;
+        h = h.wrapping_add(0x9E3779B97F4A7C15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94D049BB133111EB);
+        h ^= h >> 31;
+    }
+    h
+}
+
+const GRAD2: [[f64; 2]; 8] = [[1.0, 1.0], [-1.0, 1.0], [1.0, -1.0], [-1.0, -1.0], [1.0, 0.0], [-1.0, 0.0], [0.0, 1.0], [0.0, -1.0]];
+//the 12 edge midpoints of a cube, Perlin's choice for improved noise
+const GRAD3: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+//the 32 edge midpoints of a 4D cube: one coordinate 0 and the others +-1
+fn grad4(h: u64) -> [f64; 4] {
+    let index = (h % 32) as usize;
+    let zero = index / 8;
+    let signs = index % 8;
+    let mut g = [0.0; 4];
+    let mut bit = 0;
+    for axis in 0..4 {
+        if axis == zero {
+            continue;
+        }
+        g[axis] = if (signs >> bit) & 1 == 1 { -1.0 } else { 1.0 };
+        bit += 1;
+    }
+    g
+}
+
+//quintic smoothstep of improved noise, its second derivative is 0 at the lattice so bumps have no creases
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn fade_derivative(t: f64) -> f64 {
+    30.0 * t * t * (t - 1.0) * (t - 1.0)
+}
+
+//Perlin's improved noise (2002): quintic fade, 12 fixed gradients picked by a seeded hash,
+//in 2D, 3D and 4D, with exact derivatives for bump mapping
+pub struct improved_perlin {
+    seed: u64,
+}
+
+impl improved_perlin {
+    pub fn new_seeded(seed: u64) -> Self {
+        Self {
+            seed,
+        }
+    }
+    //value and gradient at (x, y)
+    pub fn noise2_deriv(&self, x: f64, y: f64) -> (f64, f64, f64) {
+        let (xi, yi) = (x.floor(), y.floor());
+        let (fx, fy) = (x - xi, y - yi);
+        let (u, v) = (fade(fx), fade(fy));
+        let (du, dv) = (fade_derivative(fx), fade_derivative(fy));
+        let mut value = 0.0;
+        let (mut gx, mut gy) = (0.0, 0.0);
+        for corner in 0..4 {
+            let (cx, cy) = ((corner & 1) as i64, ((corner >> 1) & 1) as i64);
+            let g = GRAD2[(lattice_hash(self.seed, &[xi as i64 + cx, yi as i64 + cy]) % 8) as usize];
+            let dot = g[0] * (fx - cx as f64) + g[1] * (fy - cy as f64);
+            let (wx, dwx) = if cx == 1 { (u, du) } else { (1.0 - u, -du) };
+            let (wy, dwy) = if cy == 1 { (v, dv) } else { (1.0 - v, -dv) };
+            value += wx * wy * dot;
+            gx += dwx * wy * dot + wx * wy * g[0];
+            gy += wx * dwy * dot + wx * wy * g[1];
+        }
+        (value, gx, gy)
+    }
+    //value and gradient at p
+    pub fn noise3_deriv(&self, p: &Vec3) -> (f64, Vec3) {
+        let (xi, yi, zi) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (fx, fy, fz) = (p.x - xi, p.y - yi, p.z - zi);
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let (du, dv, dw) = (fade_derivative(fx), fade_derivative(fy), fade_derivative(fz));
+        let mut value = 0.0;
+        let mut gradient = Vec3::zero();
+        for corner in 0..8 {
+            let (cx, cy, cz) = ((corner & 1) as i64, ((corner >> 1) & 1) as i64, ((corner >> 2) & 1) as i64);
+            let g = GRAD3[(lattice_hash(self.seed, &[xi as i64 + cx, yi as i64 + cy, zi as i64 + cz]) % 12) as usize];
+            let dot = g[0] * (fx - cx as f64) + g[1] * (fy - cy as f64) + g[2] * (fz - cz as f64);
+            let (wx, dwx) = if cx == 1 { (u, du) } else { (1.0 - u, -du) };
+            let (wy, dwy) = if cy == 1 { (v, dv) } else { (1.0 - v, -dv) };
+            let (wz, dwz) = if cz == 1 { (w, dw) } else { (1.0 - w, -dw) };
+            let weight = wx * wy * wz;
+            value += weight * dot;
+            gradient += Vec3::new(
+                dwx * wy * wz * dot + weight * g[0],
+                wx * dwy * wz * dot + weight * g[1],
+                wx * wy * dwz * dot + weight * g[2],
+            );
+        }
+        (value, gradient)
+    }
+    //value and gradient with respect to (x, y, z, w)
+    pub fn noise4_deriv(&self, p: &Vec3, w: f64) -> (f64, [f64; 4]) {
+        let x = [p.x, p.y, p.z, w];
+        let xi = x.map(f64::floor);
+        let f = [x[0] - xi[0], x[1] - xi[1], x[2] - xi[2], x[3] - xi[3]];
+        let t = f.map(fade);
+        let dt = f.map(fade_derivative);
+        let mut value = 0.0;
+        let mut gradient = [0.0; 4];
+        for corner in 0..16 {
+            let c = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1, (corner >> 3) & 1];
+            let g = grad4(lattice_hash(self.seed, &[xi[0] as i64 + c[0], xi[1] as i64 + c[1], xi[2] as i64 + c[2], xi[3] as i64 + c[3]]));
+            let mut dot = 0.0;
+            let mut weights = [0.0; 4];
+            let mut dweights = [0.0; 4];
+            for axis in 0..4 {
+                dot += g[axis] * (f[axis] - c[axis] as f64);
+                (weights[axis], dweights[axis]) = if c[axis] == 1 { (t[axis], dt[axis]) } else { (1.0 - t[axis], -dt[axis]) };
+            }
+            let weight = weights[0] * weights[1] * weights[2] * weights[3];
+            value += weight * dot;
+            for axis in 0..4 {
+                let others: f64 = (0..4).filter(|b| *b != axis).map(|b| weights[b]).product();
+                gradient[axis] += dweights[axis] * others * dot + weight * g[axis];
+            }
+        }
+        (value, gradient)
+    }
+}
+
+impl noise_source for improved_perlin {
+    fn noise(&self, p: &Vec3) -> f64 {
+        self.noise3_deriv(p).0
+    }
+    fn noise2(&self, x: f64, y: f64) -> f64 {
+        self.noise2_deriv(x, y).0
+    }
+    fn noise4(&self, p: &Vec3, w: f64) -> f64 {
+        self.noise4_deriv(p, w).0
+    }
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        self.noise3_deriv(p).1
+    }
+}
+
+const F2: f64 = 0.36602540378443865; //(sqrt(3) - 1) / 2
+const G2: f64 = 0.21132486540518713; //(3 - sqrt(3)) / 6
+//4D grid to world: a point q of the grid is at q + UNSKEW4 * (q.x + q.y + q.z + q.w), SKEW4 undoes it
+const SKEW4: f64 = -0.1381966011250105; //(1 / sqrt(5) - 1) / 4
+const UNSKEW4: f64 = 0.30901699437494745; //(sqrt(5) - 1) / 4
+//squared kernel radius: a point reaches no further than the lattice points the noise functions visit
+//(three corners in 2D, the nearest point of each grid and one neighbour in 3D, one point per copy in 4D),
+//OpenSimplex2's 0.6 leaves small jumps where it misses one
+const R2: f64 = 0.5;
+//bring the peaks to about 1
+const SCALE2: f64 = 70.0;
+const SCALE3: f64 = 76.0;
+const SCALE4: f64 = 62.0;
+
+//value and gradient of the kernel (r^2 - |d|^2)^4 (g . d) of one lattice point at offset d
+fn kernel<const N: usize>(r2: f64, g: [f64; N], d: [f64; N]) -> (f64, [f64; N]) {
+    let t = r2 - d.iter().map(|x| x * x).sum::<f64>();
+    if t <= 0.0 {
+        return (0.0, [0.0; N]);
+    }
+    let dot: f64 = (0..N).map(|i| g[i] * d[i]).sum();
+    let t2 = t * t;
+    let t4 = t2 * t2;
+    let mut gradient = [0.0; N];
+    for i in 0..N {
+        gradient[i] = t4 * g[i] - 8.0 * t2 * t * dot * d[i];
+    }
+    (t4 * dot, gradient)
+}
+
+//OpenSimplex2 (K. Jordan, 2019-2022): gradient noise that avoids the grids of Perlin's 3D and 4D simplex
+//noise patent. 3D sums over the two interleaved cubic grids of a body centered cubic lattice, reflected so
+//that no grid axis lines up with x, y or z; 4D over five copies of the hypercube grid stretched along its
+//diagonal and shifted along it by a fifth each. 2D is the triangle grid of simplex noise, which the patent
+//never covered. Seeded by hash like improved_perlin, with exact derivatives in 2D, 3D and 4D
+pub struct open_simplex {
+    seed: u64,
+}
+
+impl open_simplex {
+    pub fn new_seeded(seed: u64) -> Self {
+        Self {
+            seed,
+        }
+    }
+    //value and gradient at (x, y)
+    pub fn noise2_deriv(&self, x: f64, y: f64) -> (f64, f64, f64) {
+        let s = (x + y) * F2;
+        let (i, j) = ((x + s).floor(), (y + s).floor());
+        let t = (i + j) * G2;
+        let (x0, y0) = (x - (i - t), y - (j - t));
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (i1, j1, x0 - i1 as f64 + G2, y0 - j1 as f64 + G2),
+            (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+        ];
+        let mut value = 0.0;
+        let (mut gx, mut gy) = (0.0, 0.0);
+        for (ci, cj, dx, dy) in corners {
+            let g = GRAD2[(lattice_hash(self.seed, &[i as i64 + ci, j as i64 + cj]) % 8) as usize];
+            let (v, d) = kernel(R2, g, [dx, dy]);
+            value += v;
+            gx += d[0];
+            gy += d[1];
+        }
+        (SCALE2 * value, SCALE2 * gx, SCALE2 * gy)
+    }
+    //value and gradient at p
+    pub fn noise3_deriv(&self, p: &Vec3) -> (f64, Vec3) {
+        //q = 2/3 (x + y + z) - p reflects p, and is its own inverse
+        let r = (p.x + p.y + p.z) * (2.0 / 3.0);
+        let q = [r - p.x, r - p.y, r - p.z];
+        let mut value = 0.0;
+        let mut gradient = [0.0; 3];
+        //grid 0 has its points at the integers, grid 1 half way between them
+        for grid in 0..2 {
+            let shift = 0.5 * grid as f64;
+            let nearest = q.map(|c| (c - shift).round());
+            let d = [q[0] - shift - nearest[0], q[1] - shift - nearest[1], q[2] - shift - nearest[2]];
+            //the only other point of this grid in reach is across the face q is closest to
+            let axis = if d[0].abs() >= d[1].abs() && d[0].abs() >= d[2].abs() {
+                0
+            } else if d[1].abs() >= d[2].abs() {
+                1
+            } else {
+                2
+            };
+            let mut neighbour = nearest;
+            neighbour[axis] += d[axis].signum();
+            for point in [nearest, neighbour] {
+                let offset = [q[0] - shift - point[0], q[1] - shift - point[1], q[2] - shift - point[2]];
+                let g = GRAD3[(lattice_hash(self.seed, &[grid, point[0] as i64, point[1] as i64, point[2] as i64]) % 12) as usize];
+                let (v, dv) = kernel(R2, g, offset);
+                value += v;
+                for a in 0..3 {
+                    gradient[a] += dv[a];
+                }
+            }
+        }
+        //the gradient goes back through the same reflection
+        let s = (gradient[0] + gradient[1] + gradient[2]) * (2.0 / 3.0);
+        (SCALE3 * value, Vec3::new(s - gradient[0], s - gradient[1], s - gradient[2]) * SCALE3)
+    }
+    //value and gradient with respect to (x, y, z, w)
+    pub fn noise4_deriv(&self, p: &Vec3, w: f64) -> (f64, [f64; 4]) {
+        let x = [p.x, p.y, p.z, w];
+        let s = SKEW4 * (x[0] + x[1] + x[2] + x[3]);
+        let xs = x.map(|c| c + s);
+        let mut base = xs.map(|c| c.floor() as i64);
+        let mut si = [xs[0] - base[0] as f64, xs[1] - base[1] as f64, xs[2] - base[2] as f64, xs[3] - base[3] as f64];
+        //grid copy k has its points at the integers + k / 5 on every axis; start at the copy whose cell
+        //holds p in its base simplex and walk down through the others, each one shifted by -1/5 from the last
+        let si_sum = si[0] + si[1] + si[2] + si[3];
+        let start = (si_sum * 1.25) as i64;
+        let mut copy = start;
+        for c in si.iter_mut() {
+            *c -= 0.2 * start as f64;
+        }
+        let mut value = 0.0;
+        let mut gradient = [0.0; 4];
+        for step in 0..5 {
+            //the point of this copy closest to p: the base corner of the cell or one step along an axis,
+            //and the walk carries on from there
+            let sum = si[0] + si[1] + si[2] + si[3];
+            let mut largest = 0;
+            for a in 1..4 {
+                if si[a] > si[largest] {
+                    largest = a;
+                }
+            }
+            if si[largest] >= 1.0 - sum {
+                base[largest] += 1;
+                si[largest] -= 1.0;
+            }
+            let unskew = UNSKEW4 * (si[0] + si[1] + si[2] + si[3]);
+            let d = si.map(|c| c + unskew);
+            let g = grad4(lattice_hash(self.seed, &[copy, base[0], base[1], base[2], base[3]]));
+            let (v, dv) = kernel(R2, g, d);
+            value += v;
+            for a in 0..4 {
+                gradient[a] += dv[a];
+            }
+            if step == 4 {
+                break;
+            }
+            for c in si.iter_mut() {
+                *c += 0.2;
+            }
+            copy -= 1;
+            //copy -1 is copy 4 of the cell below
+            if copy < 0 {
+                copy += 5;
+                for b in base.iter_mut() {
+                    *b -= 1;
+                }
+            }
+        }
+        (SCALE4 * value, gradient.map(|g| g * SCALE4))
+    }
+}
+
+impl noise_source for open_simplex {
+    fn noise(&self, p: &Vec3) -> f64 {
+        self.noise3_deriv(p).0
+    }
+    fn noise2(&self, x: f64, y: f64) -> f64 {
+        self.noise2_deriv(x, y).0
+    }
+    fn noise4(&self, p: &Vec3, w: f64) -> f64 {
+        self.noise4_deriv(p, w).0
+    }
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        self.noise3_deriv(p).1
+    }
+}
+
+//Worley (cellular) noise: one feature point jittered inside every unit cell, the value at p is
+//the distance to the nearest few of them
+pub struct worley {
+    seed: u64,
+    //how far the points move from the cell centers, 0 is a regular grid and 1 fully random
+    pub jitter: f64,
+}
+
+impl worley {
+    pub fn new_seeded(seed: u64) -> Self {
+        Self {
+            seed,
+            jitter: 1.0,
+        }
+    }
+    fn feature_point(&self, i: i32, j: i32, k: i32) -> Vec3 {
+        //the top 53 bits of the hash as a number in [0, 1)
+        let offset = |axis: i64| {
+            let h = lattice_hash(self.seed, &[i as i64, j as i64, k as i64, axis]);
+            0.5 + self.jitter * ((h >> 11) as f64 / (1u64 << 53) as f64 - 0.5)
+        };
+        Vec3::new(i as f64 + offset(0), j as f64 + offset(1), k as f64 + offset(2))
+    }
+    //distances to the nearest and the second nearest feature point
+    pub fn distances(&self, p: &Vec3) -> (f64, f64) {
+        let i = p.x.floor() as i32;
+        let j = p.y.floor() as i32;
+        let k = p.z.floor() as i32;
+        let mut f1 = f64::INFINITY;
+        let mut f2 = f64::INFINITY;
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let d = (self.feature_point(i + di, j + dj, k + dk) - *p).length();
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> Vec<(Vec3, f64)> {
+        (0..64).map(|i| {
+            let t = i as f64;
+            (Vec3::new(t * 0.731 - 11.3, t * 0.419 + 3.7, 5.1 - t * 0.283), t * 0.157 - 2.9)
+        }).collect()
+    }
+
+    //every dimension of a noise as plain values, for comparing two seeds
+    fn values(noise: &dyn noise_source) -> Vec<f64> {
+        sample_points().iter().flat_map(|(p, w)| [noise.noise2(p.x, p.y), noise.noise(p), noise.noise4(p, *w)]).collect()
+    }
+
+    fn assert_seeded(make: fn(u64) -> Box<dyn noise_source>) {
+        let a = values(make(7).as_ref());
+        assert_eq!(a, values(make(7).as_ref()));
+        let b = values(make(8).as_ref());
+        let differing = a.iter().zip(&b).filter(|(x, y)| x != y).count();
+        assert!(differing > a.len() * 3 / 4, "only {} of {} values depend on the seed", differing, a.len());
+    }
+
+    #[test]
+    fn improved_perlin_is_seeded() {
+        assert_seeded(|seed| Box::new(improved_perlin::new_seeded(seed)));
+    }
+
+    #[test]
+    fn open_simplex_is_seeded() {
+        assert_seeded(|seed| Box::new(open_simplex::new_seeded(seed)));
+    }
+
+    #[test]
+    fn worley_is_seeded() {
+        let distances = |seed| sample_points().iter().map(|(p, _)| worley::new_seeded(seed).distances(p)).collect::<Vec<_>>();
+        assert_eq!(distances(3), distances(3));
+        assert_ne!(distances(3), distances(3 + (1 << 40)));
+    }
+
+    //central differences of f along each of the N axes
+    fn numeric_gradient<const N: usize>(f: impl Fn([f64; N]) -> f64, x: [f64; N]) -> [f64; N] {
+        let h = 1e-5;
+        std::array::from_fn(|axis| {
+            let (mut lo, mut hi) = (x, x);
+            lo[axis] -= h;
+            hi[axis] += h;
+            (f(hi) - f(lo)) / (2.0 * h)
+        })
+    }
+
+    fn assert_close<const N: usize>(analytic: [f64; N], numeric: [f64; N]) {
+        for axis in 0..N {
+            assert!((analytic[axis] - numeric[axis]).abs() < 1e-5, "derivative {:?} against finite differences {:?}", analytic, numeric);
+        }
+    }
+
+    #[test]
+    fn improved_perlin_derivatives() {
+        let noise = improved_perlin::new_seeded(11);
+        for (p, w) in sample_points() {
+            let (_, dx, dy) = noise.noise2_deriv(p.x, p.y);
+            assert_close([dx, dy], numeric_gradient(|x| noise.noise2(x[0], x[1]), [p.x, p.y]));
+            let (_, d) = noise.noise3_deriv(&p);
+            assert_close([d.x, d.y, d.z], numeric_gradient(|x| noise.noise(&Vec3::new(x[0], x[1], x[2])), [p.x, p.y, p.z]));
+            let (_, d) = noise.noise4_deriv(&p, w);
+            assert_close(d, numeric_gradient(|x| noise.noise4(&Vec3::new(x[0], x[1], x[2]), x[3]), [p.x, p.y, p.z, w]));
+        }
+    }
+
+    #[test]
+    fn open_simplex_derivatives() {
+        let noise = open_simplex::new_seeded(11);
+        for (p, w) in sample_points() {
+            let (_, dx, dy) = noise.noise2_deriv(p.x, p.y);
+            assert_close([dx, dy], numeric_gradient(|x| noise.noise2(x[0], x[1]), [p.x, p.y]));
+            let (_, d) = noise.noise3_deriv(&p);
+            assert_close([d.x, d.y, d.z], numeric_gradient(|x| noise.noise(&Vec3::new(x[0], x[1], x[2])), [p.x, p.y, p.z]));
+            let (_, d) = noise.noise4_deriv(&p, w);
+            assert_close(d, numeric_gradient(|x| noise.noise4(&Vec3::new(x[0], x[1], x[2]), x[3]), [p.x, p.y, p.z, w]));
+        }
+    }
+
+    #[test]
+    fn open_simplex_stays_in_range() {
+        let noise = open_simplex::new_seeded(5);
+        for (p, w) in sample_points() {
+            for value in [noise.noise2(p.x, p.y), noise.noise(&p), noise.noise4(&p, w)] {
+                assert!(value.abs() <= 1.0, "{} out of range", value);
+            }
+        }
+    }
 }
\ No newline at end of file
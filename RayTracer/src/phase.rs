@@ -0,0 +1,117 @@
+use crate::vec3::Vec3;
+use crate::util;
+use std::f64::consts::PI;
+
+//How a participating medium redirects light at a scattering event
+//cos_theta is the cosine between the direction the light was travelling and the scattered direction,
+//so positive values are forward scattering; p() is a density over the sphere of directions
+pub trait phase_function : Send + Sync {
+    fn p(&self, cos_theta: f64) -> f64;
+    //a cosine distributed according to p()
+    fn sample_cos(&self) -> f64;
+
+    //a scattered direction for light travelling along `direction`, with density p()
+    fn sample(&self, direction: Vec3) -> Vec3 {
+        let w = direction.unit_vector();
+        let cos_theta = self.sample_cos().clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * util::random_f64_0_1();
+        let (u, v) = util::orthonormal_basis(w);
+        u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + w * cos_theta
+    }
+}
+
+//Same in every direction, what isotropic always did
+pub struct IsotropicPhase;
+
+impl phase_function for IsotropicPhase {
+    fn p(&self, cos_theta: f64) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn sample_cos(&self) -> f64 {
+        1.0 - 2.0 * util::random_f64_0_1()
+    }
+}
+
+//Henyey-Greenstein with asymmetry g in (-1, 1): 0 is isotropic, g > 0 scatters forward (haze glowing
+//around lights), g < 0 backward
+pub struct HenyeyGreenstein {
+    pub g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+}
+
+fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+    let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+    (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+}
+
+fn sample_henyey_greenstein(g: f64) -> f64 {
+    let xi = util::random_f64_0_1();
+    if g.abs() < 1e-3 {
+        return 1.0 - 2.0 * xi;
+    }
+    let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+    (1.0 + g * g - s * s) / (2.0 * g)
+}
+
+impl phase_function for HenyeyGreenstein {
+    fn p(&self, cos_theta: f64) -> f64 {
+        henyey_greenstein(cos_theta, self.g)
+    }
+    fn sample_cos(&self) -> f64 {
+        sample_henyey_greenstein(self.g)
+    }
+}
+
+//Blend of two Henyey-Greenstein lobes, usually a strong forward one and a weak backward one
+//as in clouds; weight goes to the first lobe
+pub struct DoubleHenyeyGreenstein {
+    pub g1: f64,
+    pub g2: f64,
+    pub weight: f64,
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(g1: f64, g2: f64, weight: f64) -> Self {
+        Self {
+            g1: g1.clamp(-0.99, 0.99),
+            g2: g2.clamp(-0.99, 0.99),
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl phase_function for DoubleHenyeyGreenstein {
+    fn p(&self, cos_theta: f64) -> f64 {
+        self.weight * henyey_greenstein(cos_theta, self.g1) + (1.0 - self.weight) * henyey_greenstein(cos_theta, self.g2)
+    }
+    fn sample_cos(&self) -> f64 {
+        if util::random_f64_0_1() < self.weight {
+            sample_henyey_greenstein(self.g1)
+        } else {
+            sample_henyey_greenstein(self.g2)
+        }
+    }
+}
+
+//Scattering by particles much smaller than the wavelength (clear air), 3 / (16 pi) (1 + cos^2)
+pub struct RayleighPhase;
+
+impl phase_function for RayleighPhase {
+    fn p(&self, cos_theta: f64) -> f64 {
+        3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta)
+    }
+    //inverts the cdf (mu^3 + 3 mu + 4) / 8 with Cardano's formula
+    fn sample_cos(&self) -> f64 {
+        let q = 4.0 * util::random_f64_0_1() - 2.0;
+        let u = (q + (q * q + 1.0).sqrt()).cbrt();
+        u - 1.0 / u
+    }
+}
\ No newline at end of file
@@ -0,0 +1,288 @@
+use crate::vec3::Vec3;
+use crate::perlins::{perlin, worley, noise_source};
+use crate::textures::texture;
+
+//Procedural textures: a scalar pattern from noise, colored by a ColorRamp
+//All of them take a seed, so the same scene renders the same pattern every time
+
+//Where a procedural texture is evaluated: at the 3D hit point (a solid texture, as if the object was
+//carved from the material) or at the surface's (u, v) like an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureSpace {
+    Solid,
+    Uv,
+}
+
+impl TextureSpace {
+    fn point(&self, u: f64, v: f64, p: &Vec3, scale: f64) -> Vec3 {
+        match self {
+            TextureSpace::Solid => *p * scale,
+            TextureSpace::Uv => Vec3::new(u, v, 0.0) * scale,
+        }
+    }
+}
+
+//Maps a pattern value in [0, 1] to a color, linear between the stops
+#[derive(Clone, Debug)]
+pub struct ColorRamp {
+    //(position, color), sorted by position
+    stops: Vec<(f64, Vec3)>,
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f64, Vec3)>) -> Self {
+        if stops.is_empty() {
+            panic!("a color ramp needs at least one stop");
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            stops,
+        }
+    }
+    pub fn new_two(from: Vec3, to: Vec3) -> Self {
+        Self::new(vec![(0.0, from), (1.0, to)])
+    }
+    //black to white
+    pub fn gray() -> Self {
+        Self::new_two(Vec3::zero(), Vec3::new(1.0, 1.0, 1.0))
+    }
+    pub fn value(&self, t: f64) -> Vec3 {
+        let first = self.stops[0];
+        if t <= first.0 {
+            return first.1;
+        }
+        for window in self.stops.windows(2) {
+            let (t0, c0) = window[0];
+            let (t1, c1) = window[1];
+            if t <= t1 {
+                let s = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return c0 * (1.0 - s) + c1 * s;
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+//Fractal Brownian motion, soft clouds and stains
+pub struct Fbm {
+    noise: perlin,
+    pub scale: f64,
+    pub octaves: u32,
+    //frequency and amplitude change from one octave to the next
+    pub lacunarity: f64,
+    pub gain: f64,
+    pub space: TextureSpace,
+    pub ramp: ColorRamp,
+}
+
+impl Fbm {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: perlin::new_seeded(seed),
+            scale,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+            space: TextureSpace::Solid,
+            ramp: ColorRamp::gray(),
+        }
+    }
+}
+
+impl texture for Fbm {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let q = self.space.point(u, v, p, self.scale);
+        let n = self.noise.fbm(&q, self.octaves, self.lacunarity, self.gain);
+        self.ramp.value((0.5 + n).clamp(0.0, 1.0))
+    }
+}
+
+//Ridged multifractal, mountain ridges, veins and lightning-like cracks
+pub struct RidgedMultifractal {
+    noise: perlin,
+    pub scale: f64,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+    //higher values make the ridges wider and brighter
+    pub offset: f64,
+    pub space: TextureSpace,
+    pub ramp: ColorRamp,
+}
+
+impl RidgedMultifractal {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: perlin::new_seeded(seed),
+            scale,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+            offset: 1.0,
+            space: TextureSpace::Solid,
+            ramp: ColorRamp::gray(),
+        }
+    }
+}
+
+impl texture for RidgedMultifractal {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let q = self.space.point(u, v, p, self.scale);
+        self.ramp.value(self.noise.ridged(&q, self.octaves, self.lacunarity, self.gain, self.offset))
+    }
+}
+
+//Which distance of the cellular noise makes the pattern
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorleyFeature {
+    //round cells, dark at the feature points
+    F1,
+    F2,
+    //thin lines along the cell borders, like cracked mud or scales
+    F2MinusF1,
+}
+
+//Worley (cellular) noise, with optional fBm octaves of it on top
+pub struct Worley {
+    noise: worley,
+    pub scale: f64,
+    pub feature: WorleyFeature,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+    pub space: TextureSpace,
+    pub ramp: ColorRamp,
+}
+
+impl Worley {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: worley::new_seeded(seed),
+            scale,
+            feature: WorleyFeature::F1,
+            octaves: 1,
+            lacunarity: 2.0,
+            gain: 0.5,
+            space: TextureSpace::Solid,
+            ramp: ColorRamp::gray(),
+        }
+    }
+    //0 is a regular grid of cells, 1 (the default) fully random
+    pub fn set_jitter(&mut self, jitter: f64) {
+        self.noise.jitter = jitter.clamp(0.0, 1.0);
+    }
+}
+
+impl texture for Worley {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let mut q = self.space.point(u, v, p, self.scale);
+        let mut accum = 0.0;
+        let mut total = 0.0;
+        let mut weight = 1.0;
+        for _ in 0..self.octaves.max(1) {
+            let (f1, f2) = self.noise.distances(&q);
+            accum += weight * match self.feature {
+                WorleyFeature::F1 => f1,
+                WorleyFeature::F2 => f2,
+                WorleyFeature::F2MinusF1 => f2 - f1,
+            };
+            total += weight;
+            weight *= self.gain;
+            q = q * self.lacunarity;
+        }
+        self.ramp.value((accum / total).clamp(0.0, 1.0))
+    }
+}
+
+//Veined marble: stripes along `direction` bent by turbulence, the old Noise texture with every
+//constant as a parameter (Noise::new(4.0) is scale 4 with the defaults)
+pub struct Marble {
+    noise: perlin,
+    //stripes per unit length along the direction
+    pub scale: f64,
+    pub direction: Vec3,
+    //how far the turbulence pushes the stripes around, and the size of its features
+    pub turbulence: f64,
+    pub noise_scale: f64,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+    pub space: TextureSpace,
+    pub ramp: ColorRamp,
+}
+
+impl Marble {
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self {
+            noise: perlin::new_seeded(seed),
+            scale,
+            direction: Vec3::new(0.0, 0.0, 1.0),
+            turbulence: 20.0,
+            noise_scale: 1.0,
+            octaves: 7,
+            lacunarity: 2.0,
+            gain: 0.5,
+            space: TextureSpace::Solid,
+            ramp: ColorRamp::gray(),
+        }
+    }
+}
+
+impl texture for Marble {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let q = self.space.point(u, v, p, 1.0);
+        let turb = self.noise.fbm(&(q * self.noise_scale), self.octaves, self.lacunarity, self.gain).abs();
+        let stripe = self.scale * (q * self.direction) + self.turbulence * turb;
+        self.ramp.value(0.5 * (1.0 + stripe.sin()))
+    }
+}
+
+//Growth rings around the y axis, made uneven by noise; rotate the object to turn the grain
+pub struct Wood {
+    noise: perlin,
+    //distance from one ring to the next
+    pub ring_spacing: f64,
+    //how much the rings wobble, in ring spacings, and the size of the wobbles
+    pub distortion: f64,
+    pub noise_scale: f64,
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+    pub space: TextureSpace,
+    //from early wood (t = 0) to the dark late wood at the end of a ring (t = 1)
+    pub ramp: ColorRamp,
+}
+
+impl Wood {
+    pub fn new(seed: u64, ring_spacing: f64) -> Self {
+        Self {
+            noise: perlin::new_seeded(seed),
+            ring_spacing,
+            distortion: 0.5,
+            noise_scale: 2.0,
+            octaves: 4,
+            lacunarity: 2.0,
+            gain: 0.5,
+            space: TextureSpace::Solid,
+            ramp: ColorRamp::new(vec![
+                (0.0, Vec3::new(0.72, 0.50, 0.30)),
+                (0.7, Vec3::new(0.60, 0.40, 0.22)),
+                (1.0, Vec3::new(0.35, 0.20, 0.10)),
+            ]),
+        }
+    }
+}
+
+impl texture for Wood {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let q = self.space.point(u, v, p, 1.0);
+        //in uv space the rings are around the middle of the texture
+        let (a, b) = match self.space {
+            TextureSpace::Solid => (q.x, q.z),
+            TextureSpace::Uv => (q.x - 0.5, q.y - 0.5),
+        };
+        let wobble = self.noise.fbm(&(q * self.noise_scale), self.octaves, self.lacunarity, self.gain);
+        let rings = (a * a + b * b).sqrt() / self.ring_spacing + self.distortion * wobble;
+        self.ramp.value(rings - rings.floor())
+    }
+}
\ No newline at end of file
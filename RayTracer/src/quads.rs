@@ -2,11 +2,23 @@ pub use crate::ray::Ray;
 use crate::Vec3;
 use crate::util;
 pub use crate::hittables::{hit_record, hittable, hittable_list};
-use crate::materials::{material, lambertian};
-use crate::textures::{Image};
+use crate::materials::{material};
 use crate::Interval;
 use std::sync::Arc;
 use crate::aabb::AABB;
+use crate::light_bvh::LightBounds;
+use crate::color;
+
+//The flat shapes that live in quad's plane, they differ only in which plane coordinates are inside
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlanarShape {
+    //Q + alpha u + beta v for alpha, beta in [0, 1]
+    Parallelogram,
+    //Q, Q + u, Q + v
+    Triangle,
+    //centered on Q with semi-axes u and v, alpha^2 + beta^2 <= 1; a disk when u and v are perpendicular and equally long
+    Ellipse,
+}
 
 pub struct quad {
     Q: Vec3,
@@ -14,6 +26,7 @@ pub struct quad {
     v: Vec3,
     w: Vec3,
     mat: Arc<dyn material + Send + Sync>,
+    shape: PlanarShape,
 
     //bounding box
     bbox: AABB,
@@ -21,22 +34,45 @@ pub struct quad {
     //temp variables
     normal: Vec3,
     D: f64,
+    area: f64,
 }
 
 impl quad {
     pub fn new(Q: Vec3, u: Vec3, v: Vec3, mat: Arc<dyn material + Send + Sync>) -> Self {
+        Self::new_shape(Q, u, v, mat, PlanarShape::Parallelogram)
+    }
+    //the triangle with corners Q, Q + u and Q + v, (u, v) are its barycentric coordinates
+    pub fn new_triangle(Q: Vec3, u: Vec3, v: Vec3, mat: Arc<dyn material + Send + Sync>) -> Self {
+        Self::new_shape(Q, u, v, mat, PlanarShape::Triangle)
+    }
+    //the ellipse around center with semi-axes u and v, the texture is mapped onto the square around it
+    pub fn new_ellipse(center: Vec3, u: Vec3, v: Vec3, mat: Arc<dyn material + Send + Sync>) -> Self {
+        Self::new_shape(center, u, v, mat, PlanarShape::Ellipse)
+    }
+    //a round disk facing `normal`
+    pub fn new_disk(center: Vec3, normal: Vec3, radius: f64, mat: Arc<dyn material + Send + Sync>) -> Self {
+        let (a, b) = util::orthonormal_basis(normal.unit_vector());
+        Self::new_shape(center, a * radius, b * radius, mat, PlanarShape::Ellipse)
+    }
+    fn new_shape(Q: Vec3, u: Vec3, v: Vec3, mat: Arc<dyn material + Send + Sync>, shape: PlanarShape) -> Self {
         let n = u.cross(v);
         let normal = n.unit_vector();
         let D = normal * Q;
         let w = n * (1.0 / (n * n));
-        let new_bbox = Self::set_bbox(Q, u, v);
+        let new_bbox = Self::set_bbox(Q, u, v, shape);
         // println!("quad bbox: [{}, {}], [{}, {}], [{}, {}]", new_bbox.x.tmin, new_bbox.x.tmax, new_bbox.y.tmin, new_bbox.y.tmax, new_bbox.z.tmin, new_bbox.z.tmax);
+        let area = match shape {
+            PlanarShape::Parallelogram => n.length(),
+            PlanarShape::Triangle => 0.5 * n.length(),
+            PlanarShape::Ellipse => std::f64::consts::PI * n.length(),
+        };
         Self {
             Q,
             u,
             v,
             w,
             mat,
+            shape,
 
             //bounding box
             bbox: new_bbox,
@@ -44,48 +80,100 @@ impl quad {
             //temp variables
             normal,
             D,
+            area,
         }
     }
-    fn set_bbox(Q: Vec3, u: Vec3, v: Vec3) -> AABB {
-        let bbox_diag1 = AABB::new_from_points(Q, Q + u + v);
-        let bbox_diag2 = AABB::new_from_points(Q + u, Q + v);
-        AABB::new_from_boxes(&bbox_diag1, &bbox_diag2)
+    fn set_bbox(Q: Vec3, u: Vec3, v: Vec3, shape: PlanarShape) -> AABB {
+        match shape {
+            PlanarShape::Parallelogram => {
+                let bbox_diag1 = AABB::new_from_points(Q, Q + u + v);
+                let bbox_diag2 = AABB::new_from_points(Q + u, Q + v);
+                AABB::new_from_boxes(&bbox_diag1, &bbox_diag2)
+            }
+            PlanarShape::Triangle => {
+                AABB::new_from_boxes(&AABB::new_from_points(Q, Q + u), &AABB::new_from_points(Q, Q + v))
+            }
+            PlanarShape::Ellipse => {
+                //Q + u cos(t) + v sin(t) reaches sqrt(u_i^2 + v_i^2) along each axis
+                let extent = Vec3::new((u.x * u.x + v.x * v.x).sqrt(), (u.y * u.y + v.y * v.y).sqrt(), (u.z * u.z + v.z * v.z).sqrt());
+                AABB::new_from_points(Q - extent, Q + extent)
+            }
+        }
     }
-    fn is_interior(alpha: f64, beta: f64, rec: &mut hit_record) -> bool {
-        let unit_interval = Interval::new(0.0, 1.0);
-        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
-            return false;
+    fn is_interior(&self, alpha: f64, beta: f64, rec: &mut hit_record) -> bool {
+        match self.shape {
+            PlanarShape::Parallelogram => {
+                let unit_interval = Interval::new(0.0, 1.0);
+                if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+                    return false;
+                }
+                rec.u = alpha;
+                rec.v = beta;
+            }
+            PlanarShape::Triangle => {
+                if alpha < 0.0 || beta < 0.0 || alpha + beta > 1.0 {
+                    return false;
+                }
+                rec.u = alpha;
+                rec.v = beta;
+            }
+            PlanarShape::Ellipse => {
+                if alpha * alpha + beta * beta > 1.0 {
+                    return false;
+                }
+                rec.u = 0.5 * alpha + 0.5;
+                rec.v = 0.5 * beta + 0.5;
+            }
         }
-        rec.u = alpha;
-        rec.v = beta;
         // println!("got u: {}, got v: {}", rec.u, rec.v);
         true
     }
-}
-
-impl hittable for quad {
-    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+    //where the ray meets the plane inside ray_t: t, the point and its plane coordinates
+    fn plane_hit(&self, r: &Ray, ray_t: &Interval) -> Option<(f64, Vec3, f64, f64)> {
         let denom = self.normal * r.b_direction;
 
         //No hit if ray is parallel to the plane
         if util::fabs(denom) < 1e-8 {
-            return false;
+            return None;
         }
 
-        //Return false if the hit point t is outside the ray_t interval
+        //No hit if the hit point t is outside the ray_t interval
         let t = (self.D - self.normal * r.a_origin) / denom;
         if !ray_t.contains(t) {
-            return false;
+            return None;
         }
 
-        //Determine the hit point lies within the quad using its plane coordinates
-
         let intersection = r.at(t);
         let planar_hitpt_vector = intersection - self.Q;
         let alpha = self.w * (planar_hitpt_vector.cross(self.v));
         let beta = self.w * (self.u.cross(planar_hitpt_vector));
+        Some((t, intersection, alpha, beta))
+    }
+    //where (u, v) = (0.5, 0.5) is
+    fn center(&self) -> Vec3 {
+        match self.shape {
+            PlanarShape::Parallelogram | PlanarShape::Triangle => self.Q + self.u * 0.5 + self.v * 0.5,
+            PlanarShape::Ellipse => self.Q,
+        }
+    }
+}
 
-        if !Self::is_interior(alpha, beta, rec) {
+impl hittable for quad {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        let (t, intersection, alpha, beta) = match self.plane_hit(r, ray_t) {
+            Some(hit) => hit,
+            None => return false,
+        };
+
+        //Determine the hit point lies within the quad using its plane coordinates
+        let (old_u, old_v) = (rec.u, rec.v);
+        if !self.is_interior(alpha, beta, rec) {
+            return false;
+        }
+        //Cut-out texels let the ray pass through, keep the uv of an earlier hit
+        if !self.mat.alpha_test(rec.u, rec.v, &intersection) {
+            rec.u = old_u;
+            rec.v = old_v;
             return false;
         }
         // println!("actually got u: {}, got v: {}", rec.u, rec.v);
@@ -95,12 +183,64 @@ impl hittable for quad {
         rec.p = intersection;
         rec.mat = Arc::clone(&self.mat);
         rec.set_face_normal(r, &self.normal);
+        //the ellipse's uv covers the square from -u - v to u + v
+        let uv_span = if self.shape == PlanarShape::Ellipse { 2.0 } else { 1.0 };
+        rec.dpdu = self.u * uv_span;
+        rec.dpdv = self.v * uv_span;
 
         true
     }
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    //the density of random(), which samples cut-out texels too, so the alpha test is skipped here
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = hit_record::new();
+        let (t, _, alpha, beta) = match self.plane_hit(&Ray::new(*origin, *direction, 0.0), &Interval::new(0.001, f64::INFINITY)) {
+            Some(hit) => hit,
+            None => return 0.0,
+        };
+        if !self.is_interior(alpha, beta, &mut rec) {
+            return 0.0;
+        }
+        //area density to solid angle density
+        let distance_squared = t * t * direction.squared_length();
+        let cosine = util::fabs(*direction * self.normal / direction.length());
+        distance_squared / (cosine * self.area)
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let (mut alpha, mut beta) = (util::random_f64_0_1(), util::random_f64_0_1());
+        match self.shape {
+            PlanarShape::Parallelogram => {}
+            //the half of the parallelogram outside the triangle folds back onto it
+            PlanarShape::Triangle => {
+                if alpha + beta > 1.0 {
+                    (alpha, beta) = (1.0 - alpha, 1.0 - beta);
+                }
+            }
+            PlanarShape::Ellipse => {
+                let radius = alpha.sqrt();
+                let phi = 2.0 * std::f64::consts::PI * beta;
+                (alpha, beta) = (radius * phi.cos(), radius * phi.sin());
+            }
+        }
+        let p = self.Q + self.u * alpha + self.v * beta;
+        p - *origin
+    }
+    fn area(&self) -> f64 {
+        self.area
+    }
+    //diffuse_light shines from both sides of a quad unless it is made one-sided
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let center = self.center();
+        let two_sided = self.mat.emits_two_sided();
+        let sides = if two_sided { 2.0 } else { 1.0 };
+        let power = color::luminance(self.mat.emitted(0.5, 0.5, &center)) * self.area * sides;
+        if power <= 0.0 {
+            return None;
+        }
+        Some(LightBounds::new(&self.bbox, power, self.normal, 1.0, 0.0, two_sided))
+    }
 }
 
 pub fn newbox(a: Vec3, b: Vec3, mat: Arc<dyn material + Send + Sync>) -> hittable_list {
@@ -116,80 +256,14 @@ pub fn newbox(a: Vec3, b: Vec3, mat: Arc<dyn material + Send + Sync>) -> hittabl
     let dy = Vec3::new(0.0, max.y - min.y, 0.0);
     let dz = Vec3::new(0.0, 0.0, max.z - min.z);
 
-    let wsh_texture = Arc::new(Image::new("wsh_light.png"));
-    let wsh_surface = Arc::new(lambertian::new_from_texture(wsh_texture.clone()));
-
     sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, mat.clone())));//front
     sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, max.z), dz * (-1.0), dy, mat.clone())));//right
     sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, min.z), dx * (-1.0), dy, mat.clone())));//back
     sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, mat.clone())));//left
     sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), mat.clone())));//top
-    // sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), wsh_surface.clone())));//top with wsh texture
     sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dx, dz, mat.clone())));//bottom
 
     // println!("bbox of box: [{}, {}], [{}, {}], [{}, {}]", sides.bbox.x.tmin, sides.bbox.x.tmax, sides.bbox.y.tmin, sides.bbox.y.tmax, sides.bbox.z.tmin, sides.bbox.z.tmax);
-    
-    //FOR MINECRAFT SCENE
-    /*   
-
-    //chest
-    let chest_top_texture = Arc::new(Image::new("chest_top.png"));
-    let chest_top = Arc::new(lambertian::new_from_texture(chest_top_texture.clone()));
-
-    let chest_side_texture = Arc::new(Image::new("chest_side.png"));
-    let chest_side = Arc::new(lambertian::new_from_texture(chest_side_texture.clone()));
-
-    let chest_front_texture = Arc::new(Image::new("chest_front.png"));
-    let chest_front = Arc::new(lambertian::new_from_texture(chest_front_texture.clone()));
-
-    if(a.x == 5.0 && a.y == 3.0 && a.z == 1.0) {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, chest_side.clone())));//front
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, max.z), dz * (-1.0), dy, chest_side.clone())));//right
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, min.z), dx * (-1.0), dy, chest_side.clone())));//back
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, chest_front.clone())));//left
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), chest_top.clone())));//top
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dx, dz, chest_top.clone())));//bottom
-        return sides;
-    }
-
-
-    if b.y == 3.0 {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, grass_side.clone())));//front
-    }
-    else {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, mat.clone())));//front
-    }
-
-    if b.y == 3.0 {
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, max.z), dz * (-1.0), dy, grass_side.clone())));//right
-    }
-    else {
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, max.z), dz * (-1.0), dy, mat.clone())));//right
-    }
-
-    if b.y == 3.0 {
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, min.z), dx * (-1.0), dy, grass_side.clone())));//back
-    }
-    else {
-        sides.add(Arc::new(quad::new(Vec3::new(max.x, min.y, min.z), dx * (-1.0), dy, mat.clone())));//back
-    }
-
-    if b.y == 3.0 {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, grass_side.clone())));//left
-    }
-    else {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, mat.clone())));//left
-    }
-
-    if b.y == 3.0 {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), grass_top.clone())));//top
-    }
-    else {
-        sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), mat.clone())));//top
-    }
-    // sides.add(Arc::new(quad::new(Vec3::new(min.x, max.y, max.z), dx, dz * (-1.0), wsh_surface.clone())));//top with wsh texture
-    sides.add(Arc::new(quad::new(Vec3::new(min.x, min.y, min.z), dx, dz, mat.clone())));//bottom
 
-     */
     sides
 }
\ No newline at end of file
@@ -2,12 +2,27 @@
 **使用了蒋捷提供的ray.rs
 */
 pub use crate::vec3::Vec3;
+use crate::spectrum::SampledWavelengths;
+
+//offset rays one pixel to the right (x) and one pixel down (y), used to estimate texture footprints
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayDifferential {
+    pub rx_origin: Vec3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Vec3,
+    pub ry_direction: Vec3,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Ray {
     pub a_origin: Vec3,
     pub b_direction: Vec3,
     pub time: f64,
+
+    //only camera rays carry differentials
+    pub differential: Option<RayDifferential>,
+    //set on every ray of a path in spectral mode
+    pub wavelengths: Option<SampledWavelengths>,
 }
 
 impl Ray {
@@ -16,6 +31,17 @@ impl Ray {
             a_origin,
             b_direction,
             time,
+            differential: None,
+            wavelengths: None,
+        }
+    }
+    pub fn new_with_differential(a_origin: Vec3, b_direction: Vec3, time: f64, differential: RayDifferential) -> Self {
+        Self {
+            a_origin,
+            b_direction,
+            time,
+            differential: Some(differential),
+            wavelengths: None,
         }
     }
     pub fn at(&self, t: f64) -> Vec3 {
@@ -0,0 +1,179 @@
+use crate::vec3::Vec3;
+use crate::color::xyz_to_linear_srgb;
+use crate::environment::environment;
+use crate::util;
+
+//Perez et al. sky luminance distribution: (1 + A e^(B / cos theta)) (1 + C e^(D gamma) + E cos^2 gamma)
+#[derive(Clone, Copy)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    fn f(&self, cos_theta: f64, gamma: f64) -> f64 {
+        (1.0 + self.a * (self.b / cos_theta).exp()) * (1.0 + self.c * (self.d * gamma).exp() + self.e * gamma.cos() * gamma.cos())
+    }
+}
+
+//Preetham, Shirley & Smits "A Practical Analytic Model for Daylight" (1999): clear sky radiance and a sun disk
+//the sun is tinted by Rayleigh and aerosol extinction along its path through the atmosphere
+pub struct Sky {
+    //brightness multiplier of the sky, Preetham's luminance is in kcd/m^2
+    pub sky_intensity: f64,
+    //irradiance of the sun on a surface facing it, before the atmosphere
+    pub sun_irradiance: f64,
+    //seen below the horizon
+    pub ground: Vec3,
+
+    sun_direction: Vec3,
+    //angular radius of the sun disk in radians
+    sun_radius: f64,
+    cos_sun_radius: f64,
+    sun_color: Vec3,
+
+    theta_sun: f64,
+    zenith: Vec3, //Y, x, y at the zenith
+    perez_y: Perez, //luminance
+    perez_cx: Perez, //chromaticity x
+    perez_cy: Perez, //chromaticity y
+}
+
+impl Sky {
+    //elevation above the horizon and azimuth in degrees, azimuth 0 looks towards -z and 90 towards +x
+    //turbidity: 2 for a very clear sky, 10 for a hazy one
+    pub fn new(sun_elevation: f64, sun_azimuth: f64, turbidity: f64) -> Self {
+        let el = sun_elevation.to_radians();
+        let az = sun_azimuth.to_radians();
+        let sun_direction = Vec3::new(el.cos() * az.sin(), el.sin(), -el.cos() * az.cos());
+
+        //the model is only valid for the sun above the horizon
+        let theta_sun = util::fmin(std::f64::consts::FRAC_PI_2 - el, std::f64::consts::FRAC_PI_2);
+        let t = turbidity;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * theta_sun);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let th = theta_sun;
+        let (th2, th3) = (th * th, th * th * th);
+        let zenith_x = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let zenith_y = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        let sun_radius = (0.533f64 / 2.0).to_radians();
+        let mut sky = Self {
+            sky_intensity: 0.1,
+            sun_irradiance: 10.0,
+            ground: Vec3::new(0.05, 0.05, 0.05),
+
+            sun_direction,
+            sun_radius,
+            cos_sun_radius: sun_radius.cos(),
+            sun_color: Vec3::ones(),
+
+            theta_sun,
+            zenith: Vec3::new(zenith_luminance, zenith_x, zenith_y),
+            perez_y: Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            perez_cx: Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            perez_cy: Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        };
+        sky.sun_color = Self::sun_transmittance(theta_sun, t);
+        sky
+    }
+    pub fn sun_direction(&self) -> Vec3 {
+        self.sun_direction
+    }
+    //angular diameter of the sun disk in degrees, 0.533 by default; larger suns give softer shadows
+    pub fn set_sun_size(&mut self, degrees: f64) {
+        self.sun_radius = (degrees / 2.0).to_radians();
+        self.cos_sun_radius = self.sun_radius.cos();
+    }
+
+    //Rayleigh and aerosol (Angstrom) extinction at 650, 570 and 475 nm for the optical mass of the sun's path
+    fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Vec3 {
+        let theta_deg = theta_sun.to_degrees();
+        let m = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        let tau = |lambda: f64| -> f64 {
+            let rayleigh = 0.008735 * lambda.powf(-4.08);
+            let aerosol = beta * lambda.powf(-1.3);
+            (-(rayleigh + aerosol) * m).exp()
+        };
+        Vec3::new(tau(0.650), tau(0.570), tau(0.475))
+    }
+    fn sun_above_horizon(&self) -> bool {
+        self.sun_direction.y > 0.0
+    }
+    fn sun_solid_angle(&self) -> f64 {
+        2.0 * std::f64::consts::PI * (1.0 - self.cos_sun_radius)
+    }
+
+    //sky radiance without the sun disk
+    pub fn sky_radiance(&self, dir: &Vec3) -> Vec3 {
+        let d = dir.unit_vector();
+        if d.y <= 0.0 {
+            return self.ground;
+        }
+        let cos_theta = util::fmax(d.y, 0.001);
+        let gamma = (d * self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let big_y = self.zenith.x * self.perez_y.f(cos_theta, gamma) / self.perez_y.f(1.0, self.theta_sun);
+        let x = self.zenith.y * self.perez_cx.f(cos_theta, gamma) / self.perez_cx.f(1.0, self.theta_sun);
+        let y = self.zenith.z * self.perez_cy.f(cos_theta, gamma) / self.perez_cy.f(1.0, self.theta_sun);
+
+        //xyY to XYZ
+        let xyz = Vec3::new(x / y * big_y, big_y, (1.0 - x - y) / y * big_y);
+        xyz_to_linear_srgb(xyz) * self.sky_intensity
+    }
+    //radiance of the sun disk, zero outside of it
+    pub fn sun_radiance(&self, dir: &Vec3) -> Vec3 {
+        if !self.sun_above_horizon() || dir.unit_vector() * self.sun_direction < self.cos_sun_radius {
+            return Vec3::zero();
+        }
+        self.sun_color * (self.sun_irradiance / self.sun_solid_angle())
+    }
+}
+
+impl environment for Sky {
+    fn value(&self, dir: &Vec3) -> Vec3 {
+        self.sky_radiance(dir) + self.sun_radiance(dir)
+    }
+    //half of the samples go to the sun disk, the others are uniform over the sphere
+    fn sample(&self) -> Vec3 {
+        if !self.sun_above_horizon() || util::random_f64_0_1() < 0.5 {
+            return util::random_on_unit_sphere();
+        }
+        util::random_in_cone(self.sun_direction, self.cos_sun_radius)
+    }
+    fn pdf(&self, dir: &Vec3) -> f64 {
+        let uniform = 1.0 / (4.0 * std::f64::consts::PI);
+        if !self.sun_above_horizon() {
+            return uniform;
+        }
+        let cone = if dir.unit_vector() * self.sun_direction >= self.cos_sun_radius { 1.0 / self.sun_solid_angle() } else { 0.0 };
+        0.5 * uniform + 0.5 * cone
+    }
+}
\ No newline at end of file
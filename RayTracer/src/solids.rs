@@ -0,0 +1,626 @@
+pub use crate::ray::Ray;
+use crate::Vec3;
+use crate::util;
+pub use crate::hittables::{hit_record, hittable};
+use crate::materials::{material};
+use crate::Interval;
+use std::sync::Arc;
+use crate::aabb::AABB;
+use crate::light_bvh::LightBounds;
+use crate::color;
+use std::f64::consts::PI;
+
+//Solids of revolution around an arbitrary axis. The ray is moved into a frame where the axis is y and
+//the solid starts at the origin, intersected there, and the hit is moved back
+
+struct AxisFrame {
+    origin: Vec3,
+    x: Vec3,
+    y: Vec3,
+    z: Vec3,
+}
+
+impl AxisFrame {
+    fn new(origin: Vec3, axis: Vec3) -> Self {
+        let y = axis.unit_vector();
+        //right handed: x cross y = z
+        let (z, x) = util::orthonormal_basis(y);
+        Self {
+            origin,
+            x,
+            y,
+            z,
+        }
+    }
+    fn to_local(&self, p: Vec3) -> Vec3 {
+        let d = p - self.origin;
+        Vec3::new(d * self.x, d * self.y, d * self.z)
+    }
+    fn direction_to_local(&self, d: Vec3) -> Vec3 {
+        Vec3::new(d * self.x, d * self.y, d * self.z)
+    }
+    fn to_world(&self, v: Vec3) -> Vec3 {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+    //a disk of radius r around c perpendicular to the axis reaches r * sqrt(1 - y_i^2) along world axis i
+    fn disk_extent(&self, radius: f64) -> Vec3 {
+        let y = self.y;
+        Vec3::new(
+            radius * (1.0 - y.x * y.x).max(0.0).sqrt(),
+            radius * (1.0 - y.y * y.y).max(0.0).sqrt(),
+            radius * (1.0 - y.z * y.z).max(0.0).sqrt(),
+        )
+    }
+}
+
+//a candidate intersection in the local frame
+struct LocalHit {
+    t: f64,
+    p: Vec3,
+    normal: Vec3,
+    u: f64,
+    v: f64,
+    dpdu: Vec3,
+    dpdv: Vec3,
+}
+
+//solid angle density of sampling the surface uniformly by area, summed over every point where the
+//direction crosses it since a sample on the far side is seen through the near one
+fn area_pdf(frame: &AxisFrame, hits: Vec<LocalHit>, direction: &Vec3, area: f64) -> f64 {
+    let d = frame.direction_to_local(*direction);
+    hits.iter().filter(|hit| hit.t > 0.001).map(|hit| {
+        let distance_squared = hit.t * hit.t * d.squared_length();
+        let cosine = util::fabs(d * hit.normal.unit_vector() / d.length());
+        distance_squared / (cosine * area)
+    }).sum()
+}
+
+//closed emitters shine all around
+fn omni_bounds(mat: &Arc<dyn material + Send + Sync>, center: &Vec3, bbox: &AABB, area: f64) -> Option<LightBounds> {
+    let power = color::luminance(mat.emitted(0.5, 0.5, center)) * area;
+    if power <= 0.0 {
+        return None;
+    }
+    Some(LightBounds::new_omni(bbox, power))
+}
+
+//uniform point on the disk of `radius` in the plane y = height
+fn random_on_disk(height: f64, radius: f64) -> Vec3 {
+    let rho = radius * util::random_f64_0_1().sqrt();
+    let phi = 2.0 * PI * util::random_f64_0_1();
+    Vec3::new(rho * phi.cos(), height, rho * phi.sin())
+}
+
+//the nearest candidate inside ray_t that is not cut out, written to rec in world space
+fn closest_hit(frame: &AxisFrame, mat: &Arc<dyn material + Send + Sync>, r: &Ray, ray_t: &Interval, mut hits: Vec<LocalHit>, rec: &mut hit_record) -> bool {
+    hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+    for hit in hits {
+        if !ray_t.surrounds(hit.t) {
+            continue;
+        }
+        let p = r.at(hit.t);
+        if !mat.alpha_test(hit.u, hit.v, &p) {
+            continue;
+        }
+        rec.t = hit.t;
+        rec.p = p;
+        rec.set_face_normal(r, &frame.to_world(hit.normal).unit_vector());
+        rec.u = hit.u;
+        rec.v = hit.v;
+        rec.dpdu = frame.to_world(hit.dpdu);
+        rec.dpdv = frame.to_world(hit.dpdv);
+        rec.mat = Arc::clone(mat);
+        return true;
+    }
+    false
+}
+
+//u around the axis, starting at local +x
+fn azimuth(x: f64, z: f64) -> f64 {
+    let phi = z.atan2(x);
+    (if phi < 0.0 { phi + 2.0 * PI } else { phi }) / (2.0 * PI)
+}
+
+//hit of the disk of `radius` in the plane y = height, facing up or down
+fn cap_hit(o: Vec3, d: Vec3, height: f64, radius: f64, up: bool) -> Option<LocalHit> {
+    if util::fabs(d.y) < 1e-12 {
+        return None;
+    }
+    let t = (height - o.y) / d.y;
+    let p = o + d * t;
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return None;
+    }
+    let sign = if up { 1.0 } else { -1.0 };
+    Some(LocalHit {
+        t,
+        p,
+        normal: Vec3::new(0.0, sign, 0.0),
+        u: 0.5 + 0.5 * p.x / radius,
+        v: 0.5 + 0.5 * sign * p.z / radius,
+        dpdu: Vec3::new(2.0 * radius, 0.0, 0.0),
+        dpdv: Vec3::new(0.0, 0.0, 2.0 * sign * radius),
+    })
+}
+
+//A cylinder from base to top, closed by a disk at both ends
+//On the side u goes around the axis and v from base to top, the caps are mapped like disks
+pub struct cylinder {
+    frame: AxisFrame,
+    radius: f64,
+    height: f64,
+    mat: Arc<dyn material + Send + Sync>,
+    bbox: AABB,
+}
+
+impl cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f64, mat: Arc<dyn material + Send + Sync>) -> Self {
+        let frame = AxisFrame::new(base, top - base);
+        let extent = frame.disk_extent(radius);
+        let bbox = AABB::new_from_boxes(&AABB::new_from_points(base - extent, base + extent), &AABB::new_from_points(top - extent, top + extent));
+        Self {
+            frame,
+            radius,
+            height: (top - base).length(),
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl cylinder {
+    //every crossing of the ray's line with the surface, in the local frame
+    fn local_hits(&self, r: &Ray) -> Vec<LocalHit> {
+        let o = self.frame.to_local(r.a_origin);
+        let d = self.frame.direction_to_local(r.b_direction);
+        let mut hits = Vec::new();
+
+        //side: x^2 + z^2 = radius^2
+        let a = d.x * d.x + d.z * d.z;
+        if a > 1e-12 {
+            let h = o.x * d.x + o.z * d.z;
+            let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+            let discriminant = h * h - a * c;
+            if discriminant >= 0.0 {
+                let sqrtd = discriminant.sqrt();
+                for t in [(-h - sqrtd) / a, (-h + sqrtd) / a] {
+                    let p = o + d * t;
+                    if p.y < 0.0 || p.y > self.height {
+                        continue;
+                    }
+                    hits.push(LocalHit {
+                        t,
+                        p,
+                        normal: Vec3::new(p.x, 0.0, p.z) / self.radius,
+                        u: azimuth(p.x, p.z),
+                        v: p.y / self.height,
+                        dpdu: Vec3::new(-p.z, 0.0, p.x) * (2.0 * PI),
+                        dpdv: Vec3::new(0.0, self.height, 0.0),
+                    });
+                }
+            }
+        }
+        hits.extend(cap_hit(o, d, 0.0, self.radius, false));
+        hits.extend(cap_hit(o, d, self.height, self.radius, true));
+        hits
+    }
+    fn side_area(&self) -> f64 {
+        2.0 * PI * self.radius * self.height
+    }
+}
+
+impl hittable for cylinder {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        closest_hit(&self.frame, &self.mat, r, ray_t, self.local_hits(r), rec)
+    }
+    fn bbox(&self) -> &AABB {
+        &self.bbox
+    }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        area_pdf(&self.frame, self.local_hits(&Ray::new(*origin, *direction, 0.0)), direction, self.area())
+    }
+    //uniform over the side and both caps
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let pick = util::random_f64_0_1() * self.area();
+        let p = if pick < self.side_area() {
+            let phi = 2.0 * PI * util::random_f64_0_1();
+            Vec3::new(self.radius * phi.cos(), self.height * util::random_f64_0_1(), self.radius * phi.sin())
+        } else {
+            let top = pick - self.side_area() < PI * self.radius * self.radius;
+            random_on_disk(if top { self.height } else { 0.0 }, self.radius)
+        };
+        self.frame.origin + self.frame.to_world(p) - *origin
+    }
+    fn area(&self) -> f64 {
+        self.side_area() + 2.0 * PI * self.radius * self.radius
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let center = self.frame.origin + self.frame.y * (0.5 * self.height);
+        omni_bounds(&self.mat, &center, &self.bbox, self.area())
+    }
+}
+
+//A cone with its base disk around base and its tip at apex, the base is closed
+//On the side u goes around the axis and v from the base to the tip
+pub struct cone {
+    frame: AxisFrame,
+    radius: f64,
+    height: f64,
+    mat: Arc<dyn material + Send + Sync>,
+    bbox: AABB,
+}
+
+impl cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f64, mat: Arc<dyn material + Send + Sync>) -> Self {
+        let frame = AxisFrame::new(base, apex - base);
+        let extent = frame.disk_extent(radius);
+        let bbox = AABB::new_from_boxes(&AABB::new_from_points(base - extent, base + extent), &AABB::new_from_points(apex, apex));
+        Self {
+            frame,
+            radius,
+            height: (apex - base).length(),
+            mat,
+            bbox,
+        }
+    }
+}
+
+impl cone {
+    //every crossing of the ray's line with the surface, in the local frame
+    fn local_hits(&self, r: &Ray) -> Vec<LocalHit> {
+        let o = self.frame.to_local(r.a_origin);
+        let d = self.frame.direction_to_local(r.b_direction);
+        let mut hits = Vec::new();
+
+        //side: x^2 + z^2 = (k (height - y))^2 with k = radius / height
+        let k2 = (self.radius / self.height) * (self.radius / self.height);
+        let above = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let h = o.x * d.x + o.z * d.z + k2 * above * d.y;
+        let c = o.x * o.x + o.z * o.z - k2 * above * above;
+        let roots = if util::fabs(a) < 1e-12 {
+            //parallel to the slant, one crossing
+            if util::fabs(h) < 1e-12 { vec![] } else { vec![-c / (2.0 * h)] }
+        } else {
+            let discriminant = h * h - a * c;
+            if discriminant < 0.0 {
+                vec![]
+            } else {
+                let sqrtd = discriminant.sqrt();
+                vec![(-h - sqrtd) / a, (-h + sqrtd) / a]
+            }
+        };
+        for t in roots {
+            let p = o + d * t;
+            //the equation also holds on the mirrored cone above the tip
+            if p.y < 0.0 || p.y > self.height {
+                continue;
+            }
+            let rho = (p.x * p.x + p.z * p.z).sqrt();
+            let normal = if rho > 1e-12 {
+                Vec3::new(p.x / rho, self.radius / self.height, p.z / rho)
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            };
+            let (cos_phi, sin_phi) = if rho > 1e-12 { (p.x / rho, p.z / rho) } else { (1.0, 0.0) };
+            hits.push(LocalHit {
+                t,
+                p,
+                normal,
+                u: azimuth(p.x, p.z),
+                v: p.y / self.height,
+                dpdu: Vec3::new(-p.z, 0.0, p.x) * (2.0 * PI),
+                dpdv: Vec3::new(-self.radius * cos_phi, self.height, -self.radius * sin_phi),
+            });
+        }
+        hits.extend(cap_hit(o, d, 0.0, self.radius, false));
+        hits
+    }
+    fn side_area(&self) -> f64 {
+        PI * self.radius * (self.radius * self.radius + self.height * self.height).sqrt()
+    }
+}
+
+impl hittable for cone {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        closest_hit(&self.frame, &self.mat, r, ray_t, self.local_hits(r), rec)
+    }
+    fn bbox(&self) -> &AABB {
+        &self.bbox
+    }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        area_pdf(&self.frame, self.local_hits(&Ray::new(*origin, *direction, 0.0)), direction, self.area())
+    }
+    //uniform over the side and the base
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let p = if util::random_f64_0_1() * self.area() < self.side_area() {
+            //the unrolled side is a circular sector, the area up to distance s from the tip grows as s^2
+            let s = util::random_f64_0_1().sqrt();
+            let phi = 2.0 * PI * util::random_f64_0_1();
+            Vec3::new(self.radius * s * phi.cos(), self.height * (1.0 - s), self.radius * s * phi.sin())
+        } else {
+            random_on_disk(0.0, self.radius)
+        };
+        self.frame.origin + self.frame.to_world(p) - *origin
+    }
+    fn area(&self) -> f64 {
+        self.side_area() + PI * self.radius * self.radius
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let center = self.frame.origin + self.frame.y * (0.5 * self.height);
+        omni_bounds(&self.mat, &center, &self.bbox, self.area())
+    }
+}
+
+//A torus around center: the tube of minor_radius around the circle of major_radius perpendicular to axis
+//u goes around the axis and v around the tube, starting on the outside
+pub struct torus {
+    frame: AxisFrame,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Arc<dyn material + Send + Sync>,
+    bbox: AABB,
+}
+
+impl torus {
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f64, minor_radius: f64, mat: Arc<dyn material + Send + Sync>) -> Self {
+        let frame = AxisFrame::new(center, axis);
+        let y = frame.y;
+        let ring = frame.disk_extent(major_radius + minor_radius);
+        let extent = Vec3::new(ring.x + minor_radius * y.x.abs(), ring.y + minor_radius * y.y.abs(), ring.z + minor_radius * y.z.abs());
+        Self {
+            frame,
+            major_radius,
+            minor_radius,
+            mat,
+            bbox: AABB::new_from_points(center - extent, center + extent),
+        }
+    }
+}
+
+//p(t) = c[0] t^n + ... + c[n]
+fn polynomial(c: &[f64], t: f64) -> f64 {
+    c.iter().fold(0.0, |acc, &ci| acc * t + ci)
+}
+
+//real roots of the polynomial in [lo, hi], in increasing order: the roots of the derivative split the
+//interval into pieces where the polynomial is monotonic, each holds at most one root found by bisection
+fn polynomial_roots(c: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let n = c.len() - 1;
+    if n == 0 {
+        return vec![];
+    }
+    if n == 1 {
+        if c[0] == 0.0 {
+            return vec![];
+        }
+        let t = -c[1] / c[0];
+        return if t >= lo && t <= hi { vec![t] } else { vec![] };
+    }
+    let derivative: Vec<f64> = (0..n).map(|i| c[i] * (n - i) as f64).collect();
+    let mut ends = vec![lo];
+    ends.extend(polynomial_roots(&derivative, lo, hi));
+    ends.push(hi);
+    let mut roots = Vec::new();
+    for pair in ends.windows(2) {
+        let (mut a, mut b) = (pair[0], pair[1]);
+        let (mut fa, fb) = (polynomial(c, a), polynomial(c, b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        if fa * fb > 0.0 {
+            continue;
+        }
+        for _ in 0..64 {
+            let m = 0.5 * (a + b);
+            let fm = polynomial(c, m);
+            if fa * fm <= 0.0 {
+                b = m;
+            } else {
+                a = m;
+                fa = fm;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    roots
+}
+
+impl torus {
+    //the crossings of the ray with the surface inside ray_t, in the local frame
+    fn local_hits(&self, r: &Ray, ray_t: &Interval) -> Vec<LocalHit> {
+        let o = self.frame.to_local(r.a_origin);
+        let d = self.frame.direction_to_local(r.b_direction);
+        let length = d.length();
+        if length <= 0.0 {
+            return vec![];
+        }
+        let d = d / length;
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        //only the part of the ray inside the bounding sphere can hit, and starting the quartic there
+        //keeps its coefficients small
+        let bound = major + minor;
+        let h = o * d;
+        let discriminant = h * h - (o.squared_length() - bound * bound);
+        if discriminant < 0.0 {
+            return vec![];
+        }
+        let s0 = -h - discriminant.sqrt();
+        let s1 = -h + discriminant.sqrt();
+        let lo = s0.max(ray_t.tmin * length);
+        let hi = s1.min(ray_t.tmax * length);
+        if lo > hi {
+            return vec![];
+        }
+
+        //(|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along p = o + d s
+        let o = o + d * s0;
+        let b = o * d;
+        let g = o.squared_length() + major * major - minor * minor;
+        let four_r2 = 4.0 * major * major;
+        let coefficients = [
+            1.0,
+            4.0 * b,
+            4.0 * b * b + 2.0 * g - four_r2 * (d.x * d.x + d.z * d.z),
+            4.0 * b * g - 2.0 * four_r2 * (o.x * d.x + o.z * d.z),
+            g * g - four_r2 * (o.x * o.x + o.z * o.z),
+        ];
+        let mut hits = Vec::new();
+        for s in polynomial_roots(&coefficients, lo - s0, hi - s0) {
+            let p = o + d * s;
+            let rho = (p.x * p.x + p.z * p.z).sqrt();
+            if rho <= 1e-12 {
+                continue;
+            }
+            let (cos_phi, sin_phi) = (p.x / rho, p.z / rho);
+            //the normal points away from the nearest point of the center circle
+            let core = Vec3::new(major * cos_phi, 0.0, major * sin_phi);
+            let theta = p.y.atan2(rho - major);
+            let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+            hits.push(LocalHit {
+                t: (s0 + s) / length,
+                p,
+                normal: (p - core) / minor,
+                u: azimuth(p.x, p.z),
+                v: theta / (2.0 * PI),
+                dpdu: Vec3::new(-p.z, 0.0, p.x) * (2.0 * PI),
+                dpdv: Vec3::new(-minor * theta.sin() * cos_phi, minor * theta.cos(), -minor * theta.sin() * sin_phi) * (2.0 * PI),
+            });
+        }
+        hits
+    }
+}
+
+impl hittable for torus {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        closest_hit(&self.frame, &self.mat, r, ray_t, self.local_hits(r, ray_t), rec)
+    }
+    fn bbox(&self) -> &AABB {
+        &self.bbox
+    }
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let hits = self.local_hits(&Ray::new(*origin, *direction, 0.0), &Interval::new(0.001, f64::INFINITY));
+        area_pdf(&self.frame, hits, direction, self.area())
+    }
+    //uniform by area: the outside of the tube is larger than the inside, so theta is accepted
+    //in proportion to the distance from the axis
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let theta = loop {
+            let theta = 2.0 * PI * util::random_f64_0_1();
+            if util::random_f64_0_1() * (major + minor) <= major + minor * theta.cos() {
+                break theta;
+            }
+        };
+        let phi = 2.0 * PI * util::random_f64_0_1();
+        let rho = major + minor * theta.cos();
+        let p = Vec3::new(rho * phi.cos(), minor * theta.sin(), rho * phi.sin());
+        self.frame.origin + self.frame.to_world(p) - *origin
+    }
+    fn area(&self) -> f64 {
+        4.0 * PI * PI * self.major_radius * self.minor_radius
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        omni_bounds(&self.mat, &self.frame.origin, &self.bbox, self.area())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::diffuse_light;
+
+    fn light() -> Arc<dyn material + Send + Sync> {
+        Arc::new(diffuse_light::new_from_color(Vec3::new(1.0, 1.0, 1.0)))
+    }
+
+    fn hit(object: &dyn hittable, origin: Vec3, direction: Vec3) -> Option<hit_record> {
+        let mut rec = hit_record::new();
+        if object.hit(&Ray::new(origin, direction, 0.0), &Interval::new(0.001, f64::INFINITY), &mut rec) {
+            Some(rec)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn quartic_roots() {
+        //(t - 1)(t - 2)(t - 3)(t - 4)
+        let c = [1.0, -10.0, 35.0, -50.0, 24.0];
+        let roots = polynomial_roots(&c, 0.0, 5.0);
+        assert_eq!(roots.len(), 4, "{:?}", roots);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-9, "{:?}", roots);
+        }
+        let inside = polynomial_roots(&c, 1.5, 3.5);
+        assert_eq!(inside.len(), 2, "{:?}", inside);
+        assert!((inside[0] - 2.0).abs() < 1e-9 && (inside[1] - 3.0).abs() < 1e-9, "{:?}", inside);
+        //t^4 + 1 has no real roots
+        assert!(polynomial_roots(&[1.0, 0.0, 0.0, 0.0, 1.0], -10.0, 10.0).is_empty());
+    }
+
+    #[test]
+    fn torus_hole_misses() {
+        let ring = torus::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, light());
+        assert!(hit(&ring, Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).is_none());
+        //tilted through the hole, staying clear of the tube
+        assert!(hit(&ring, Vec3::new(0.5, 5.0, 0.0), Vec3::new(-0.1, -1.0, 0.0)).is_none());
+        let rec = hit(&ring, Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0)).expect("the tube is in the way");
+        assert!((rec.t - 2.5).abs() < 1e-9, "{}", rec.t);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-9);
+        //the ray leaves the outer tube and hits the inside of the far side
+        let rec = hit(&ring, Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0)).expect("the tube is in the way");
+        assert!((rec.t - 0.5).abs() < 1e-9, "{}", rec.t);
+    }
+
+    #[test]
+    fn cone_tip_grazing() {
+        let tip = Vec3::new(0.0, 1.0, 0.0);
+        let spike = cone::new(Vec3::new(0.0, 0.0, 0.0), tip, 1.0, light());
+        let rec = hit(&spike, Vec3::new(-2.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0)).expect("the ray touches the tip");
+        assert!((rec.p - tip).length() < 1e-9);
+        assert!(rec.normal.x.is_finite() && rec.normal.y.is_finite() && rec.normal.z.is_finite());
+        assert!(hit(&spike, Vec3::new(-2.0, 1.001, 0.0), Vec3::new(1.0, 0.0, 0.0)).is_none());
+        //along the slant the quadratic degenerates
+        let rec = hit(&spike, Vec3::new(-1.0, 2.0, 0.0), Vec3::new(1.0, -1.0, 0.0)).expect("the ray runs down the side");
+        assert!(rec.t > 0.0 && rec.p.y <= 1.0 + 1e-9);
+    }
+
+    //area sampling and its density agree: 1 / pdf of the sampled directions averages to the solid angle
+    //the shape covers, which is counted with uniform directions
+    fn assert_sampling_consistent(object: &dyn hittable, origin: Vec3) {
+        let n = 100000;
+        let covered = (0..n).filter(|_| hit(object, origin, util::random_on_unit_sphere()).is_some()).count();
+        let solid_angle = 4.0 * PI * covered as f64 / n as f64;
+        let (mut estimate, mut missed) = (0.0, 0);
+        for _ in 0..n {
+            let direction = object.random(&origin);
+            let pdf = object.pdf_value(&origin, &direction);
+            //a point on the silhouette can be tangent to the ray and lost to rounding
+            if pdf > 0.0 {
+                estimate += 1.0 / pdf;
+            } else {
+                missed += 1;
+            }
+        }
+        assert!(missed < n / 1000, "{} sampled directions miss", missed);
+        let estimate = estimate / n as f64;
+        assert!((estimate - solid_angle).abs() < 0.05 * solid_angle, "{} against {}", estimate, solid_angle);
+    }
+
+    #[test]
+    fn solids_are_lights() {
+        let origin = Vec3::new(0.5, 1.0, 3.0);
+        let tube = cylinder::new(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.3, 1.0, -0.2), 0.6, light());
+        let spike = cone::new(Vec3::new(0.0, -0.5, 0.0), Vec3::new(0.2, 1.0, 0.1), 0.8, light());
+        let ring = torus::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.2, 1.0, 0.4), 1.0, 0.3, light());
+        for object in [&tube as &dyn hittable, &spike, &ring] {
+            assert!(object.light_bounds().is_some());
+            assert_sampling_consistent(object, origin);
+        }
+        assert!((tube.area() - (2.0 * PI * 0.6 * tube.height + 2.0 * PI * 0.36)).abs() < 1e-9);
+        assert!((ring.area() - 4.0 * PI * PI * 0.3).abs() < 1e-9);
+    }
+}
\ No newline at end of file
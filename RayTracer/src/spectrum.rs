@@ -0,0 +1,208 @@
+use crate::vec3::Vec3;
+use crate::ray::Ray;
+use crate::color::xyz_to_linear_srgb;
+
+//Spectral rendering: in spectral mode a path carries radiance at three wavelengths in the x, y and z of
+//its Vec3 colors instead of red, green and blue; every RGB albedo and emitter is uplifted to a spectrum
+//and evaluated at those wavelengths, and the film turns them into XYZ and then sRGB
+
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+//integral of the y matching function, Y of a constant spectrum of 1 is 1 after dividing by it
+const CIE_Y_INTEGRAL: f64 = 106.856895;
+
+//The wavelengths (nm) of one path: a hero wavelength and two rotations of it, stratified over the spectrum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; 3],
+    pub pdf: [f64; 3],
+    //set once something wavelength dependent (dispersion) has happened, only the hero is followed from there
+    pub secondary_terminated: bool,
+}
+
+impl SampledWavelengths {
+    //importance samples the visible range where the eye is most sensitive (PBRT's SampleVisibleWavelengths)
+    pub fn sample_visible(u: f64) -> Self {
+        let mut lambda = [0.0; 3];
+        let mut pdf = [0.0; 3];
+        for i in 0..3 {
+            let mut up = u + i as f64 / 3.0;
+            if up > 1.0 {
+                up -= 1.0;
+            }
+            lambda[i] = 538.0 - 138.888889 * (0.85691062 - 1.82750197 * up).atanh();
+            pdf[i] = visible_wavelengths_pdf(lambda[i]);
+        }
+        Self {
+            lambda,
+            pdf,
+            secondary_terminated: false,
+        }
+    }
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+    pub fn terminate_secondary(&mut self) {
+        self.secondary_terminated = true;
+    }
+    //RGB reflectance or emission as spectral values at these wavelengths
+    pub fn uplift(&self, rgb: Vec3) -> Vec3 {
+        Vec3::new(rgb_to_spectrum(rgb, self.lambda[0]), rgb_to_spectrum(rgb, self.lambda[1]), rgb_to_spectrum(rgb, self.lambda[2]))
+    }
+    //XYZ of the spectral radiance l of one path, averaged over the wavelengths; terminated secondaries
+    //count the hero three times, the path throughput already carries that factor
+    pub fn to_xyz(&self, l: Vec3) -> Vec3 {
+        let mut xyz = Vec3::zero();
+        for i in 0..3 {
+            if self.pdf[i] <= 0.0 {
+                continue;
+            }
+            xyz += cie_xyz(self.lambda[i]) * (l.lp(i as u8) / self.pdf[i]);
+        }
+        xyz / (3.0 * CIE_Y_INTEGRAL)
+    }
+}
+
+fn visible_wavelengths_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.0;
+    }
+    let c = (0.0072 * (lambda - 538.0)).cosh();
+    0.0039398042 / (c * c)
+}
+
+//the uplift is applied to whatever RGB the path meets, RGB rays pass through unchanged
+pub fn for_ray(rgb: Vec3, r: &Ray) -> Vec3 {
+    match &r.wavelengths {
+        Some(wavelengths) => wavelengths.uplift(rgb),
+        None => rgb,
+    }
+}
+
+//CIE 1931 color matching functions, the multi-lobe fit of Wyman, Sloan and Shirley (2013)
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    let g = |mu: f64, sigma1: f64, sigma2: f64| -> f64 {
+        let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+//Smits' basis spectra (1999, "An RGB-to-Spectrum Conversion for Reflectances"), 10 bins over 380-720 nm
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+//linear between bin centers, constant beyond the first and last one
+fn smits_basis(table: &[f64; 10], lambda: f64) -> f64 {
+    let x = ((lambda - 380.0) / 34.0 - 0.5).clamp(0.0, 9.0);
+    let i = (x.floor() as usize).min(8);
+    let t = x - i as f64;
+    table[i] * (1.0 - t) + table[i + 1] * t
+}
+
+//white takes the smallest component, then the secondary and primary colors the rest
+pub fn rgb_to_spectrum(rgb: Vec3, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+    let s = |table: &[f64; 10]| smits_basis(table, lambda);
+    if r <= g && r <= b {
+        if g <= b {
+            r * s(&SMITS_WHITE) + (g - r) * s(&SMITS_CYAN) + (b - g) * s(&SMITS_BLUE)
+        } else {
+            r * s(&SMITS_WHITE) + (b - r) * s(&SMITS_CYAN) + (g - b) * s(&SMITS_GREEN)
+        }
+    } else if g <= r && g <= b {
+        if r <= b {
+            g * s(&SMITS_WHITE) + (r - g) * s(&SMITS_MAGENTA) + (b - r) * s(&SMITS_BLUE)
+        } else {
+            g * s(&SMITS_WHITE) + (b - g) * s(&SMITS_MAGENTA) + (r - b) * s(&SMITS_RED)
+        }
+    } else if r <= g {
+        b * s(&SMITS_WHITE) + (r - b) * s(&SMITS_YELLOW) + (g - r) * s(&SMITS_GREEN)
+    } else {
+        b * s(&SMITS_WHITE) + (g - b) * s(&SMITS_YELLOW) + (r - g) * s(&SMITS_RED)
+    }
+}
+
+//linear sRGB of a pixel's average XYZ, white balanced so that a constant spectrum (white light on a white
+//surface) comes out white like it does in RGB mode
+pub fn xyz_to_film_rgb(xyz: Vec3) -> Vec3 {
+    let rgb = xyz_to_linear_srgb(xyz);
+    let white = xyz_to_linear_srgb(Vec3::new(1.0, 1.0, 1.0));
+    Vec3::new(rgb.x / white.x, rgb.y / white.y, rgb.z / white.z)
+}
+
+//Index of refraction that changes with the wavelength, which splits white light into colors
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    //n = a + b / lambda^2, lambda in micrometers
+    Cauchy { a: f64, b: f64 },
+    //n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn ior(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1e-3) * (lambda * 1e-3);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let mut n2 = 1.0;
+                for i in 0..3 {
+                    n2 += b[i] * l2 / (l2 - c[i]);
+                }
+                n2.sqrt()
+            }
+        }
+    }
+    //at the sodium D line, the index quoted for glasses and used in RGB mode
+    pub fn ior_d(&self) -> f64 {
+        self.ior(589.3)
+    }
+    //crown glass, window and lens glass
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+    //dense flint glass, strongly dispersive, good for prisms
+    pub fn sf11() -> Self {
+        Dispersion::Sellmeier { b: [1.73759695, 0.313747346, 1.89878101], c: [0.013188707, 0.0623068142, 155.23629] }
+    }
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+}
+
+//Planck's law: spectral radiance of a blackbody at `kelvin`, lambda in nm
+pub fn blackbody(lambda: f64, kelvin: f64) -> f64 {
+    if kelvin <= 0.0 {
+        return 0.0;
+    }
+    let c = 299792458.0;
+    let h = 6.62606957e-34;
+    let kb = 1.3806488e-23;
+    let l = lambda * 1e-9;
+    (2.0 * h * c * c) / (l.powi(5) * (((h * c) / (l * kb * kelvin)).exp() - 1.0))
+}
+
+//linear sRGB color of a blackbody at `kelvin` with a luminance of 1: about 1900 K for candles,
+//2700 K for incandescent bulbs, 5800 K for the sun, and bluish above 6500 K
+pub fn blackbody_color(kelvin: f64) -> Vec3 {
+    let mut xyz = Vec3::zero();
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        xyz += cie_xyz(lambda) * blackbody(lambda, kelvin);
+        lambda += 1.0;
+    }
+    if xyz.y <= 0.0 {
+        return Vec3::zero();
+    }
+    xyz_to_linear_srgb(xyz / xyz.y)
+}
\ No newline at end of file
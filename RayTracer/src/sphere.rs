@@ -1,11 +1,14 @@
 pub use crate::ray::Ray;
 use crate::Vec3;
 pub use crate::util::{fmax};
+use crate::util;
 pub use crate::hittables::{hit_record, hittable};
 use crate::materials::{material};
 use crate::Interval;
 use std::sync::Arc;
 use crate::aabb::AABB;
+use crate::light_bvh::LightBounds;
+use crate::color;
 
 pub struct Sphere {
     pub center: Vec3,
@@ -66,6 +69,18 @@ impl Sphere {
         *u = phi / (2.0 * std::f64::consts::PI);
         *v = theta / std::f64::consts::PI;
     }
+    //derivatives of the hit point along u and v, p is the point on the unit sphere
+    fn get_sphere_tangents(&self, p: Vec3, dpdu: &mut Vec3, dpdv: &mut Vec3) {
+        let pi = std::f64::consts::PI;
+        *dpdu = Vec3::new(p.z, 0.0, -p.x) * (2.0 * pi * self.radius);
+        let sin_theta = (1.0 - p.y * p.y).max(0.0).sqrt();
+        if sin_theta < 1e-8 {
+            //at the poles v runs along any meridian, pick the one for u = 0
+            *dpdv = Vec3::new(pi * self.radius, 0.0, 0.0);
+            return;
+        }
+        *dpdv = Vec3::new(-p.x * p.y / sin_theta, sin_theta, -p.z * p.y / sin_theta) * (pi * self.radius);
+    }
 }
 
 impl hittable for Sphere {
@@ -81,26 +96,70 @@ impl hittable for Sphere {
             return false;
         }
         
-        let mut root = (h - discriminant.sqrt()) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + discriminant.sqrt()) / a;
+        //Try the near root first, then the far one if the near one is out of range or cut out
+        let sqrtd = discriminant.sqrt();
+        for root in [(h - sqrtd) / a, (h + sqrtd) / a] {
             if !ray_t.surrounds(root) {
-                return false;
+                continue;
+            }
+            let p = r.at(root);
+            let outward_normal = (p - center) / self.radius;
+            let (mut u, mut v) = (0.0, 0.0);
+            Self::get_sphere_uv(outward_normal, &mut u, &mut v);
+            if !self.mat.alpha_test(u, v, &p) {
+                continue;
             }
-        }
 
-        rec.t = root;
-        rec.p = r.at(root);
-        let outward_normal = (rec.p - center) / self.radius;
-        rec.set_face_normal(r, &outward_normal);
-        Self::get_sphere_uv(outward_normal, &mut rec.u, &mut rec.v);
-        //dyn type can not be cloned, we use refrence count Arc to clone it
-        rec.mat = Arc::clone(&self.mat);
+            rec.t = root;
+            rec.p = p;
+            rec.set_face_normal(r, &outward_normal);
+            rec.u = u;
+            rec.v = v;
+            self.get_sphere_tangents(outward_normal, &mut rec.dpdu, &mut rec.dpdv);
+            //dyn type can not be cloned, we use refrence count Arc to clone it
+            rec.mat = Arc::clone(&self.mat);
 
-        true
+            return true;
+        }
+
+        false
     }
 
     fn bbox(&self) -> &AABB {
         &self.bbox
     }
+    //uniform over the cone of directions that hit the sphere, only for stationary spheres
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f64 {
+        let mut rec = hit_record::new();
+        if !self.hit(&Ray::new(*origin, *direction, 0.0), &Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+        let distance_squared = (self.center - *origin).squared_length();
+        if distance_squared <= self.radius * self.radius {
+            //inside the sphere every direction hits it
+            return 1.0 / (4.0 * std::f64::consts::PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * std::f64::consts::PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+    fn random(&self, origin: &Vec3) -> Vec3 {
+        let direction = self.center - *origin;
+        let distance_squared = direction.squared_length();
+        if distance_squared <= self.radius * self.radius {
+            return util::random_on_unit_sphere();
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        util::random_in_cone(direction.unit_vector(), cos_theta_max)
+    }
+    fn area(&self) -> f64 {
+        4.0 * std::f64::consts::PI * self.radius * self.radius
+    }
+    fn light_bounds(&self) -> Option<LightBounds> {
+        let power = color::luminance(self.mat.emitted(0.5, 0.5, &self.center)) * self.area();
+        if power <= 0.0 {
+            return None;
+        }
+        Some(LightBounds::new_omni(&self.bbox, power))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,332 @@
+use crate::vec3::Vec3;
+use crate::color;
+use crate::textures::{texture, SolidColor, UvDifferentials};
+use crate::procedural::ColorRamp;
+use crate::hittables::hit_record;
+use std::sync::Arc;
+
+//Texture nodes: textures made from other textures, so a scene can wire up something like dirt blended
+//into grass by noise without a new texture type
+//Every node passes value_at() on to its inputs, image textures below them keep their filtering,
+//and alpha() too, a cutout image stays a cutout inside a graph
+
+fn mul(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::new(a.x * b.x, a.y * b.y, a.z * b.z)
+}
+
+fn lerp(a: Vec3, b: Vec3, t: Vec3) -> Vec3 {
+    Vec3::new(
+        a.x + (b.x - a.x) * t.x,
+        a.y + (b.y - a.y) * t.y,
+        a.z + (b.z - a.z) * t.z,
+    )
+}
+
+//a where the mask is 0, b where it is 1, per channel so a gray mask blends all three alike
+pub struct Mix {
+    pub a: Arc<dyn texture + Send + Sync>,
+    pub b: Arc<dyn texture + Send + Sync>,
+    pub mask: Arc<dyn texture + Send + Sync>,
+}
+
+impl Mix {
+    pub fn new(a: Arc<dyn texture + Send + Sync>, b: Arc<dyn texture + Send + Sync>, mask: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            a,
+            b,
+            mask,
+        }
+    }
+}
+
+impl texture for Mix {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        lerp(self.a.value(u, v, p), self.b.value(u, v, p), self.mask.value(u, v, p))
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        lerp(self.a.value_at(rec), self.b.value_at(rec), self.mask.value_at(rec))
+    }
+    //blended by the mask's gray value
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let m = self.mask.value(u, v, p);
+        let t = ((m.x + m.y + m.z) / 3.0).clamp(0.0, 1.0);
+        self.a.alpha(u, v, p) * (1.0 - t) + self.b.alpha(u, v, p) * t
+    }
+}
+
+//Product of two textures, e.g. a color tinted by a dirt map
+pub struct Multiply {
+    pub a: Arc<dyn texture + Send + Sync>,
+    pub b: Arc<dyn texture + Send + Sync>,
+}
+
+impl Multiply {
+    pub fn new(a: Arc<dyn texture + Send + Sync>, b: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            a,
+            b,
+        }
+    }
+}
+
+impl texture for Multiply {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        mul(self.a.value(u, v, p), self.b.value(u, v, p))
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        mul(self.a.value_at(rec), self.b.value_at(rec))
+    }
+    //a hole in either input is a hole in the product
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        self.a.alpha(u, v, p) * self.b.alpha(u, v, p)
+    }
+}
+
+//Sum of two textures
+pub struct Add {
+    pub a: Arc<dyn texture + Send + Sync>,
+    pub b: Arc<dyn texture + Send + Sync>,
+}
+
+impl Add {
+    pub fn new(a: Arc<dyn texture + Send + Sync>, b: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            a,
+            b,
+        }
+    }
+}
+
+impl texture for Add {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.a.value(u, v, p) + self.b.value(u, v, p)
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        self.a.value_at(rec) + self.b.value_at(rec)
+    }
+    //`b` is added on top of `a`, the shape comes from `a`
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        self.a.alpha(u, v, p)
+    }
+}
+
+//1 - value, turns a mask around
+pub struct Invert {
+    pub tex: Arc<dyn texture + Send + Sync>,
+}
+
+impl Invert {
+    pub fn new(tex: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            tex,
+        }
+    }
+}
+
+impl texture for Invert {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0) - self.tex.value(u, v, p)
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        Vec3::new(1.0, 1.0, 1.0) - self.tex.value_at(rec)
+    }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        self.tex.alpha(u, v, p)
+    }
+}
+
+//Colors the luminance of another texture, e.g. a noise remapped to a sharp mask or a palette
+pub struct Ramp {
+    pub tex: Arc<dyn texture + Send + Sync>,
+    pub ramp: ColorRamp,
+}
+
+impl Ramp {
+    pub fn new(tex: Arc<dyn texture + Send + Sync>, ramp: ColorRamp) -> Self {
+        Self {
+            tex,
+            ramp,
+        }
+    }
+}
+
+impl texture for Ramp {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.ramp.value(color::luminance(self.tex.value(u, v, p)))
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        self.ramp.value(color::luminance(self.tex.value_at(rec)))
+    }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        self.tex.alpha(u, v, p)
+    }
+}
+
+//Scales, rotates (degrees) and then offsets the (u, v) another texture is looked up at,
+//for tiling an image or turning a pattern
+pub struct UvTransform {
+    pub tex: Arc<dyn texture + Send + Sync>,
+    //the linear part as a 2x2 matrix, rows for u and v
+    m: [[f64; 2]; 2],
+    pub offset: (f64, f64),
+}
+
+impl UvTransform {
+    pub fn new(tex: Arc<dyn texture + Send + Sync>, scale: (f64, f64), rotation: f64, offset: (f64, f64)) -> Self {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        Self {
+            tex,
+            m: [[cos * scale.0, -sin * scale.1], [sin * scale.0, cos * scale.1]],
+            offset,
+        }
+    }
+    //the same image repeated `times` in both directions
+    pub fn new_tiled(tex: Arc<dyn texture + Send + Sync>, times: f64) -> Self {
+        Self::new(tex, (times, times), 0.0, (0.0, 0.0))
+    }
+    fn apply(&self, u: f64, v: f64) -> (f64, f64) {
+        (
+            self.m[0][0] * u + self.m[0][1] * v + self.offset.0,
+            self.m[1][0] * u + self.m[1][1] * v + self.offset.1,
+        )
+    }
+}
+
+impl texture for UvTransform {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        let (u, v) = self.apply(u, v);
+        self.tex.value(u, v, p)
+    }
+    //the pixel footprint goes through the same matrix, a tiled image still gets the right mip level
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        let mut rec = rec.clone();
+        (rec.u, rec.v) = self.apply(rec.u, rec.v);
+        let d = rec.uv_diff;
+        rec.uv_diff = UvDifferentials {
+            dudx: self.m[0][0] * d.dudx + self.m[0][1] * d.dvdx,
+            dvdx: self.m[1][0] * d.dudx + self.m[1][1] * d.dvdx,
+            dudy: self.m[0][0] * d.dudy + self.m[0][1] * d.dvdy,
+            dvdy: self.m[1][0] * d.dudy + self.m[1][1] * d.dvdy,
+        };
+        self.tex.value_at(&rec)
+    }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let (u, v) = self.apply(u, v);
+        self.tex.alpha(u, v, p)
+    }
+}
+
+//Projects a texture onto the object from the three axes and blends by the normal, for objects
+//without usable uv (boxes, voxels, meshes) where the image would otherwise smear
+pub struct Triplanar {
+    pub tex: Arc<dyn texture + Send + Sync>,
+    //texture repeats per unit length
+    pub scale: f64,
+    //higher values make the seams between the projections narrower
+    pub sharpness: f64,
+}
+
+impl Triplanar {
+    pub fn new(tex: Arc<dyn texture + Send + Sync>, scale: f64) -> Self {
+        Self {
+            tex,
+            scale,
+            sharpness: 4.0,
+        }
+    }
+}
+
+impl texture for Triplanar {
+    //without a normal only the projection from above is left
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        self.tex.value(p.x * self.scale, p.z * self.scale, p)
+    }
+    //each projection is looked up like a planar mapped surface, with the pixel footprint projected too,
+    //so images keep their mip and EWA filtering
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        let n = rec.normal;
+        let w = Vec3::new(n.x.abs().powf(self.sharpness), n.y.abs().powf(self.sharpness), n.z.abs().powf(self.sharpness));
+        let total = w.x + w.y + w.z;
+        if total <= 0.0 {
+            return self.value(rec.u, rec.v, &rec.p);
+        }
+        //the footprint on the surface, back from the uv differentials (none on surfaces without uv)
+        let d = rec.uv_diff;
+        let dpdx = rec.dpdu * d.dudx + rec.dpdv * d.dvdx;
+        let dpdy = rec.dpdu * d.dudy + rec.dpdv * d.dvdy;
+        let (x, y, z) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut sum = Vec3::zero();
+        for (weight, u_axis, v_axis) in [(w.x, z, y), (w.y, x, z), (w.z, x, y)] {
+            if weight / total < 1e-3 {
+                continue;
+            }
+            let mut projected = rec.clone();
+            projected.u = (rec.p * u_axis) * self.scale;
+            projected.v = (rec.p * v_axis) * self.scale;
+            projected.dpdu = u_axis / self.scale;
+            projected.dpdv = v_axis / self.scale;
+            projected.uv_diff = UvDifferentials {
+                dudx: (dpdx * u_axis) * self.scale,
+                dvdx: (dpdx * v_axis) * self.scale,
+                dudy: (dpdy * u_axis) * self.scale,
+                dvdy: (dpdy * v_axis) * self.scale,
+            };
+            sum += self.tex.value_at(&projected) * weight;
+        }
+        sum / total
+    }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        self.tex.alpha(p.x * self.scale, p.z * self.scale, p)
+    }
+}
+
+//Checkerboard in texture space, it follows the surface's (u, v) where Checker cuts through space
+pub struct UvChecker {
+    pub u_count: f64,
+    pub v_count: f64,
+    pub even: Arc<dyn texture + Send + Sync>,
+    pub odd: Arc<dyn texture + Send + Sync>,
+}
+
+impl UvChecker {
+    pub fn new(u_count: f64, v_count: f64, even: Arc<dyn texture + Send + Sync>, odd: Arc<dyn texture + Send + Sync>) -> Self {
+        Self {
+            u_count,
+            v_count,
+            even,
+            odd,
+        }
+    }
+    pub fn new_from_color(u_count: f64, v_count: f64, color1: Vec3, color2: Vec3) -> Self {
+        Self::new(u_count, v_count, Arc::new(SolidColor::new(color1)), Arc::new(SolidColor::new(color2)))
+    }
+    fn is_even(&self, u: f64, v: f64) -> bool {
+        let i = (u * self.u_count).floor() as i64;
+        let j = (v * self.v_count).floor() as i64;
+        (i + j).rem_euclid(2) == 0
+    }
+}
+
+impl texture for UvChecker {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
+        if self.is_even(u, v) {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        if self.is_even(rec.u, rec.v) {
+            self.even.value_at(rec)
+        } else {
+            self.odd.value_at(rec)
+        }
+    }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        if self.is_even(u, v) {
+            self.even.alpha(u, v, p)
+        } else {
+            self.odd.alpha(u, v, p)
+        }
+    }
+}
\ No newline at end of file
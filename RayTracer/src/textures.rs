@@ -1,11 +1,38 @@
 use crate::vec3::Vec3;
-use std::sync::Arc;
-use opencv::core::{MatTraitConst, VecN};
-use opencv::imgcodecs::{imread, IMREAD_COLOR};
-use crate::perlins::perlin;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use opencv::core::{MatTraitConst, VecN, CV_8U, CV_32F};
+use opencv::imgcodecs::{imread, IMREAD_COLOR, IMREAD_UNCHANGED};
+use crate::perlins::{perlin, noise_source};
+use crate::util;
+use crate::hittables::hit_record;
 
 pub trait texture : Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3;
+    //lookup at a hit point, textures that filter over the pixel footprint also use rec.uv_diff
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        self.value(rec.u, rec.v, &rec.p)
+    }
+    //coverage in [0, 1], only image textures with an alpha channel are ever see-through
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        1.0
+    }
+}
+
+//change of the texture coordinates from one pixel to the next
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UvDifferentials {
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
+}
+
+impl UvDifferentials {
+    pub fn is_zero(&self) -> bool {
+        self.dudx == 0.0 && self.dvdx == 0.0 && self.dudy == 0.0 && self.dvdy == 0.0
+    }
 }
 pub struct SolidColor {
     albedo: Vec3,
@@ -17,6 +44,12 @@ impl SolidColor {
             albedo,
         }
     }
+    //the same value in every channel, for scalar material parameters
+    pub fn new_gray(value: f64) -> Self {
+        Self {
+            albedo: Vec3::new(value, value, value),
+        }
+    }
 }
 
 impl texture for SolidColor {
@@ -60,41 +93,526 @@ impl texture for Checker {
             self.odd.value(u, v, p)
         }
     }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        let x_integer = (self.inv_scale * rec.p.x()).floor() as i32;
+        let y_integer = (self.inv_scale * rec.p.y()).floor() as i32;
+        let z_integer = (self.inv_scale * rec.p.z()).floor() as i32;
+        if (x_integer + y_integer + z_integer) % 2 == 0 {
+            self.even.value_at(rec)
+        } else {
+            self.odd.value_at(rec)
+        }
+    }
+}
+
+//one level of a mip pyramid, colors in [0, 1], row by row from the top of the picture
+pub struct MipLevel {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3>,
+    //one alpha per pixel, all 1.0 for images without an alpha channel
+    pub alphas: Vec<f64>,
+}
+
+impl MipLevel {
+    pub fn texel(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+    pub fn texel_alpha(&self, x: u32, y: u32) -> f64 {
+        self.alphas[(y * self.width + x) as usize]
+    }
+    //2x2 box filter, odd sizes reuse the last row/column
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut alphas = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let x0 = (2 * x).min(self.width - 1);
+                let x1 = (2 * x + 1).min(self.width - 1);
+                let y0 = (2 * y).min(self.height - 1);
+                let y1 = (2 * y + 1).min(self.height - 1);
+                let sum = self.texel(x0, y0) + self.texel(x1, y0) + self.texel(x0, y1) + self.texel(x1, y1);
+                pixels.push(sum * 0.25);
+                let alpha_sum = self.texel_alpha(x0, y0) + self.texel_alpha(x1, y0) + self.texel_alpha(x0, y1) + self.texel_alpha(x1, y1);
+                alphas.push(alpha_sum * 0.25);
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+            alphas,
+        }
+    }
+}
+
+//decoded texels of an image file, shared by every Image that uses the file
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    //levels[0] is the image itself, every next level is half as large, down to 1x1
+    pub levels: Vec<MipLevel>,
+    //true if some pixel is not fully opaque
+    pub has_alpha: bool,
+    //float images (.hdr, .exr) hold linear values, 8 bit images are gamma encoded
+    pub linear: bool,
+}
+
+impl ImageData {
+    pub fn empty() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            levels: Vec::new(),
+            has_alpha: false,
+            linear: false,
+        }
+    }
+    pub fn load(path: &Path) -> Self {
+        let filename = path.to_string_lossy();
+        let mut img_data = imread(&filename, IMREAD_UNCHANGED).expect("Image reading error!");
+        let float = img_data.depth() == CV_32F && img_data.channels() == 3;
+        if !float && (img_data.depth() != CV_8U || (img_data.channels() != 3 && img_data.channels() != 4)) {
+            //16 bit and gray images: let opencv convert them to 8 bit BGR
+            img_data = imread(&filename, IMREAD_COLOR).expect("Image reading error!");
+        }
+        let width = img_data.cols() as u32;
+        let height = img_data.rows() as u32;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut alphas = Vec::with_capacity((width * height) as usize);
+        for row in 0..height as i32 {
+            for col in 0..width as i32 {
+                //opencv stores BGR / BGRA
+                if float {
+                    let color: &VecN<f32, 3> = img_data.at_2d(row, col).unwrap();
+                    pixels.push(Vec3::new(color[2] as f64, color[1] as f64, color[0] as f64));
+                    alphas.push(1.0);
+                } else if img_data.channels() == 4 {
+                    let color: &VecN<u8, 4> = img_data.at_2d(row, col).unwrap();
+                    pixels.push(Vec3::new(color[2] as f64, color[1] as f64, color[0] as f64) * (1.0 / 255.0));
+                    alphas.push(color[3] as f64 / 255.0);
+                } else {
+                    let color: &VecN<u8, 3> = img_data.at_2d(row, col).unwrap();
+                    pixels.push(Vec3::new(color[2] as f64, color[1] as f64, color[0] as f64) * (1.0 / 255.0));
+                    alphas.push(1.0);
+                }
+            }
+        }
+        let mut data = Self::new_from_pixels_alpha(width, height, pixels, alphas);
+        data.linear = float;
+        data
+    }
+    //builds the mip pyramid of an opaque image
+    pub fn new_from_pixels(width: u32, height: u32, pixels: Vec<Vec3>) -> Self {
+        let alphas = vec![1.0; pixels.len()];
+        Self::new_from_pixels_alpha(width, height, pixels, alphas)
+    }
+    pub fn new_from_pixels_alpha(width: u32, height: u32, pixels: Vec<Vec3>, alphas: Vec<f64>) -> Self {
+        if width == 0 || height == 0 {
+            return Self::empty();
+        }
+        let has_alpha = alphas.iter().any(|a| *a < 1.0);
+        let mut levels = vec![MipLevel { width, height, pixels, alphas }];
+        while levels[levels.len() - 1].width > 1 || levels[levels.len() - 1].height > 1 {
+            let next = levels[levels.len() - 1].downsample();
+            levels.push(next);
+        }
+        Self {
+            width,
+            height,
+            levels,
+            has_alpha,
+            linear: false,
+        }
+    }
+    pub fn texel(&self, x: u32, y: u32) -> Vec3 {
+        self.levels[0].texel(x, y)
+    }
+}
+
+//every image file is decoded once and shared through an Arc
+//files are looked up in the search paths in order, "./mytexture/" by default
+pub struct TextureRegistry {
+    search_paths: Vec<PathBuf>,
+    images: HashMap<PathBuf, Arc<ImageData>>,
+}
+
+static TEXTURE_REGISTRY: OnceLock<Mutex<TextureRegistry>> = OnceLock::new();
+
+impl TextureRegistry {
+    fn global() -> MutexGuard<'static, TextureRegistry> {
+        TEXTURE_REGISTRY.get_or_init(|| Mutex::new(TextureRegistry {
+            search_paths: vec![PathBuf::from("./mytexture/")],
+            images: HashMap::new(),
+        })).lock().unwrap()
+    }
+    pub fn add_search_path(path: &str) {
+        Self::global().search_paths.push(PathBuf::from(path));
+    }
+    pub fn set_search_paths(paths: &[&str]) {
+        Self::global().search_paths = paths.iter().map(PathBuf::from).collect();
+    }
+    //full path of a texture file, None if it is in none of the search paths
+    pub fn resolve(filename: &str) -> Option<PathBuf> {
+        let registry = Self::global();
+        registry.find(filename)
+    }
+    pub fn load(filename: &str) -> Arc<ImageData> {
+        let path = {
+            let mut registry = Self::global();
+            let path = registry.find(filename).unwrap_or_else(|| PathBuf::from(filename));
+            if let Some(data) = registry.images.get(&path) {
+                return Arc::clone(data);
+            }
+            //a missing file is cached as an empty image, so it is reported only once
+            if !path.exists() {
+                eprintln!("Texture \"{}\" not found", filename);
+                let data = Arc::new(ImageData::empty());
+                registry.images.insert(path, Arc::clone(&data));
+                return data;
+            }
+            path
+        };
+        //decoding and building the mip pyramid happen without the lock, textures load in parallel
+        let data = Arc::new(ImageData::load(&path));
+        //another thread may have loaded the same file meanwhile, everyone shares the first one
+        Arc::clone(Self::global().images.entry(path).or_insert(data))
+    }
+    //drop every cached image, e.g. between scenes
+    pub fn clear() {
+        Self::global().images.clear();
+    }
+
+    fn find(&self, filename: &str) -> Option<PathBuf> {
+        let direct = Path::new(filename);
+        if direct.is_absolute() {
+            return if direct.exists() { Some(direct.to_path_buf()) } else { None };
+        }
+        self.search_paths.iter().map(|dir| dir.join(filename)).find(|path| path.exists())
+    }
+}
+
+//how texels are interpolated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    //Catmull-Rom over 4x4 texels
+    Bicubic,
 }
 
+//what happens to uv outside of [0, 1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp,
+    //a constant color outside of the image
+    Border(Vec3),
+}
+
+impl WrapMode {
+    //maps a texel index into [0, n), None means the border color
+    fn wrap(&self, i: i64, n: i64) -> Option<i64> {
+        match self {
+            WrapMode::Repeat => Some(i.rem_euclid(n)),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                Some(if m >= n { 2 * n - 1 - m } else { m })
+            }
+            WrapMode::Clamp => Some(i.clamp(0, n - 1)),
+            WrapMode::Border(_) => if i < 0 || i >= n { None } else { Some(i) },
+        }
+    }
+}
+
+//how minified lookups are filtered
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MipMode {
+    //always sample the full resolution image
+    None,
+    //bilinear on the two nearest mip levels
+    Trilinear,
+    //elliptical weighted average, sharper at grazing angles
+    Ewa,
+}
+
+const MAX_ANISOTROPY: f64 = 8.0;
+
 pub struct Image {
-    pub img_data: opencv::core::Mat,
+    pub data: Arc<ImageData>,
     width: u32,
     height: u32,
-}
 
-unsafe impl Send for Image {}
-unsafe impl Sync for Image {}
+    //sampling
+    pub filter: FilterMode,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode,
+
+    //uv transform, applied as uv * scale + offset before the lookup
+    pub uv_scale: (f64, f64),
+    pub uv_offset: (f64, f64),
+
+    //minification filter, used when the hit record has ray differentials
+    pub mip_mode: MipMode,
+}
 
 impl Image {
     pub fn new(filename: &str) -> Self {
-        let img_data = imread(&("./mytexture/".to_owned() + filename), IMREAD_COLOR).expect("Image reading error!");
-        let width = img_data.cols() as u32;
-        let height = img_data.rows() as u32;
+        Self::new_from_data(TextureRegistry::load(filename))
+    }
+    pub fn new_from_data(data: Arc<ImageData>) -> Self {
+        let width = data.width;
+        let height = data.height;
         Self {
-            img_data,
+            data,
             width,
             height,
+
+            filter: FilterMode::Nearest,
+            wrap_u: WrapMode::Clamp,
+            wrap_v: WrapMode::Clamp,
+
+            uv_scale: (1.0, 1.0),
+            uv_offset: (0.0, 0.0),
+
+            mip_mode: MipMode::Trilinear,
         }
     }
-    pub fn get_color(&self, mut u: f64, mut v: f64) -> Vec3 {
+    //repeat the image repeats_u x repeats_v times over the surface, bilinear filtered
+    pub fn new_tiled(filename: &str, repeats_u: f64, repeats_v: f64) -> Self {
+        let mut img = Self::new(filename);
+        img.filter = FilterMode::Bilinear;
+        img.wrap_u = WrapMode::Repeat;
+        img.wrap_v = WrapMode::Repeat;
+        img.uv_scale = (repeats_u, repeats_v);
+        img
+    }
+
+    //texel of a mip level with wrapping, (x, y) counted from the top left corner
+    fn fetch(&self, level: &MipLevel, x: i64, y: i64) -> Vec3 {
+        let x = match self.wrap_u.wrap(x, level.width as i64) {
+            Some(x) => x,
+            None => return Self::border_color(self.wrap_u),
+        };
+        let y = match self.wrap_v.wrap(y, level.height as i64) {
+            Some(y) => y,
+            None => return Self::border_color(self.wrap_v),
+        };
+        level.texel(x as u32, y as u32)
+    }
+    fn fetch_alpha(&self, level: &MipLevel, x: i64, y: i64) -> f64 {
+        //outside of a bordered image there is nothing to hit
+        let x = match self.wrap_u.wrap(x, level.width as i64) {
+            Some(x) => x,
+            None => return 0.0,
+        };
+        let y = match self.wrap_v.wrap(y, level.height as i64) {
+            Some(y) => y,
+            None => return 0.0,
+        };
+        level.texel_alpha(x as u32, y as u32)
+    }
+    fn border_color(wrap: WrapMode) -> Vec3 {
+        match wrap {
+            WrapMode::Border(color) => color,
+            _ => Vec3::zero(),
+        }
+    }
+    //Catmull-Rom weights for the 4 texels around a sample at fraction t
+    fn cubic_weights(t: f64) -> [f64; 4] {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        [
+            0.5 * (-t3 + 2.0 * t2 - t),
+            0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+            0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+            0.5 * (t3 - t2),
+        ]
+    }
+
+    pub fn get_color(&self, u: f64, v: f64) -> Vec3 {
         // println!("u: {}, v: {}", u, v);
-        if u <= 0.0 { u = 0.001; }
-        if u >= 1.0 { u = 0.999; }
-        if v <= 0.0 { v = 0.001; }
-        if v >= 1.0 { v = 0.999; }
+        let u = u * self.uv_scale.0 + self.uv_offset.0;
+        let v = v * self.uv_scale.1 + self.uv_offset.1;
+        self.sample_level(0, u, v, self.filter)
+    }
 
-        let u_img = u * self.width as f64;
-        let v_img = (1.0 - v) * self.height as f64;
-        let color: &VecN<u8, 3> = self.img_data.at_2d(v_img as i32, u_img as i32).unwrap();
-        // println!("color: {:?}", color);
+    //alpha from the full resolution image, bicubic falls back to bilinear
+    //cutouts are not mip filtered, averaged alpha would make foliage fade away in the distance
+    pub fn get_alpha(&self, u: f64, v: f64) -> f64 {
+        if !self.data.has_alpha {
+            return 1.0;
+        }
+        let level = &self.data.levels[0];
+        let u = u * self.uv_scale.0 + self.uv_offset.0;
+        let v = v * self.uv_scale.1 + self.uv_offset.1;
+        let u_img = u * level.width as f64;
+        let v_img = (1.0 - v) * level.height as f64;
+        match self.filter {
+            FilterMode::Nearest => self.fetch_alpha(level, u_img.floor() as i64, v_img.floor() as i64),
+            FilterMode::Bilinear | FilterMode::Bicubic => {
+                let x = u_img - 0.5;
+                let y = v_img - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                self.fetch_alpha(level, x0, y0) * ((1.0 - fx) * (1.0 - fy))
+                    + self.fetch_alpha(level, x0 + 1, y0) * (fx * (1.0 - fy))
+                    + self.fetch_alpha(level, x0, y0 + 1) * ((1.0 - fx) * fy)
+                    + self.fetch_alpha(level, x0 + 1, y0 + 1) * (fx * fy)
+            }
+        }
+    }
 
-        Vec3::new(color[2] as f64, color[1] as f64, color[0] as f64) * (1.0 / 255.0)
+    //filtered lookup over the pixel footprint given by the uv differentials
+    pub fn get_color_filtered(&self, u: f64, v: f64, diff: &UvDifferentials) -> Vec3 {
+        if diff.is_zero() {
+            return self.get_color(u, v);
+        }
+        let u = u * self.uv_scale.0 + self.uv_offset.0;
+        let v = v * self.uv_scale.1 + self.uv_offset.1;
+        let (su, sv) = self.uv_scale;
+        let dx = (diff.dudx * su, diff.dvdx * sv);
+        let dy = (diff.dudy * su, diff.dvdy * sv);
+
+        let size = self.width.max(self.height) as f64;
+        let top = (self.data.levels.len() - 1) as f64;
+        match self.mip_mode {
+            //the full resolution image, what get_color() samples
+            MipMode::None => self.sample_level(0, u, v, self.filter),
+            MipMode::Trilinear => {
+                let width = util::fmax(util::fmax(dx.0.abs(), dx.1.abs()), util::fmax(dy.0.abs(), dy.1.abs()));
+                let lod = (width * size).log2();
+                if lod <= 0.0 {
+                    //magnified: keep the chosen filter on the full image
+                    return self.sample_level(0, u, v, self.filter);
+                }
+                let lod = util::fmin(lod, top);
+                let lower = lod.floor();
+                let t = lod - lower;
+                let lower = lower as usize;
+                let upper = (lower + 1).min(top as usize);
+                self.sample_level(lower, u, v, FilterMode::Bilinear) * (1.0 - t)
+                    + self.sample_level(upper, u, v, FilterMode::Bilinear) * t
+            }
+            MipMode::Ewa => {
+                //make dx the major axis and limit the anisotropy like PBRT does
+                let (mut major, mut minor) = (dx, dy);
+                let len = |a: (f64, f64)| (a.0 * a.0 + a.1 * a.1).sqrt();
+                if len(minor) > len(major) {
+                    std::mem::swap(&mut major, &mut minor);
+                }
+                let major_len = len(major);
+                let mut minor_len = len(minor);
+                if minor_len * MAX_ANISOTROPY < major_len && minor_len > 0.0 {
+                    let scale = major_len / (minor_len * MAX_ANISOTROPY);
+                    minor = (minor.0 * scale, minor.1 * scale);
+                    minor_len *= scale;
+                }
+                if minor_len == 0.0 {
+                    return self.sample_level(0, u, v, FilterMode::Bilinear);
+                }
+                let lod = util::fmax(0.0, util::fmin((minor_len * size).log2(), top));
+                let lower = lod.floor();
+                let t = lod - lower;
+                let lower = lower as usize;
+                let upper = (lower + 1).min(top as usize);
+                self.ewa(lower, u, v, major, minor) * (1.0 - t) + self.ewa(upper, u, v, major, minor) * t
+            }
+        }
+    }
+
+    fn sample_level(&self, level: usize, u: f64, v: f64, filter: FilterMode) -> Vec3 {
+        let level = &self.data.levels[level];
+        let u_img = u * level.width as f64;
+        let v_img = (1.0 - v) * level.height as f64;
+        match filter {
+            FilterMode::Nearest => self.fetch(level, u_img.floor() as i64, v_img.floor() as i64),
+            FilterMode::Bilinear => {
+                //texel centers sit at +0.5
+                let x = u_img - 0.5;
+                let y = v_img - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                self.fetch(level, x0, y0) * ((1.0 - fx) * (1.0 - fy))
+                    + self.fetch(level, x0 + 1, y0) * (fx * (1.0 - fy))
+                    + self.fetch(level, x0, y0 + 1) * ((1.0 - fx) * fy)
+                    + self.fetch(level, x0 + 1, y0 + 1) * (fx * fy)
+            }
+            FilterMode::Bicubic => {
+                let x = u_img - 0.5;
+                let y = v_img - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let wx = Self::cubic_weights(x - x0);
+                let wy = Self::cubic_weights(y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let mut color = Vec3::zero();
+                for j in 0..4 {
+                    for i in 0..4 {
+                        color += self.fetch(level, x0 + i as i64 - 1, y0 + j as i64 - 1) * (wx[i] * wy[j]);
+                    }
+                }
+                //Catmull-Rom can undershoot below black next to bright texels, HDR data may go above 1
+                Vec3::new(color.x.max(0.0), color.y.max(0.0), color.z.max(0.0))
+            }
+        }
+    }
+
+    //elliptically weighted average with a gaussian over the footprint ellipse spanned by the two axes (in uv)
+    fn ewa(&self, level: usize, u: f64, v: f64, axis0: (f64, f64), axis1: (f64, f64)) -> Vec3 {
+        let level = &self.data.levels[level];
+        let (w, h) = (level.width as f64, level.height as f64);
+        //to texel space, t grows downwards
+        let s0 = u * w - 0.5;
+        let t0 = (1.0 - v) * h - 0.5;
+        let d0 = (axis0.0 * w, -axis0.1 * h);
+        let d1 = (axis1.0 * w, -axis1.1 * h);
+
+        let mut a = d0.1 * d0.1 + d1.1 * d1.1 + 1.0;
+        let mut b = -2.0 * (d0.0 * d0.1 + d1.0 * d1.1);
+        let mut c = d0.0 * d0.0 + d1.0 * d1.0 + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        //bounding box of the ellipse
+        let det = -b * b + 4.0 * a * c;
+        let inv_det = 1.0 / det;
+        let u_sqrt = (det * c).sqrt();
+        let v_sqrt = (a * det).sqrt();
+        let s_min = (s0 - 2.0 * inv_det * u_sqrt).ceil() as i64;
+        let s_max = (s0 + 2.0 * inv_det * u_sqrt).floor() as i64;
+        let t_min = (t0 - 2.0 * inv_det * v_sqrt).ceil() as i64;
+        let t_max = (t0 + 2.0 * inv_det * v_sqrt).floor() as i64;
+
+        let mut sum = Vec3::zero();
+        let mut sum_weights = 0.0;
+        for it in t_min..=t_max {
+            let tt = it as f64 - t0;
+            for is in s_min..=s_max {
+                let ss = is as f64 - s0;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1.0 {
+                    let weight = (-2.0 * r2).exp() - (-2.0f64).exp();
+                    sum += self.fetch(level, is, it) * weight;
+                    sum_weights += weight;
+                }
+            }
+        }
+        if sum_weights <= 0.0 {
+            return self.fetch(level, s0.round() as i64, t0.round() as i64);
+        }
+        sum / sum_weights
     }
 }
 
@@ -104,6 +622,25 @@ impl texture for Image {
             return Vec3::new(0.0, 1.0, 1.0);
         }
         let org_color = self.get_color(u, v);
+        if self.data.linear {
+            return org_color;
+        }
+
+        //Adjust the color to right gamma
+        Vec3::new(
+            org_color.x * org_color.x(),
+            org_color.y * org_color.y(),
+            org_color.z * org_color.z(),
+        )
+    }
+    fn value_at(&self, rec: &hit_record) -> Vec3 {
+        if self.width == 0 || self.height == 0 {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        let org_color = self.get_color_filtered(rec.u, rec.v, &rec.uv_diff);
+        if self.data.linear {
+            return org_color;
+        }
 
         //Adjust the color to right gamma
         Vec3::new(
@@ -112,18 +649,31 @@ impl texture for Image {
             org_color.z * org_color.z(),
         )
     }
+    fn alpha(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        if self.width == 0 || self.height == 0 {
+            return 1.0;
+        }
+        self.get_alpha(u, v)
+    }
 }
 
 pub struct Noise {
-    noise: perlin,
+    noise: Arc<dyn noise_source>,
     scale: f64,
+    //the 4th dimension of the noise, changing it from frame to frame makes the marble flow
+    pub time: f64,
 }
 
 impl Noise {
     pub fn new(scale: f64) -> Self {
+        Self::new_with_noise(Arc::new(perlin::new()), scale)
+    }
+    //the same marble from another noise, e.g. a seeded improved_perlin or open_simplex
+    pub fn new_with_noise(noise: Arc<dyn noise_source>, scale: f64) -> Self {
         Self {
-            noise: perlin::new(),
+            noise,
             scale,
+            time: 0.0,
         }
     }
 }
@@ -131,6 +681,6 @@ impl Noise {
 impl texture for Noise {
     fn value(&self, u: f64, v: f64, p: &Vec3) -> Vec3 {
         // Vec3::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + self.noise.noise(&(*p * self.scale)))
-        Vec3::new(0.5, 0.5, 0.5) * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin())
+        Vec3::new(0.5, 0.5, 0.5) * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb4(p, self.time, 7)).sin())
     }
 }
\ No newline at end of file
@@ -170,6 +170,36 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
+//以单位向量w为轴的正交基，返回与w垂直的两个单位向量(u, v)
+pub fn orthonormal_basis(w: Vec3) -> (Vec3, Vec3) {
+    let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let u = w.cross(a).unit_vector();
+    let v = w.cross(u);
+    (u, v)
+}
+
+//以w为轴、半角余弦为cos_max的圆锥内均匀随机方向
+pub fn random_in_cone(w: Vec3, cos_max: f64) -> Vec3 {
+    let (u, v) = orthonormal_basis(w);
+    let cos_theta = 1.0 - random_f64_0_1() * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * random_f64_0_1();
+    u * (phi.cos() * sin_theta) + v * (phi.sin() * sin_theta) + w * cos_theta
+}
+
+//把若干个浮点数散列成[0, 1)中的数(splitmix64)，同样的输入总得到同样的结果
+pub fn hash_to_unit(values: &[f64]) -> f64 {
+    let mut h: u64 = 0x9E3779B97F4A7C15;
+    for value in values {
+        h ^= value.to_bits();
+        h = h.wrapping_add(0x9E3779B97F4A7C15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94D049BB133111EB);
+        h ^= h >> 31;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
 //0-1截断函数
 pub fn cut(x: f64) -> f64 {
     if x > 0.99 {
@@ -0,0 +1,895 @@
+use crate::Vec3;
+use crate::materials::{material, lambertian, alpha_mask};
+use crate::textures::{Image, TextureRegistry};
+use crate::voxels::{Block, VoxelGrid};
+use flate2::read::GzDecoder;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::sync::Arc;
+
+//little helper over a byte slice, panics on truncated files like Image::new does on bad images
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+        }
+    }
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+    fn take(&mut self, n: usize) -> &'a [u8] {
+        if self.remaining() < n {
+            panic!("Unexpected end of voxel file at byte {}", self.pos);
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        slice
+    }
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+    fn i32_le(&mut self) -> i32 {
+        i32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+    fn i16_be(&mut self) -> i16 {
+        i16::from_be_bytes(self.take(2).try_into().unwrap())
+    }
+    fn i32_be(&mut self) -> i32 {
+        i32::from_be_bytes(self.take(4).try_into().unwrap())
+    }
+    fn i64_be(&mut self) -> i64 {
+        i64::from_be_bytes(self.take(8).try_into().unwrap())
+    }
+    //strings in .vox dictionaries: i32 length + bytes
+    fn string_le(&mut self) -> String {
+        let len = self.i32_le().max(0) as usize;
+        String::from_utf8_lossy(self.take(len)).into_owned()
+    }
+    //strings in NBT: u16 length + (modified) utf-8
+    fn string_be(&mut self) -> String {
+        let len = self.i16_be() as u16 as usize;
+        String::from_utf8_lossy(self.take(len)).into_owned()
+    }
+}
+
+/*
+** MagicaVoxel .vox
+*/
+
+pub struct VoxModel {
+    //per model: voxels as (x, y, z, color index) in MagicaVoxel's z-up space, plus the model's translation
+    pub models: Vec<(Vec<[u8; 4]>, [i32; 3])>,
+    //palette[i] is the color of color index i, index 0 is unused
+    pub palette: [[u8; 4]; 256],
+}
+
+impl VoxModel {
+    pub fn load(filename: &str) -> Self {
+        let data = std::fs::read(filename).expect("Vox reading error!");
+        Self::parse(&data)
+    }
+    pub fn parse(data: &[u8]) -> Self {
+        let mut reader = ByteReader::new(data);
+        if reader.take(4) != b"VOX " {
+            panic!("Not a MagicaVoxel file");
+        }
+        let _version = reader.i32_le();
+
+        let mut sizes: Vec<[i32; 3]> = Vec::new();
+        let mut models: Vec<Vec<[u8; 4]>> = Vec::new();
+        let mut palette = Self::default_palette();
+        //scene graph nodes, only used to find where each model is placed
+        let mut transforms: HashMap<i32, ([i32; 3], i32)> = HashMap::new();
+        let mut groups: HashMap<i32, Vec<i32>> = HashMap::new();
+        let mut shapes: HashMap<i32, Vec<i32>> = HashMap::new();
+
+        while reader.remaining() >= 12 {
+            let id = reader.take(4);
+            let content_size = reader.i32_le() as usize;
+            let _children_size = reader.i32_le();
+            if id == b"MAIN" {
+                //MAIN has no content of its own, its children follow directly
+                continue;
+            }
+            let mut chunk = ByteReader::new(reader.take(content_size));
+            match id {
+                b"SIZE" => sizes.push([chunk.i32_le(), chunk.i32_le(), chunk.i32_le()]),
+                b"XYZI" => {
+                    let n = chunk.i32_le().max(0) as usize;
+                    let mut voxels = Vec::with_capacity(n);
+                    for _ in 0..n {
+                        let v = chunk.take(4);
+                        voxels.push([v[0], v[1], v[2], v[3]]);
+                    }
+                    models.push(voxels);
+                }
+                b"RGBA" => {
+                    for i in 0..255 {
+                        let c = chunk.take(4);
+                        palette[i + 1] = [c[0], c[1], c[2], c[3]];
+                    }
+                }
+                b"nTRN" => {
+                    let node_id = chunk.i32_le();
+                    Self::read_dict(&mut chunk);
+                    let child = chunk.i32_le();
+                    let _reserved = chunk.i32_le();
+                    let _layer = chunk.i32_le();
+                    let frames = chunk.i32_le();
+                    let mut translation = [0, 0, 0];
+                    //rotations (_r) are ignored, only the first frame's translation is used
+                    for frame in 0..frames {
+                        let dict = Self::read_dict(&mut chunk);
+                        if frame == 0 {
+                            if let Some(t) = dict.get("_t") {
+                                let values: Vec<i32> = t.split_whitespace().filter_map(|s| s.parse().ok()).collect();
+                                if values.len() == 3 {
+                                    translation = [values[0], values[1], values[2]];
+                                }
+                            }
+                        }
+                    }
+                    transforms.insert(node_id, (translation, child));
+                }
+                b"nGRP" => {
+                    let node_id = chunk.i32_le();
+                    Self::read_dict(&mut chunk);
+                    let n = chunk.i32_le();
+                    let children = (0..n).map(|_| chunk.i32_le()).collect();
+                    groups.insert(node_id, children);
+                }
+                b"nSHP" => {
+                    let node_id = chunk.i32_le();
+                    Self::read_dict(&mut chunk);
+                    let n = chunk.i32_le();
+                    let mut model_ids = Vec::new();
+                    for _ in 0..n {
+                        model_ids.push(chunk.i32_le());
+                        Self::read_dict(&mut chunk);
+                    }
+                    shapes.insert(node_id, model_ids);
+                }
+                _ => {}
+            }
+        }
+
+        //place every model: voxel coordinates are relative to the model's center
+        let mut offsets = vec![[0, 0, 0]; models.len()];
+        if transforms.contains_key(&0) {
+            let mut stack = vec![(0, [0, 0, 0])];
+            //a broken file can link nodes into a cycle
+            let mut visited = HashSet::new();
+            while let Some((node, acc)) = stack.pop() {
+                if !visited.insert(node) {
+                    continue;
+                }
+                if let Some((t, child)) = transforms.get(&node) {
+                    stack.push((*child, [acc[0] + t[0], acc[1] + t[1], acc[2] + t[2]]));
+                } else if let Some(children) = groups.get(&node) {
+                    for child in children {
+                        stack.push((*child, acc));
+                    }
+                } else if let Some(model_ids) = shapes.get(&node) {
+                    for model in model_ids {
+                        let m = *model as usize;
+                        if m < sizes.len() {
+                            let size = sizes[m];
+                            offsets[m] = [acc[0] - size[0] / 2, acc[1] - size[1] / 2, acc[2] - size[2] / 2];
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            models: models.into_iter().zip(offsets).collect(),
+            palette,
+        }
+    }
+    fn read_dict(chunk: &mut ByteReader) -> HashMap<String, String> {
+        let n = chunk.i32_le();
+        let mut dict = HashMap::new();
+        for _ in 0..n {
+            let key = chunk.string_le();
+            let value = chunk.string_le();
+            dict.insert(key, value);
+        }
+        dict
+    }
+    //MagicaVoxel's built-in palette, used when the file has no RGBA chunk
+    fn default_palette() -> [[u8; 4]; 256] {
+        let mut palette = [[0, 0, 0, 0]; 256];
+        let levels = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+        let mut index = 1;
+        for r in levels {
+            for g in levels {
+                for b in levels {
+                    if index < 216 {
+                        palette[index] = [r, g, b, 0xff];
+                        index += 1;
+                    }
+                }
+            }
+        }
+        let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+        for channel in 0..4 {
+            for value in ramp {
+                palette[index] = match channel {
+                    0 => [0, 0, value, 0xff],
+                    1 => [0, value, 0, 0xff],
+                    2 => [value, 0, 0, 0xff],
+                    _ => [value, value, value, 0xff],
+                };
+                index += 1;
+            }
+        }
+        palette
+    }
+
+    //build a grid of colored lambertian blocks; MagicaVoxel is z-up, so (x, y, z) becomes (x, z, -y)
+    pub fn to_voxel_grid(&self, voxel_size: f64) -> VoxelGrid {
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for (voxels, offset) in self.models.iter() {
+            for v in voxels {
+                let cell = Self::to_cell(v, offset);
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(cell[axis]);
+                    max[axis] = max[axis].max(cell[axis] + 1);
+                }
+            }
+        }
+        if min[0] > max[0] {
+            min = [0, 0, 0];
+            max = [1, 1, 1];
+        }
+
+        let mut grid = VoxelGrid::new(min, max, voxel_size);
+        let mut ids: HashMap<u8, u16> = HashMap::new();
+        for (voxels, offset) in self.models.iter() {
+            for v in voxels {
+                let color_index = v[3];
+                let id = *ids.entry(color_index).or_insert_with(|| {
+                    let c = self.palette[color_index as usize];
+                    //same gamma as the image textures
+                    let albedo = Vec3::new(c[0] as f64 / 255.0, c[1] as f64 / 255.0, c[2] as f64 / 255.0);
+                    let albedo = Vec3::new(albedo.x * albedo.x, albedo.y * albedo.y, albedo.z * albedo.z);
+                    grid.register_block(Block::new(Arc::new(lambertian::new(albedo))))
+                });
+                let cell = Self::to_cell(v, offset);
+                grid.set(cell[0], cell[1], cell[2], id);
+            }
+        }
+        grid
+    }
+    fn to_cell(v: &[u8; 4], offset: &[i32; 3]) -> [i32; 3] {
+        let x = v[0] as i32 + offset[0];
+        let y = v[1] as i32 + offset[1];
+        let z = v[2] as i32 + offset[2];
+        [x, z, -y]
+    }
+}
+
+/*
+** Minecraft NBT, Sponge .schem and vanilla structure .nbt
+*/
+
+#[derive(Clone, Debug)]
+pub enum Nbt {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(HashMap<String, Nbt>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    //reads a (possibly gzip-compressed) NBT file, returns the root compound
+    pub fn load(filename: &str) -> Self {
+        let raw = std::fs::read(filename).expect("NBT reading error!");
+        Self::parse(&raw)
+    }
+    pub fn parse(raw: &[u8]) -> Self {
+        let data = if raw.len() >= 2 && raw[0] == 0x1f && raw[1] == 0x8b {
+            let mut decoded = Vec::new();
+            GzDecoder::new(&raw[..]).read_to_end(&mut decoded).expect("NBT decompression error!");
+            decoded
+        } else {
+            raw.to_vec()
+        };
+        let mut reader = ByteReader::new(&data);
+        let tag = reader.u8();
+        let _name = reader.string_be();
+        Self::read_payload(&mut reader, tag)
+    }
+    fn read_payload(reader: &mut ByteReader, tag: u8) -> Self {
+        match tag {
+            0 => Nbt::End,
+            1 => Nbt::Byte(reader.u8() as i8),
+            2 => Nbt::Short(reader.i16_be()),
+            3 => Nbt::Int(reader.i32_be()),
+            4 => Nbt::Long(reader.i64_be()),
+            5 => Nbt::Float(f32::from_bits(reader.i32_be() as u32)),
+            6 => Nbt::Double(f64::from_bits(reader.i64_be() as u64)),
+            7 => {
+                let n = reader.i32_be().max(0) as usize;
+                Nbt::ByteArray(reader.take(n).iter().map(|b| *b as i8).collect())
+            }
+            8 => Nbt::String(reader.string_be()),
+            9 => {
+                let element = reader.u8();
+                let n = reader.i32_be().max(0) as usize;
+                //an empty list may say it holds End tags, which take no bytes
+                if element == 0 {
+                    return Nbt::List(Vec::new());
+                }
+                //every other element takes at least one byte
+                if n > reader.remaining() {
+                    panic!("NBT list of {} elements at byte {} is longer than the file", n, reader.pos);
+                }
+                Nbt::List((0..n).map(|_| Self::read_payload(reader, element)).collect())
+            }
+            10 => {
+                let mut map = HashMap::new();
+                loop {
+                    let element = reader.u8();
+                    if element == 0 {
+                        break;
+                    }
+                    let name = reader.string_be();
+                    map.insert(name, Self::read_payload(reader, element));
+                }
+                Nbt::Compound(map)
+            }
+            11 => {
+                let n = reader.i32_be().max(0);
+                Nbt::IntArray((0..n).map(|_| reader.i32_be()).collect())
+            }
+            12 => {
+                let n = reader.i32_be().max(0);
+                Nbt::LongArray((0..n).map(|_| reader.i64_be()).collect())
+            }
+            _ => panic!("Unknown NBT tag {}", tag),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(map) => map.get(key),
+            _ => None,
+        }
+    }
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            Nbt::Byte(x) => Some(*x as i32),
+            Nbt::Short(x) => Some(*x as i32),
+            Nbt::Int(x) => Some(*x),
+            Nbt::Long(x) => Some(*x as i32),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Nbt::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    pub fn as_list(&self) -> Option<&Vec<Nbt>> {
+        match self {
+            Nbt::List(list) => Some(list),
+            _ => None,
+        }
+    }
+}
+
+//a block state as stored in structure files, e.g. minecraft:chest with facing=west
+#[derive(Clone, Debug)]
+pub struct BlockState {
+    pub name: String,
+    pub properties: HashMap<String, String>,
+}
+
+impl BlockState {
+    //parses "minecraft:oak_log[axis=x]"
+    pub fn parse(state: &str) -> Self {
+        let (name, props) = match state.find('[') {
+            Some(i) => (&state[..i], state[i + 1..].trim_end_matches(']')),
+            None => (state, ""),
+        };
+        let mut properties = HashMap::new();
+        for pair in props.split(',') {
+            if let Some((k, v)) = pair.split_once('=') {
+                properties.insert(k.trim().to_owned(), v.trim().to_owned());
+            }
+        }
+        Self {
+            name: name.to_owned(),
+            properties,
+        }
+    }
+    //name without the namespace
+    pub fn short_name(&self) -> &str {
+        match self.name.split_once(':') {
+            Some((_, name)) => name,
+            None => &self.name,
+        }
+    }
+    pub fn is_air(&self) -> bool {
+        matches!(self.short_name(), "air" | "cave_air" | "void_air" | "structure_void")
+    }
+}
+
+//block size in blocks and the block states, indexed (x, y, z)
+pub struct Structure {
+    pub size: [i32; 3],
+    pub palette: Vec<BlockState>,
+    pub blocks: Vec<([i32; 3], usize)>,
+}
+
+impl Structure {
+    //Sponge schematic, versions 2 and 3
+    pub fn load_schem(filename: &str) -> Self {
+        Self::new_from_schem(&Nbt::load(filename))
+    }
+    pub fn new_from_schem(root: &Nbt) -> Self {
+        //v3 wraps everything in a "Schematic" compound and moves palette and data into "Blocks"
+        let root = root.get("Schematic").unwrap_or(root);
+        let size = [
+            root.get("Width").and_then(|x| x.as_i32()).expect("Schematic without Width") as u16 as i32,
+            root.get("Height").and_then(|x| x.as_i32()).expect("Schematic without Height") as u16 as i32,
+            root.get("Length").and_then(|x| x.as_i32()).expect("Schematic without Length") as u16 as i32,
+        ];
+        if size.iter().any(|x| *x <= 0) {
+            panic!("Schematic with an empty size {:?}", size);
+        }
+        let container = root.get("Blocks").unwrap_or(root);
+        let palette_nbt = container.get("Palette").expect("Schematic without Palette");
+        let data = match container.get("BlockData").or_else(|| container.get("Data")) {
+            Some(Nbt::ByteArray(data)) => data,
+            _ => panic!("Schematic without block data"),
+        };
+
+        let mut palette = Vec::new();
+        let mut index_of_id: HashMap<i32, usize> = HashMap::new();
+        if let Nbt::Compound(map) = palette_nbt {
+            for (state, id) in map {
+                if let Some(id) = id.as_i32() {
+                    index_of_id.insert(id, palette.len());
+                    palette.push(BlockState::parse(state));
+                }
+            }
+        }
+
+        //block ids are stored in (y, z, x) order
+        let mut blocks = Vec::new();
+        for (index, value) in Self::decode_varints(data).into_iter().enumerate() {
+            let index = index as i32;
+            let x = index % size[0];
+            let z = (index / size[0]) % size[2];
+            let y = index / (size[0] * size[2]);
+            if let Some(p) = index_of_id.get(&value) {
+                blocks.push(([x, y, z], *p));
+            }
+        }
+
+        Self {
+            size,
+            palette,
+            blocks,
+        }
+    }
+
+    //Sponge block data: one varint per block, 7 bits per byte with the low bits first, at most 5 bytes
+    fn decode_varints(data: &[i8]) -> Vec<i32> {
+        let mut values = Vec::new();
+        let mut value = 0;
+        let mut length = 0;
+        for (i, byte) in data.iter().enumerate() {
+            let byte = *byte as u8;
+            value |= ((byte & 0x7f) as i32) << (7 * length);
+            length += 1;
+            if byte & 0x80 == 0 {
+                values.push(value);
+                value = 0;
+                length = 0;
+            } else if length == 5 {
+                panic!("Bad schematic block data: varint longer than 5 bytes at byte {}", i);
+            }
+        }
+        //a varint cut off by the end of the data is dropped, the block stays air
+        values
+    }
+
+    //vanilla structure block .nbt files
+    pub fn load_nbt(filename: &str) -> Self {
+        Self::new_from_structure(&Nbt::load(filename))
+    }
+    pub fn new_from_structure(root: &Nbt) -> Self {
+        let size_list = root.get("size").and_then(|x| x.as_list()).expect("Structure without size");
+        let size: Vec<i32> = size_list.iter().filter_map(|x| x.as_i32()).collect();
+        if size.len() != 3 || size.iter().any(|x| *x <= 0) {
+            panic!("Bad structure size {:?}, expected 3 positive numbers", size);
+        }
+        let size = [size[0], size[1], size[2]];
+        //files with several palettes (random variants) keep them in "palettes", we take the first one
+        let palette_nbt = match root.get("palette") {
+            Some(p) => p,
+            None => root.get("palettes").and_then(|x| x.as_list()).and_then(|x| x.first()).expect("Structure without palette"),
+        };
+        let mut palette = Vec::new();
+        for entry in palette_nbt.as_list().expect("Bad structure palette") {
+            let name = entry.get("Name").and_then(|x| x.as_str()).unwrap_or("minecraft:air").to_owned();
+            let mut properties = HashMap::new();
+            if let Some(Nbt::Compound(props)) = entry.get("Properties") {
+                for (k, v) in props {
+                    if let Some(v) = v.as_str() {
+                        properties.insert(k.clone(), v.to_owned());
+                    }
+                }
+            }
+            palette.push(BlockState { name, properties });
+        }
+
+        let mut blocks = Vec::new();
+        for block in root.get("blocks").and_then(|x| x.as_list()).expect("Structure without blocks") {
+            let pos = block.get("pos").and_then(|x| x.as_list());
+            let state = block.get("state").and_then(|x| x.as_i32());
+            if let (Some(pos), Some(state)) = (pos, state) {
+                if pos.len() < 3 {
+                    continue;
+                }
+                let pos = [pos[0].as_i32().unwrap_or(0), pos[1].as_i32().unwrap_or(0), pos[2].as_i32().unwrap_or(0)];
+                blocks.push((pos, state as usize));
+            }
+        }
+
+        Self {
+            size,
+            palette,
+            blocks,
+        }
+    }
+
+    pub fn load(filename: &str) -> Self {
+        if filename.ends_with(".nbt") {
+            Self::load_nbt(filename)
+        } else {
+            Self::load_schem(filename)
+        }
+    }
+
+    pub fn to_voxel_grid(&self, textures: &mut BlockTextures, voxel_size: f64) -> VoxelGrid {
+        let mut grid = VoxelGrid::new([0, 0, 0], self.size, voxel_size);
+        let mut ids: Vec<Option<u16>> = Vec::new();
+        for state in self.palette.iter() {
+            let id = textures.block(state).map(|block| grid.register_block(block));
+            ids.push(id);
+        }
+        for (pos, state) in self.blocks.iter() {
+            if let Some(Some(id)) = ids.get(*state) {
+                grid.set(pos[0], pos[1], pos[2], *id);
+            }
+        }
+        grid
+    }
+}
+
+//maps block names to the textures in the texture search paths (mytexture/ by default)
+//"oak_log" uses oak_log_top.png on its ends if there is one and oak_log.png elsewhere,
+//"chest" puts chest_front.png on the side it is facing, and so on
+pub struct BlockTextures {
+    materials: HashMap<String, Option<Arc<dyn material + Send + Sync>>>,
+    fallback: Arc<dyn material + Send + Sync>,
+    //block names whose bottom uses another block's texture
+    bottoms: HashMap<&'static str, &'static str>,
+    //texture names with transparent pixels, their blocks become cutouts
+    cutouts: HashSet<String>,
+    //blocks without any texture, drawn gray
+    pub missing: HashSet<String>,
+}
+
+impl BlockTextures {
+    pub fn new() -> Self {
+        let mut bottoms = HashMap::new();
+        bottoms.insert("grass_block", "dirt");
+        bottoms.insert("podzol", "dirt");
+        bottoms.insert("mycelium", "dirt");
+        Self {
+            materials: HashMap::new(),
+            fallback: Arc::new(lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+            bottoms,
+            cutouts: HashSet::new(),
+            missing: HashSet::new(),
+        }
+    }
+    //texture file -> material, each file is loaded only once
+    fn material(&mut self, name: &str) -> Option<Arc<dyn material + Send + Sync>> {
+        let filename = name.to_owned() + ".png";
+        self.materials.entry(filename.clone()).or_insert_with(|| {
+            if TextureRegistry::resolve(&filename).is_some() {
+                let tex = Arc::new(Image::new(&filename));
+                let mut mat: Arc<dyn material + Send + Sync> = Arc::new(lambertian::new_from_texture(tex.clone()));
+                if tex.data.has_alpha {
+                    mat = Arc::new(alpha_mask::new(mat, tex, 0.5));
+                    self.cutouts.insert(name.to_owned());
+                }
+                Some(mat)
+            } else {
+                None
+            }
+        }).clone()
+    }
+    fn first_material(&mut self, candidates: &[String]) -> Option<Arc<dyn material + Send + Sync>> {
+        candidates.iter().find_map(|name| self.material(name))
+    }
+
+    //None for air
+    pub fn block(&mut self, state: &BlockState) -> Option<Block> {
+        if state.is_air() {
+            return None;
+        }
+        let name = state.short_name().to_owned();
+        let base = self.material(&name);
+        let side = self.first_material(&[name.clone() + "_side", name.clone()]);
+        let top = self.first_material(&[name.clone() + "_top", name.clone() + "_end", name.clone()]);
+        let bottom = match self.bottoms.get(name.as_str()) {
+            Some(other) => self.material(other),
+            None => self.first_material(&[name.clone() + "_bottom", name.clone() + "_top", name.clone() + "_end", name.clone()]),
+        };
+        let front = self.material(&(name.clone() + "_front"));
+
+        let side = match side.or(base) {
+            Some(side) => side,
+            None => {
+                self.missing.insert(state.name.clone());
+                return Some(Block::new(self.fallback.clone()));
+            }
+        };
+        let top = top.unwrap_or(side.clone());
+        let bottom = bottom.unwrap_or(top.clone());
+
+        //faces in the order +x, -x, +y, -y, +z, -z
+        let mut faces = [side.clone(), side.clone(), top.clone(), bottom.clone(), side.clone(), side.clone()];
+
+        //logs and pillars lie along their axis
+        match state.properties.get("axis").map(|s| s.as_str()) {
+            Some("x") => faces = [top.clone(), bottom.clone(), side.clone(), side.clone(), side.clone(), side.clone()],
+            Some("z") => faces = [side.clone(), side.clone(), side.clone(), side.clone(), top.clone(), bottom.clone()],
+            _ => {}
+        }
+        if let Some(front) = front {
+            let facing = match state.properties.get("facing").map(|s| s.as_str()) {
+                Some("east") => 0,
+                Some("west") => 1,
+                Some("north") => 5,
+                _ => 4,
+            };
+            faces[facing] = front;
+        }
+        let mut block = Block::new_from_faces(faces);
+        block.cutout = [name.clone(), name.clone() + "_side", name.clone() + "_top"].iter().any(|n| self.cutouts.contains(n));
+        Some(block)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    //NBT writers for building files in memory
+    fn string(s: &str) -> Vec<u8> {
+        let mut out = (s.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+    fn named(tag: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        out.extend(string(name));
+        out.extend_from_slice(payload);
+        out
+    }
+    fn compound(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut out: Vec<u8> = entries.concat();
+        out.push(0);
+        out
+    }
+    fn short(x: i16) -> Vec<u8> {
+        x.to_be_bytes().to_vec()
+    }
+    fn int(x: i32) -> Vec<u8> {
+        x.to_be_bytes().to_vec()
+    }
+    fn byte_array(bytes: &[u8]) -> Vec<u8> {
+        let mut out = int(bytes.len() as i32);
+        out.extend_from_slice(bytes);
+        out
+    }
+    fn int_list(values: &[i32]) -> Vec<u8> {
+        let mut out = vec![3];
+        out.extend(int(values.len() as i32));
+        for v in values {
+            out.extend(int(*v));
+        }
+        out
+    }
+    fn root(entries: &[Vec<u8>]) -> Vec<u8> {
+        named(10, "", &compound(entries))
+    }
+    fn schematic(width: i16, height: i16, length: i16, data: &[u8]) -> Nbt {
+        let palette = compound(&[
+            named(3, "minecraft:air", &int(0)),
+            named(3, "minecraft:stone", &int(1)),
+            named(3, "minecraft:oak_log[axis=x]", &int(300)),
+        ]);
+        Nbt::parse(&root(&[
+            named(2, "Width", &short(width)),
+            named(2, "Height", &short(height)),
+            named(2, "Length", &short(length)),
+            named(10, "Palette", &palette),
+            named(7, "BlockData", &byte_array(data)),
+        ]))
+    }
+
+    #[test]
+    fn gzip_compound() {
+        let data = root(&[
+            named(3, "answer", &int(42)),
+            named(8, "name", &string("house")),
+            named(9, "size", &int_list(&[3, 4, 5])),
+            named(4, "seed", &(-7i64).to_be_bytes()),
+            named(10, "nested", &compound(&[named(1, "flag", &[1])])),
+        ]);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let nbt = Nbt::parse(&encoder.finish().unwrap());
+
+        assert_eq!(nbt.get("answer").and_then(|x| x.as_i32()), Some(42));
+        assert_eq!(nbt.get("name").and_then(|x| x.as_str()), Some("house"));
+        let size: Vec<i32> = nbt.get("size").and_then(|x| x.as_list()).unwrap().iter().filter_map(|x| x.as_i32()).collect();
+        assert_eq!(size, vec![3, 4, 5]);
+        assert!(matches!(nbt.get("seed"), Some(Nbt::Long(-7))));
+        assert_eq!(nbt.get("nested").and_then(|x| x.get("flag")).and_then(|x| x.as_i32()), Some(1));
+    }
+
+    #[test]
+    fn list_of_end_tags_is_empty() {
+        let mut list = vec![0];
+        list.extend(int(i32::MAX));
+        let nbt = Nbt::parse(&root(&[named(9, "nothing", &list)]));
+        assert!(nbt.get("nothing").and_then(|x| x.as_list()).unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "is longer than the file")]
+    fn list_longer_than_file() {
+        let mut list = vec![1];
+        list.extend(int(i32::MAX));
+        Nbt::parse(&root(&[named(9, "bytes", &list)]));
+    }
+
+    #[test]
+    fn schematic_varint_palette() {
+        //2 x 1 x 2 blocks: stone, air, log (id 300 takes two bytes), stone
+        let structure = Structure::new_from_schem(&schematic(2, 1, 2, &[1, 0, 0xac, 0x02, 1]));
+        assert_eq!(structure.size, [2, 1, 2]);
+        let mut blocks: Vec<([i32; 3], &str)> = structure.blocks.iter().map(|(pos, p)| (*pos, structure.palette[*p].short_name())).collect();
+        blocks.sort();
+        assert_eq!(blocks, vec![([0, 0, 0], "stone"), ([0, 0, 1], "oak_log"), ([1, 0, 0], "air"), ([1, 0, 1], "stone")]);
+        let log = &structure.palette[structure.blocks[2].1];
+        assert_eq!(log.properties.get("axis").map(|s| s.as_str()), Some("x"));
+    }
+
+    #[test]
+    fn truncated_varint_is_dropped() {
+        assert_eq!(Structure::decode_varints(&[1, 0xac as u8 as i8, 0x02, 0x81u8 as i8]), vec![1, 300]);
+    }
+
+    #[test]
+    #[should_panic(expected = "longer than 5 bytes")]
+    fn overlong_varint() {
+        Structure::decode_varints(&[-1, -1, -1, -1, -1, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty size")]
+    fn schematic_without_width() {
+        Structure::new_from_schem(&schematic(0, 1, 2, &[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Bad structure size")]
+    fn structure_size_too_short() {
+        Structure::new_from_structure(&Nbt::parse(&root(&[named(9, "size", &int_list(&[2, 2]))])));
+    }
+
+    fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend((content.len() as i32).to_le_bytes());
+        out.extend(0i32.to_le_bytes());
+        out.extend_from_slice(content);
+        out
+    }
+    fn vox(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = b"VOX ".to_vec();
+        out.extend(150i32.to_le_bytes());
+        out.extend(chunk(b"MAIN", &[]));
+        for c in chunks {
+            out.extend_from_slice(c);
+        }
+        out
+    }
+
+    #[test]
+    fn vox_model() {
+        let size = [2i32, 3, 1].iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let mut xyzi = 2i32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1, 1, 2, 0, 2]);
+        let mut rgba = Vec::new();
+        for i in 0..256 {
+            rgba.extend([i as u8, 10, 20, 255]);
+        }
+        let model = VoxModel::parse(&vox(&[chunk(b"SIZE", &size), chunk(b"XYZI", &xyzi), chunk(b"RGBA", &rgba)]));
+
+        assert_eq!(model.models.len(), 1);
+        assert_eq!(model.models[0].0, vec![[0, 0, 0, 1], [1, 2, 0, 2]]);
+        //the chunk's first color is color index 1
+        assert_eq!(model.palette[1], [0, 10, 20, 255]);
+        assert_eq!(model.palette[2], [1, 10, 20, 255]);
+
+        //z-up to y-up: (1, 2, 0) is cell (1, 0, -2)
+        let grid = model.to_voxel_grid(1.0);
+        assert_ne!(grid.get(0, 0, 0), 0);
+        assert_ne!(grid.get(1, 0, -2), 0);
+        assert_eq!(grid.get(1, 0, 0), 0);
+    }
+
+    #[test]
+    fn vox_node_cycle() {
+        let size = [1i32, 1, 1].iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let mut xyzi = 1i32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1]);
+        //transform 0 -> group 1 -> transform 0
+        let mut transform = Vec::new();
+        for x in [0i32, 0, 1, -1, 0, 0] {
+            transform.extend(x.to_le_bytes());
+        }
+        let mut group = Vec::new();
+        for x in [1i32, 0, 1, 0] {
+            group.extend(x.to_le_bytes());
+        }
+        let model = VoxModel::parse(&vox(&[chunk(b"SIZE", &size), chunk(b"XYZI", &xyzi), chunk(b"nTRN", &transform), chunk(b"nGRP", &group)]));
+        assert_eq!(model.models.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected end of voxel file")]
+    fn truncated_vox() {
+        let mut xyzi = 2i32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1]);
+        VoxModel::parse(&vox(&[chunk(b"XYZI", &xyzi)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not a MagicaVoxel file")]
+    fn not_vox() {
+        VoxModel::parse(b"PNG nonsense");
+    }
+}
\ No newline at end of file
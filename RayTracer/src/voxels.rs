@@ -0,0 +1,380 @@
+pub use crate::ray::Ray;
+use crate::Vec3;
+pub use crate::hittables::{hit_record, hittable};
+use crate::materials::material;
+use crate::Interval;
+use std::sync::Arc;
+use crate::aabb::AABB;
+use crate::quads::quad;
+use crate::color;
+
+//The six faces of a block, in the same order as Block::faces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl BlockFace {
+    fn from_axis(axis: usize, positive: bool) -> Self {
+        match (axis, positive) {
+            (0, true) => BlockFace::PosX,
+            (0, false) => BlockFace::NegX,
+            (1, true) => BlockFace::PosY,
+            (1, false) => BlockFace::NegY,
+            (2, true) => BlockFace::PosZ,
+            _ => BlockFace::NegZ,
+        }
+    }
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+//A block type: one material per face
+pub struct Block {
+    pub faces: [Arc<dyn material + Send + Sync>; 6],
+    //see-through block like leaves: faces between two blocks of this type are drawn too
+    pub cutout: bool,
+}
+
+impl Block {
+    pub fn new(mat: Arc<dyn material + Send + Sync>) -> Self {
+        Self {
+            faces: [mat.clone(), mat.clone(), mat.clone(), mat.clone(), mat.clone(), mat],
+            cutout: false,
+        }
+    }
+    //grass-like blocks: different top, sides and bottom
+    pub fn new_top_side_bottom(top: Arc<dyn material + Send + Sync>, side: Arc<dyn material + Send + Sync>, bottom: Arc<dyn material + Send + Sync>) -> Self {
+        Self {
+            faces: [side.clone(), side.clone(), top, bottom, side.clone(), side],
+            cutout: false,
+        }
+    }
+    //faces in the order +x, -x, +y, -y, +z, -z
+    pub fn new_from_faces(faces: [Arc<dyn material + Send + Sync>; 6]) -> Self {
+        Self {
+            faces,
+            cutout: false,
+        }
+    }
+    pub fn face_material(&self, face: BlockFace) -> &Arc<dyn material + Send + Sync> {
+        &self.faces[face.index()]
+    }
+}
+
+//A block world stored as a dense 3D array of block ids, traversed with 3D-DDA (Amanatides & Woo)
+//id 0 is air, every other id indexes the palette of registered blocks
+pub struct VoxelGrid {
+    min_cell: [i32; 3],
+    dims: [usize; 3],
+    voxel_size: f64,
+    cells: Vec<u16>,
+    palette: Vec<Block>,
+
+    //bounding box
+    bbox: AABB,
+}
+
+impl VoxelGrid {
+    //cells cover [min, max) in integer block coordinates, each block is voxel_size wide
+    pub fn new(min: [i32; 3], max: [i32; 3], voxel_size: f64) -> Self {
+        let dims = [
+            (max[0] - min[0]).max(1) as usize,
+            (max[1] - min[1]).max(1) as usize,
+            (max[2] - min[2]).max(1) as usize,
+        ];
+        let lo = Vec3::new(min[0] as f64, min[1] as f64, min[2] as f64) * voxel_size;
+        let hi = lo + Vec3::new(dims[0] as f64, dims[1] as f64, dims[2] as f64) * voxel_size;
+        Self {
+            min_cell: min,
+            dims,
+            voxel_size,
+            cells: vec![0; dims[0] * dims[1] * dims[2]],
+            palette: Vec::new(),
+
+            bbox: AABB::new_from_points(lo, hi),
+        }
+    }
+    //returns the id to use with set()
+    pub fn register_block(&mut self, block: Block) -> u16 {
+        //id 0 is air, so there is room for u16::MAX blocks
+        assert!(self.palette.len() < u16::MAX as usize, "Too many block types, at most {}", u16::MAX);
+        self.palette.push(block);
+        self.palette.len() as u16
+    }
+    pub fn set(&mut self, x: i32, y: i32, z: i32, id: u16) {
+        assert!((id as usize) <= self.palette.len(), "Unregistered block id {}", id);
+        if let Some(index) = self.index(x - self.min_cell[0], y - self.min_cell[1], z - self.min_cell[2]) {
+            self.cells[index] = id;
+        }
+    }
+    pub fn get(&self, x: i32, y: i32, z: i32) -> u16 {
+        self.local_get(x - self.min_cell[0], y - self.min_cell[1], z - self.min_cell[2])
+    }
+    //fill the box [min, max) with one block id
+    pub fn fill(&mut self, min: [i32; 3], max: [i32; 3], id: u16) {
+        for x in min[0]..max[0] {
+            for y in min[1]..max[1] {
+                for z in min[2]..max[2] {
+                    self.set(x, y, z, id);
+                }
+            }
+        }
+    }
+
+    fn index(&self, i: i32, j: i32, k: i32) -> Option<usize> {
+        if i < 0 || j < 0 || k < 0 {
+            return None;
+        }
+        let (i, j, k) = (i as usize, j as usize, k as usize);
+        if i >= self.dims[0] || j >= self.dims[1] || k >= self.dims[2] {
+            return None;
+        }
+        Some((i * self.dims[1] + j) * self.dims[2] + k)
+    }
+    //cell lookup in grid-local coordinates, outside of the grid is air
+    fn local_get(&self, i: i32, j: i32, k: i32) -> u16 {
+        match self.index(i, j, k) {
+            Some(index) => self.cells[index],
+            None => 0,
+        }
+    }
+    fn grid_min(&self) -> Vec3 {
+        Vec3::new(self.bbox.x.tmin, self.bbox.y.tmin, self.bbox.z.tmin)
+    }
+
+    //uv on a block face, oriented the same way as the quads of quads::newbox
+    fn face_uv(local: Vec3, face: BlockFace) -> (f64, f64) {
+        match face {
+            BlockFace::PosZ => (local.x, local.y),
+            BlockFace::PosX => (1.0 - local.z, local.y),
+            BlockFace::NegZ => (1.0 - local.x, local.y),
+            BlockFace::NegX => (local.z, local.y),
+            BlockFace::PosY => (local.x, 1.0 - local.z),
+            BlockFace::NegY => (local.x, local.z),
+        }
+    }
+
+    //derivatives of the hit point along u and v for each face, matching face_uv
+    fn face_tangents(face: BlockFace) -> (Vec3, Vec3) {
+        match face {
+            BlockFace::PosZ => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            BlockFace::PosX => (Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0)),
+            BlockFace::NegZ => (Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            BlockFace::NegX => (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)),
+            BlockFace::PosY => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+            BlockFace::NegY => (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+        }
+    }
+
+    //hit point and uv on a face of the block in cell `cell`
+    fn face_hit(&self, r: &Ray, t: f64, cell: [i32; 3], face: BlockFace) -> (Vec3, f64, f64) {
+        let p = r.at(t);
+        let local = (p - self.grid_min()) / self.voxel_size
+                    - Vec3::new(cell[0] as f64, cell[1] as f64, cell[2] as f64);
+        let local = Vec3::new(local.x.clamp(0.0, 1.0), local.y.clamp(0.0, 1.0), local.z.clamp(0.0, 1.0));
+        let (u, v) = Self::face_uv(local, face);
+        (p, u, v)
+    }
+
+    //false if the face is cut out at the hit point
+    fn alpha_test(&self, r: &Ray, t: f64, cell: [i32; 3], id: u16, face: BlockFace) -> bool {
+        let (p, u, v) = self.face_hit(r, t, cell, face);
+        self.palette[id as usize - 1].face_material(face).alpha_test(u, v, &p)
+    }
+
+    fn is_cutout(&self, id: u16) -> bool {
+        id != 0 && self.palette[id as usize - 1].cutout
+    }
+
+    //fill the hit record for a face of the block in cell `cell`
+    fn set_hit(&self, r: &Ray, t: f64, cell: [i32; 3], id: u16, face: BlockFace, rec: &mut hit_record) {
+        let (p, u, v) = self.face_hit(r, t, cell, face);
+        rec.t = t;
+        rec.p = p;
+        rec.u = u;
+        rec.v = v;
+        let (dpdu, dpdv) = Self::face_tangents(face);
+        rec.dpdu = dpdu * self.voxel_size;
+        rec.dpdv = dpdv * self.voxel_size;
+
+        let mut outward_normal = Vec3::zero();
+        match face {
+            BlockFace::PosX => outward_normal.x = 1.0,
+            BlockFace::NegX => outward_normal.x = -1.0,
+            BlockFace::PosY => outward_normal.y = 1.0,
+            BlockFace::NegY => outward_normal.y = -1.0,
+            BlockFace::PosZ => outward_normal.z = 1.0,
+            BlockFace::NegZ => outward_normal.z = -1.0,
+        }
+        rec.set_face_normal(r, &outward_normal);
+        rec.mat = Arc::clone(self.palette[id as usize - 1].face_material(face));
+    }
+}
+
+impl hittable for VoxelGrid {
+    fn hit(&self, r: &Ray, ray_t: &Interval, rec: &mut hit_record) -> bool {
+        let origin = r.a_origin;
+        let dir = r.b_direction;
+        let grid_min = self.grid_min();
+
+        //Clip the ray against the grid bounds, remembering the axis we enter through
+        let mut t_enter = f64::NEG_INFINITY;
+        let mut t_exit = f64::INFINITY;
+        let mut enter_axis = 0;
+        for axis in 0..3 {
+            let interval = self.bbox.axis_interval(axis as u8);
+            let d = dir.lp(axis as u8);
+            let o = origin.lp(axis as u8);
+            if d.abs() < 1e-12 {
+                if o < interval.tmin || o > interval.tmax {
+                    return false;
+                }
+                continue;
+            }
+            let t0 = (interval.tmin - o) / d;
+            let t1 = (interval.tmax - o) / d;
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > t_enter {
+                t_enter = near;
+                enter_axis = axis;
+            }
+            if far < t_exit {
+                t_exit = far;
+            }
+        }
+        if t_enter > t_exit || t_exit <= ray_t.tmin || t_enter >= ray_t.tmax {
+            return false;
+        }
+
+        //Find the starting cell
+        let from_outside = t_enter > ray_t.tmin;
+        let t_start = if from_outside { t_enter } else { ray_t.tmin };
+        let start = (r.at(t_start) - grid_min) / self.voxel_size;
+        let mut cell = [0; 3];
+        for axis in 0..3 {
+            let c = start.lp(axis as u8).floor() as i32;
+            cell[axis] = c.clamp(0, self.dims[axis] as i32 - 1);
+        }
+
+        let mut current = self.local_get(cell[0], cell[1], cell[2]);
+        if from_outside && current != 0 {
+            //Entering the grid straight into a block
+            if !ray_t.surrounds(t_enter) {
+                return false;
+            }
+            let face = BlockFace::from_axis(enter_axis, dir.lp(enter_axis as u8) < 0.0);
+            if self.alpha_test(r, t_enter, cell, current, face) {
+                self.set_hit(r, t_enter, cell, current, face, rec);
+                return true;
+            }
+            //cut out, walk on from inside the block
+        }
+
+        //Set up the DDA
+        let mut step = [0; 3];
+        let mut t_max = [f64::INFINITY; 3];
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            let d = dir.lp(axis as u8);
+            if d > 0.0 {
+                step[axis] = 1;
+                let boundary = grid_min.lp(axis as u8) + (cell[axis] + 1) as f64 * self.voxel_size;
+                t_max[axis] = (boundary - origin.lp(axis as u8)) / d;
+                t_delta[axis] = self.voxel_size / d;
+            } else if d < 0.0 {
+                step[axis] = -1;
+                let boundary = grid_min.lp(axis as u8) + cell[axis] as f64 * self.voxel_size;
+                t_max[axis] = (boundary - origin.lp(axis as u8)) / d;
+                t_delta[axis] = -self.voxel_size / d;
+            }
+        }
+
+        //Walk the cells; a surface lies wherever two neighbouring cells hold different ids,
+        //or between two cutout blocks of the same type
+        loop {
+            let axis = if t_max[0] < t_max[1] {
+                if t_max[0] < t_max[2] { 0 } else { 2 }
+            } else if t_max[1] < t_max[2] {
+                1
+            } else {
+                2
+            };
+            let t = t_max[axis];
+            if t >= ray_t.tmax || t > t_exit + 1e-9 {
+                return false;
+            }
+
+            let prev_cell = cell;
+            cell[axis] += step[axis];
+            let next = self.local_get(cell[0], cell[1], cell[2]);
+
+            if (next != current || self.is_cutout(next)) && t > ray_t.tmin {
+                let (hit_cell, hit_id, face) = if next != 0 {
+                    //Entering a block through the face opposite to the step direction
+                    (cell, next, BlockFace::from_axis(axis, step[axis] < 0))
+                } else {
+                    //Leaving a block (e.g. glass) through the face in the step direction
+                    (prev_cell, current, BlockFace::from_axis(axis, step[axis] > 0))
+                };
+                if self.alpha_test(r, t, hit_cell, hit_id, face) {
+                    self.set_hit(r, t, hit_cell, hit_id, face, rec);
+                    return true;
+                }
+            }
+
+            if cell[axis] < 0 || cell[axis] >= self.dims[axis] as i32 {
+                return false;
+            }
+            current = next;
+            t_max[axis] += t_delta[axis];
+        }
+    }
+
+    fn bbox(&self) -> &AABB {
+        &self.bbox
+    }
+    //every visible face of a glowing block becomes a quad that light sampling can aim at
+    fn collect_emitters(&self, emitters: &mut Vec<Arc<dyn hittable + Send + Sync>>) {
+        let faces = [BlockFace::PosX, BlockFace::NegX, BlockFace::PosY, BlockFace::NegY, BlockFace::PosZ, BlockFace::NegZ];
+        let grid_min = self.grid_min();
+        for i in 0..self.dims[0] as i32 {
+            for j in 0..self.dims[1] as i32 {
+                for k in 0..self.dims[2] as i32 {
+                    let id = self.local_get(i, j, k);
+                    if id == 0 {
+                        continue;
+                    }
+                    let block = &self.palette[id as usize - 1];
+                    let cell_center = grid_min + Vec3::new(i as f64 + 0.5, j as f64 + 0.5, k as f64 + 0.5) * self.voxel_size;
+                    for face in faces {
+                        let mut normal = [0; 3];
+                        let axis = face.index() / 2;
+                        normal[axis] = if face.index() % 2 == 0 { 1 } else { -1 };
+                        //a face against an opaque block can never be seen, unless this block has holes
+                        //to look through
+                        let neighbour = self.local_get(i + normal[0], j + normal[1], k + normal[2]);
+                        if neighbour != 0 && !self.is_cutout(neighbour) && !block.cutout {
+                            continue;
+                        }
+                        let mat = block.face_material(face);
+                        let face_center = cell_center + Vec3::new(normal[0] as f64, normal[1] as f64, normal[2] as f64) * (self.voxel_size / 2.0);
+                        if color::luminance(mat.emitted(0.5, 0.5, &face_center)) <= 0.0 {
+                            continue;
+                        }
+                        //same uv layout as the face itself, dpdu x dpdv points outwards
+                        let (dpdu, dpdv) = Self::face_tangents(face);
+                        let (u, v) = (dpdu * self.voxel_size, dpdv * self.voxel_size);
+                        emitters.push(Arc::new(quad::new(face_center - (u + v) * 0.5, u, v, mat.clone())));
+                    }
+                }
+            }
+        }
+    }
+}
\ No newline at end of file